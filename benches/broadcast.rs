@@ -0,0 +1,151 @@
+// CPU time of fanning one dealing out to every peer stream
+// Compares encoding the dealing for every peer, which is what tonic does with a Dealing per
+// stream, with encoding it once and writing the same bytes to every stream. Also times a sealed
+// broadcast, where every peer gets its own dealing that has to be sealed, signed and encoded, to
+// show how much of that encoding accounts for.
+// Run with `cargo bench --bench broadcast`.
+
+// System
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+// Third Party
+use bytes::{Bytes, BytesMut};
+use prost::Message;
+use ring::aead::{self, Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305, NONCE_LEN};
+use ring::signature::Ed25519KeyPair;
+
+// Local
+#[allow(dead_code)]
+mod sample {
+    tonic::include_proto!("sample");
+}
+#[allow(dead_code)]
+#[path = "../src/wire.rs"]
+mod wire;
+
+use sample::{Dealing, DealingKind};
+use wire::EncodedDealing;
+
+const BROADCASTS: u32 = 2000;
+// Sealing and signing for every peer is much slower
+const SEALED_BROADCASTS: u32 = 50;
+
+// A dealing about the size of a Feldman VSS dealing with threshold commitments, sealed to a peer
+fn dealing(node_count: usize) -> Dealing {
+    let payload_size = node_count * 32 + 32 + 16;
+    Dealing {
+        dealing: vec![7; payload_size].into(),
+        protocol_round: 1,
+        public_key: vec![1; 32].into(),
+        signature: vec![2; 64].into(),
+        round_id: vec![3; 32].into(),
+        kind: DealingKind::Random as i32,
+        recipient: vec![4; 32].into(),
+    }
+}
+
+// What a peer stream does with every item: encode it into its buffer
+fn write_to_stream<M: Message>(item: &M, buffer: &mut BytesMut) {
+    buffer.clear();
+    item.encode(buffer).unwrap();
+    black_box(&buffer);
+}
+
+fn per_peer(dealing: &Dealing, peers: usize, buffer: &mut BytesMut) {
+    for _ in 0..peers {
+        let item = dealing.clone();
+        write_to_stream(&item, buffer);
+    }
+}
+
+fn encoded_once(dealing: &Dealing, peers: usize, buffer: &mut BytesMut) {
+    let encoded = EncodedDealing::new(dealing);
+    for _ in 0..peers {
+        let item = encoded.clone();
+        write_to_stream(&item, buffer);
+    }
+}
+
+// What Outbox::broadcast_sealed does for every peer: seal the peer's payload to it, sign the
+// dealing and encode it into the peer's stream
+fn sealed(
+    dealing: &Dealing,
+    plaintext: &[u8],
+    key: &LessSafeKey,
+    identity: &Ed25519KeyPair,
+    recipients: &[Bytes],
+    buffer: &mut BytesMut,
+) {
+    for recipient in recipients {
+        let mut sealed_dealing = dealing.clone();
+        sealed_dealing.recipient = recipient.clone();
+        let mut payload = plaintext.to_vec();
+        key.seal_in_place_append_tag(
+            Nonce::assume_unique_for_key([0; NONCE_LEN]),
+            Aad::from(&sealed_dealing.round_id),
+            &mut payload,
+        )
+        .unwrap();
+        sealed_dealing.dealing = payload.into();
+        sealed_dealing.signature = identity
+            .sign(&sealed_dealing.encode_to_vec())
+            .as_ref()
+            .to_vec()
+            .into();
+        write_to_stream(&sealed_dealing, buffer);
+    }
+}
+
+fn time(broadcasts: u32, broadcast: impl Fn(&mut BytesMut)) -> Duration {
+    let mut buffer = BytesMut::with_capacity(1 << 16);
+    // Warm up
+    for _ in 0..broadcasts / 10 {
+        broadcast(&mut buffer);
+    }
+    let start = Instant::now();
+    for _ in 0..broadcasts {
+        broadcast(&mut buffer);
+    }
+    start.elapsed() / broadcasts
+}
+
+fn main() {
+    for node_count in [75, 200] {
+        let dealing = dealing(node_count);
+        let peers = node_count - 1;
+        let per_peer = time(BROADCASTS, |buffer| {
+            per_peer(black_box(&dealing), peers, buffer)
+        });
+        let encoded_once = time(BROADCASTS, |buffer| {
+            encoded_once(black_box(&dealing), peers, buffer)
+        });
+        println!(
+            "{} nodes, {} byte dealing: {:?} per broadcast encoding per peer, {:?} encoding once",
+            node_count,
+            dealing.encoded_len(),
+            per_peer,
+            encoded_once
+        );
+        let key = LessSafeKey::new(UnboundKey::new(&CHACHA20_POLY1305, &[5; 32]).unwrap());
+        let identity = Ed25519KeyPair::from_seed_unchecked(&[6; 32]).unwrap();
+        let plaintext = vec![7; dealing.dealing.len() - NONCE_LEN - aead::MAX_TAG_LEN];
+        let recipients: Vec<Bytes> = (0..peers)
+            .map(|peer| Bytes::from((peer as u32).to_be_bytes().repeat(8)))
+            .collect();
+        let sealed = time(SEALED_BROADCASTS, |buffer| {
+            sealed(
+                black_box(&dealing),
+                &plaintext,
+                &key,
+                &identity,
+                &recipients,
+                buffer,
+            )
+        });
+        println!(
+            "{} nodes, sealed: {:?} per broadcast, of which encoding is {:?} and can't be shared",
+            node_count, sealed, per_peer
+        );
+    }
+}
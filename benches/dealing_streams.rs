@@ -0,0 +1,187 @@
+// Frames, bytes and CPU time of writing dealings to a ReceiveDealings stream
+// Encodes the dealings initial_dealing sends one peer, three per round, the way the Sample server
+// writes them to a dealing stream: every dealing in a frame of its own, one frame per round as
+// DEALING_BATCH_DELAY_MS coalesces a burst, and frames filled up to the default
+// DEALING_BATCH_MAX_BYTES for rounds sent back to back. Every frame costs a 5 byte gRPC header and
+// at least a 9 byte HTTP/2 DATA frame header on top of its dealings, and a write to the socket.
+// Then compares the bytes and CPU time of 64 KiB frames sent uncompressed, with gzip and with zstd.
+// Run with `cargo bench --bench dealing_streams`.
+
+// System
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+// Third Party
+use prost::Message;
+use ring::rand::{SecureRandom, SystemRandom};
+use tokio::runtime::{Builder, Runtime};
+use tonic::codec::{CompressionEncoding, ProstCodec, Streaming};
+use tonic::codegen::{Body, BoxFuture, BoxStream};
+use tonic::server::{Grpc, StreamingService};
+use tonic::Status;
+
+// Local
+#[allow(dead_code)]
+mod sample {
+    tonic::include_proto!("sample");
+}
+#[allow(dead_code)]
+#[path = "../src/wire.rs"]
+mod wire;
+
+use sample::{Dealing, DealingBatch, DealingKind};
+use wire::EncodedDealing;
+
+const ROUNDS: usize = 300;
+// What initial_dealing sends every peer per round by default
+const DEALINGS_PER_ROUND: usize = 3;
+// The DEALING_BATCH_MAX_BYTES default
+const MAX_BYTES: usize = 64 * 1024;
+const HTTP2_FRAME_HEADER_BYTES: usize = 9;
+const ITERATIONS: u32 = 20;
+
+// A random dealing for a committee of node_count, sealed to one peer. Its payload and signature
+// are as good as random bytes.
+fn dealing(node_count: usize, round: usize) -> EncodedDealing {
+    let random = SystemRandom::new();
+    let mut payload = vec![0; node_count * 32 + 32 + 16];
+    random.fill(&mut payload).unwrap();
+    let mut signature = vec![0; 64];
+    random.fill(&mut signature).unwrap();
+    EncodedDealing::new(&Dealing {
+        dealing: payload.into(),
+        protocol_round: 1,
+        public_key: vec![1; 32].into(),
+        signature: signature.into(),
+        round_id: (round as u64).to_be_bytes().repeat(4).into(),
+        kind: DealingKind::Random as i32,
+        recipient: vec![4; 32].into(),
+    })
+}
+
+// Coalesce queued dealings into frames of at most max_dealings that are sent once they reach
+// max_bytes, the way batch::batches does
+fn frames(dealings: &[EncodedDealing], max_dealings: usize, max_bytes: usize) -> Vec<DealingBatch> {
+    let mut frames = Vec::new();
+    let mut queued = dealings.iter();
+    while let Some(first) = queued.next() {
+        let mut size = first.encoded_len();
+        let mut batch = vec![first.clone()];
+        while batch.len() < max_dealings && size < max_bytes {
+            match queued.next() {
+                Some(dealing) => {
+                    size += dealing.encoded_len();
+                    batch.push(dealing.clone());
+                }
+                None => break,
+            }
+        }
+        frames.push(DealingBatch { dealings: batch });
+    }
+    frames
+}
+
+// Answers a dealing stream with the given frames
+#[derive(Clone)]
+struct Frames(Vec<DealingBatch>);
+
+impl StreamingService<DealingBatch> for Frames {
+    type Response = DealingBatch;
+    type ResponseStream = BoxStream<DealingBatch>;
+    type Future = BoxFuture<tonic::Response<Self::ResponseStream>, Status>;
+
+    fn call(&mut self, _request: tonic::Request<Streaming<DealingBatch>>) -> Self::Future {
+        let frames = self.0.clone();
+        Box::pin(async move {
+            let stream: BoxStream<DealingBatch> =
+                Box::pin(tokio_stream::iter(frames.into_iter().map(Ok)));
+            Ok(tonic::Response::new(stream))
+        })
+    }
+}
+
+// Encode the frames the way the Sample server writes them to a dealing stream, compressed with the
+// named encoding as COMPRESSION would. Returns the bytes of the stream body.
+async fn write(frames: &Frames, compression: Option<(&'static str, CompressionEncoding)>) -> usize {
+    let mut request = tonic::codegen::http::Request::builder()
+        .header("content-type", "application/grpc")
+        .body(tonic::transport::Body::empty())
+        .unwrap();
+    let mut grpc = Grpc::new(ProstCodec::<DealingBatch, DealingBatch>::default());
+    if let Some((name, compression)) = compression {
+        grpc = grpc.send_compressed(compression);
+        // tonic answers with the first encoding the peer accepts, so accept only this one
+        request.headers_mut().insert(
+            "grpc-accept-encoding",
+            tonic::codegen::http::HeaderValue::from_static(name),
+        );
+    }
+    let mut body = grpc.streaming(frames.clone(), request).await.into_body();
+    let mut bytes = 0;
+    while let Some(data) = body.data().await {
+        bytes += black_box(data.unwrap()).len();
+    }
+    bytes
+}
+
+// The bytes of the stream, HTTP/2 frame headers included, and the CPU time it takes to encode
+fn measure(
+    runtime: &Runtime,
+    frames: &Frames,
+    compression: Option<(&'static str, CompressionEncoding)>,
+) -> (usize, Duration) {
+    let body_bytes = runtime.block_on(write(frames, compression));
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        runtime.block_on(write(frames, compression));
+    }
+    let bytes = body_bytes + frames.0.len() * HTTP2_FRAME_HEADER_BYTES;
+    (bytes, start.elapsed() / ITERATIONS)
+}
+
+fn main() {
+    let runtime = Builder::new_current_thread().build().unwrap();
+    for node_count in [75, 200] {
+        let dealings: Vec<EncodedDealing> = (0..ROUNDS)
+            .flat_map(|round| (0..DEALINGS_PER_ROUND).map(move |_| dealing(node_count, round)))
+            .collect();
+        println!(
+            "{} nodes, {} dealings of {} bytes to one peer:",
+            node_count,
+            dealings.len(),
+            dealings[0].encoded_len()
+        );
+        for (mode, max_dealings, max_bytes) in [
+            ("unbatched", 1, usize::MAX),
+            ("one frame per round", DEALINGS_PER_ROUND, usize::MAX),
+            ("64 KiB frames", usize::MAX, MAX_BYTES),
+        ] {
+            let frames = Frames(frames(&dealings, max_dealings, max_bytes));
+            let (bytes, elapsed) = measure(&runtime, &frames, None);
+            println!(
+                "  {}: {} frames, {} bytes, {:?} to encode",
+                mode,
+                frames.0.len(),
+                bytes,
+                elapsed
+            );
+        }
+        let frames = Frames(frames(&dealings, usize::MAX, MAX_BYTES));
+        let (raw_bytes, _) = measure(&runtime, &frames, None);
+        for (name, compression) in [
+            ("none", None),
+            ("gzip", Some(CompressionEncoding::Gzip)),
+            ("zstd", Some(CompressionEncoding::Zstd)),
+        ] {
+            let compression = compression.map(|compression| (name, compression));
+            let (bytes, elapsed) = measure(&runtime, &frames, compression);
+            println!(
+                "  64 KiB frames, compression {}: {} bytes, {:.1}% of uncompressed, {:?} to encode",
+                name,
+                bytes,
+                bytes as f64 * 100.0 / raw_bytes as f64,
+                elapsed
+            );
+        }
+    }
+}
@@ -0,0 +1,214 @@
+// Dealing fan-out while one peer has stopped reading its stream
+// Compares the peer map before the copy-on-write registry, where every broadcast held the map's
+// write lock while it sent to each peer, with taking a snapshot and sending without any lock. A
+// full peer channel blocks the sender either way. What differs is who else waits: under the write
+// lock every other broadcast and every reader of the peer map waits for the stalled peer too.
+// Run with `cargo bench --bench peer_map`.
+
+// System
+use std::collections::BTreeMap;
+use std::hint::black_box;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Third Party
+use parking_lot::RwLock;
+use tokio::runtime::{Builder, Runtime};
+use tokio::sync::mpsc;
+
+// The same capacity as the peer streams' channels
+const CHANNEL_CAPACITY: usize = 1000;
+// Broadcasts run in spawn_blocking, so several run at once
+const BROADCASTERS: usize = 4;
+const BROADCASTS: usize = 1000;
+// How long the stalled peer stops reading its channel
+const STALL: Duration = Duration::from_millis(250);
+
+type Dealing = Arc<Vec<u8>>;
+
+struct Peer {
+    public_key: Vec<u8>,
+    dealing_sender: mpsc::Sender<Dealing>,
+}
+
+trait PeerMap: Send + Sync + 'static {
+    fn broadcast(&self, dealing: &Dealing);
+    fn public_keys(&self) -> Vec<Vec<u8>>;
+}
+
+// The registry before the copy-on-write change. Broadcasts went through with_map.
+struct LockedMap {
+    inner: RwLock<BTreeMap<Vec<u8>, Peer>>,
+}
+
+impl LockedMap {
+    fn with_map<F, T>(&self, func: F) -> T
+    where
+        F: FnOnce(&mut BTreeMap<Vec<u8>, Peer>) -> T,
+    {
+        let mut lock = self.inner.write();
+        func(&mut lock)
+    }
+}
+
+impl PeerMap for LockedMap {
+    fn broadcast(&self, dealing: &Dealing) {
+        self.with_map(|peers| {
+            for peer in peers.values() {
+                peer.dealing_sender.blocking_send(dealing.clone()).unwrap();
+            }
+        });
+    }
+
+    fn public_keys(&self) -> Vec<Vec<u8>> {
+        self.inner
+            .read()
+            .values()
+            .map(|peer| peer.public_key.clone())
+            .collect()
+    }
+}
+
+// The copy-on-write registry
+struct SnapshotMap {
+    inner: RwLock<Arc<BTreeMap<Vec<u8>, Arc<Peer>>>>,
+}
+
+impl SnapshotMap {
+    fn snapshot(&self) -> Arc<BTreeMap<Vec<u8>, Arc<Peer>>> {
+        self.inner.read().clone()
+    }
+}
+
+impl PeerMap for SnapshotMap {
+    fn broadcast(&self, dealing: &Dealing) {
+        for peer in self.snapshot().values() {
+            peer.dealing_sender.blocking_send(dealing.clone()).unwrap();
+        }
+    }
+
+    fn public_keys(&self) -> Vec<Vec<u8>> {
+        self.snapshot().keys().cloned().collect()
+    }
+}
+
+struct Measurement {
+    per_broadcast: Duration,
+    reads: usize,
+    slowest_read: Duration,
+}
+
+// Peers whose streams drain their channels, the first of which stalls for a while at the start
+fn peers(runtime: &Runtime, count: usize) -> Vec<Peer> {
+    (0..count)
+        .map(|index| {
+            let (dealing_sender, mut dealing_receiver) = mpsc::channel::<Dealing>(CHANNEL_CAPACITY);
+            runtime.spawn(async move {
+                if index == 0 {
+                    tokio::time::sleep(STALL).await;
+                }
+                while let Some(dealing) = dealing_receiver.recv().await {
+                    black_box(dealing);
+                }
+            });
+            Peer {
+                public_key: (index as u32).to_be_bytes().to_vec(),
+                dealing_sender,
+            }
+        })
+        .collect()
+}
+
+// Broadcast from several threads while another thread keeps reading the peer map the way the
+// inbound dealing router does
+fn measure(peer_map: Arc<dyn PeerMap>, dealing_size: usize) -> Measurement {
+    let dealing: Dealing = Arc::new(vec![7; dealing_size]);
+    let done = Arc::new(AtomicBool::new(false));
+    let reader = {
+        let peer_map = peer_map.clone();
+        let done = done.clone();
+        thread::spawn(move || {
+            let mut reads = 0;
+            let mut slowest_read = Duration::ZERO;
+            while !done.load(Ordering::Relaxed) {
+                let start = Instant::now();
+                black_box(peer_map.public_keys());
+                slowest_read = slowest_read.max(start.elapsed());
+                reads += 1;
+            }
+            (reads, slowest_read)
+        })
+    };
+    let start = Instant::now();
+    let broadcasters: Vec<_> = (0..BROADCASTERS)
+        .map(|_| {
+            let peer_map = peer_map.clone();
+            let dealing = dealing.clone();
+            thread::spawn(move || {
+                for _ in 0..BROADCASTS {
+                    peer_map.broadcast(&dealing);
+                }
+            })
+        })
+        .collect();
+    for broadcaster in broadcasters {
+        broadcaster.join().unwrap();
+    }
+    let elapsed = start.elapsed();
+    done.store(true, Ordering::Relaxed);
+    let (reads, slowest_read) = reader.join().unwrap();
+    Measurement {
+        per_broadcast: elapsed / (BROADCASTERS * BROADCASTS) as u32,
+        reads,
+        slowest_read,
+    }
+}
+
+fn main() {
+    // The peer streams are tasks on the node's runtime
+    let runtime = Builder::new_multi_thread()
+        .worker_threads(2)
+        .enable_time()
+        .build()
+        .unwrap();
+    for node_count in [75, 200] {
+        let peer_count = node_count - 1;
+        // About the size of a sealed Feldman VSS dealing with threshold commitments
+        let dealing_size = node_count * 32 + 32 + 16 + 200;
+        let locked = measure(
+            Arc::new(LockedMap {
+                inner: RwLock::new(
+                    peers(&runtime, peer_count)
+                        .into_iter()
+                        .map(|peer| (peer.public_key.clone(), peer))
+                        .collect(),
+                ),
+            }),
+            dealing_size,
+        );
+        let snapshot = measure(
+            Arc::new(SnapshotMap {
+                inner: RwLock::new(Arc::new(
+                    peers(&runtime, peer_count)
+                        .into_iter()
+                        .map(|peer| (peer.public_key.clone(), Arc::new(peer)))
+                        .collect(),
+                )),
+            }),
+            dealing_size,
+        );
+        for (design, measurement) in [("write lock", locked), ("snapshot", snapshot)] {
+            println!(
+                "{} nodes, one peer stalled for {:?}, {}: {:?} per broadcast, {} peer map reads, slowest read {:?}",
+                node_count,
+                STALL,
+                design,
+                measurement.per_broadcast,
+                measurement.reads,
+                measurement.slowest_read
+            );
+        }
+    }
+}
@@ -5,9 +5,15 @@ use std::{env, path::PathBuf};
 // using the library tonic
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    let mut config = prost_build::Config::new();
+    // Dealings are shared rather than copied whenever one is cloned, which a broadcast does for
+    // every peer
+    config.bytes([".sample.Dealing"]);
     tonic_build::configure()
         .file_descriptor_set_path(out_dir.join("sample_descriptor.bin"))
-        .compile(&["src/api.proto"], &["src"])?;
+        // Written to peer streams as it was encoded, see src/wire.rs
+        .extern_path(".sample.EncodedDealing", "crate::wire::EncodedDealing")
+        .compile_with_config(config, &["src/api.proto"], &["src"])?;
     // TODO: For production, remove the above two lines to remove the reflection endpoint
     // and instead use the below line to compile
     //tonic_build::compile_protos("src/api.proto")?;
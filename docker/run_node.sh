@@ -28,14 +28,4 @@ do
     sleep 1
 done
 
-until [ $(wc -l < all_peers_added.debug.txt) == $TOTAL_NODES ]
-do
-    sleep 1
-done
-
-until grpcurl -plaintext localhost:2323 sample.Sample/InitialDealing > /dev/null
-do
-    sleep 1
-done
-
 wait $server_pid
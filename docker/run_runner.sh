@@ -11,6 +11,7 @@ touch all_peers_added.debug.txt
 touch servers_running.debug.txt
 touch added_peer.debug.txt
 touch opening_complete.debug.txt
+touch round_failed.debug.txt
 touch spawned_all_dealing_requests.debug.txt
 touch inbound_dealing_received.debug.txt
 touch dealing_created.debug.txt
@@ -19,6 +20,7 @@ touch client_sent.debug.txt
 touch server_sent.debug.txt
 touch client_received.debug.txt
 touch server_received.debug.txt
+touch broadcast_micros.debug.txt
 
 # Build the node binary
 echo "Debug build..."
@@ -57,6 +59,17 @@ else
     echo "All nodes successfully finished adding peers."
 fi
 
+# Every node gets the same session nonce so that they all derive the same round IDs. It is fresh
+# for every run so that no round ID of an earlier run comes back.
+session_nonce=$(head -c 16 /dev/urandom | base64)
+for (( n=1; n<=$TOTAL_NODES; n++ ))
+do
+    until grpcurl -d "{\"session_nonce\": \"$session_nonce\"}" -plaintext "tokio-sample-node-$n:2323" sample.Sample/InitialDealing > /dev/null
+    do
+        sleep 1
+    done &
+done
+
 until [ $(wc -l < spawned_all_dealing_requests.debug.txt) == $TOTAL_NODES ]
 do
     sleep 1
@@ -68,6 +81,7 @@ let expected_sent="3 * $TOTAL_NODES * ($TOTAL_NODES - 1)"
 let expected_on_one_side="$expected_sent / 2"
 print_dealings_stats () {
     current_openings_complete=$(wc -l < opening_complete.debug.txt)
+    current_rounds_failed=$(wc -l < round_failed.debug.txt)
     current_dealings_created=$(wc -l < dealing_created.debug.txt)
     current_dealings_received=$(wc -l < inbound_dealing_received.debug.txt)
     current_dealings_sent=$(wc -l < dealing_sent.debug.txt)
@@ -82,10 +96,12 @@ print_dealings_stats () {
     $current_client_received / $expected_on_one_side signatures have been received on client side,
     $current_server_received / $expected_on_one_side signatures have been received on server side,
     $current_dealings_received / $expected_sent signatures have been received,
-    $current_openings_complete / $expected_openings signature rounds have completed"
+    $current_openings_complete / $expected_openings signature rounds have completed,
+    $current_rounds_failed signature rounds have missed their deadline"
 }
 
-until [ $(wc -l < opening_complete.debug.txt) == $expected_openings ]
+# Rounds that miss their deadline will never complete, so they count towards being finished
+until [ $(( $(wc -l < opening_complete.debug.txt) + $(wc -l < round_failed.debug.txt) )) -ge $expected_openings ]
 do
     print_dealings_stats
     sleep 1
@@ -93,7 +109,15 @@ done
 print_dealings_stats
 current_openings_complete=$(wc -l < opening_complete.debug.txt)
 echo "$current_openings_complete / $expected_openings siganture rounds have completed."
+current_rounds_failed=$(wc -l < round_failed.debug.txt)
+if [ $current_rounds_failed != 0 ]; then
+    echo "$current_rounds_failed signature rounds missed their deadline!"
+    exit 1
+fi
 echo "All nodes have completed an initial opening."
+# Time each node spent fanning a dealing out to all of its peers. Compare this across registry
+# designs and network sizes.
+awk '{ sum += $1; if ($1 > max) max = $1 } END { if (NR > 0) printf "Broadcast fan-out: %d broadcasts, mean %.0f us, max %d us\n", NR, sum / NR, max }' broadcast_micros.debug.txt
 
 # This is a check for a race condition where more than expected dealing openings happen
 sleep 5
@@ -0,0 +1,735 @@
+// System
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::mem::size_of;
+use std::time::{Duration, Instant};
+
+// Local
+use super::config::RetentionPolicy;
+use super::evidence::Equivocation;
+use super::types::{PublicKey, RoundId};
+use super::vss::JointShare;
+use crate::sample::Dealing;
+
+// How many pruned rounds are remembered so that late dealings don't start them over
+const TOMBSTONE_CAPACITY: usize = 10_000;
+
+// Everything the aggregator knows about one round that is still being tracked
+pub struct RoundState {
+    // The sender's dealing index within its session, kept for display
+    pub protocol_round: u32,
+    pub dealings: BTreeMap<PublicKey, Dealing>,
+    pub first_arrival: Instant,
+    pub last_arrival: Instant,
+    pub completed_at: Option<Instant>,
+    // Senders caught signing two different dealings for this round. Their dealings are ignored.
+    pub equivocators: BTreeSet<PublicKey>,
+    // An equivocator's dealing had already counted towards the round when it was caught, so the
+    // round's completion and output can't be trusted
+    pub tainted: bool,
+    // Senders whose dealings arrived after the round had already completed. Their dealings are
+    // only kept when stragglers are recorded.
+    pub stragglers: Vec<PublicKey>,
+    // My share of the round's joint secret, once the completed round has been combined
+    pub output: Option<JointShare>,
+    // Set when the round missed its deadline. Holds the senders whose dealings never arrived.
+    pub missing: Option<Vec<PublicKey>>,
+    // Whether the missing senders have been asked to send their dealings again
+    pub retransmit_requested: bool,
+    // The committee members that complained that each dealer's dealing is missing
+    pub complaints: BTreeMap<PublicKey, BTreeSet<PublicKey>>,
+    // The complainers each dealer answered by revealing its dealing to them
+    pub answered: BTreeMap<PublicKey, BTreeSet<PublicKey>>,
+    // Dealers a complaint proved to have dealt an invalid or unreadable share
+    pub convicted: BTreeSet<PublicKey>,
+    // Dealers excluded from the round's output because they were convicted or left a complaint
+    // unanswered
+    pub disqualified: BTreeSet<PublicKey>,
+}
+
+impl RoundState {
+    fn new(protocol_round: u32, now: Instant) -> Self {
+        Self {
+            protocol_round,
+            dealings: BTreeMap::new(),
+            first_arrival: now,
+            last_arrival: now,
+            completed_at: None,
+            equivocators: BTreeSet::new(),
+            tainted: false,
+            stragglers: Vec::new(),
+            output: None,
+            missing: None,
+            retransmit_requested: false,
+            complaints: BTreeMap::new(),
+            answered: BTreeMap::new(),
+            convicted: BTreeSet::new(),
+            disqualified: BTreeSet::new(),
+        }
+    }
+
+    pub fn is_failed(&self) -> bool {
+        self.missing.is_some()
+    }
+
+    fn approx_bytes(&self) -> usize {
+        size_of::<Self>()
+            + self
+                .dealings
+                .iter()
+                .map(|(public_key, dealing)| {
+                    public_key.len()
+                        + size_of::<Dealing>()
+                        + dealing.dealing.len()
+                        + dealing.public_key.len()
+                        + dealing.signature.len()
+                        + dealing.round_id.len()
+                })
+                .sum::<usize>()
+            + self.equivocators.iter().map(PublicKey::len).sum::<usize>()
+            + self.stragglers.iter().map(PublicKey::len).sum::<usize>()
+            + self
+                .missing
+                .iter()
+                .flatten()
+                .map(PublicKey::len)
+                .sum::<usize>()
+            + self
+                .complaints
+                .iter()
+                .chain(&self.answered)
+                .map(|(dealer, complainers)| {
+                    dealer.len() + complainers.iter().map(PublicKey::len).sum::<usize>()
+                })
+                .sum::<usize>()
+            + self.convicted.iter().map(PublicKey::len).sum::<usize>()
+            + self.disqualified.iter().map(PublicKey::len).sum::<usize>()
+    }
+}
+
+// A round that missed its deadline
+#[derive(Clone)]
+pub struct FailedRound {
+    pub round: RoundId,
+    pub protocol_round: u32,
+    pub received: usize,
+    pub missing: Vec<PublicKey>,
+    pub age: Duration,
+}
+
+// The summary of a completed round that outlives its dealings
+#[derive(Clone)]
+pub struct RoundResult {
+    pub contributors: Vec<PublicKey>,
+    pub output: Option<JointShare>,
+    // See RoundState
+    pub tainted: bool,
+}
+
+impl RoundResult {
+    fn approx_bytes(&self) -> usize {
+        size_of::<Self>() + self.contributors.iter().map(PublicKey::len).sum::<usize>()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AggregatorStats {
+    pub rounds_in_flight: usize,
+    pub rounds_retained: usize, // completed but not yet pruned
+    pub rounds_completed: u64,
+    pub rounds_failed: u64,
+    pub rounds_pruned: u64,
+    pub stragglers: u64,
+    pub equivocations: usize,
+    pub results_stored: usize,
+    pub approx_bytes: usize,
+}
+
+// Collects the dealings of every round until the round completes and then keeps them around
+// according to the retention policy
+pub struct Aggregator {
+    threshold: usize,
+    record_stragglers: bool,
+    policy: RetentionPolicy,
+    rounds: HashMap<RoundId, RoundState>,
+    results: HashMap<RoundId, RoundResult>,
+    // Insertion order of results so the oldest is evicted first
+    result_order: VecDeque<RoundId>,
+    // Rounds that were pruned, in the order they were pruned
+    tombstones: HashSet<RoundId>,
+    tombstone_order: VecDeque<RoundId>,
+    // Evidence is never pruned so that it can still be forwarded after its round is gone
+    equivocations: BTreeMap<(RoundId, PublicKey), Equivocation>,
+    rounds_completed: u64,
+    rounds_failed: u64,
+    rounds_pruned: u64,
+    stragglers: u64,
+}
+
+impl Aggregator {
+    pub fn new(threshold: u32, record_stragglers: bool, policy: RetentionPolicy) -> Self {
+        Self {
+            threshold: threshold as usize,
+            record_stragglers,
+            policy,
+            rounds: HashMap::new(),
+            results: HashMap::new(),
+            result_order: VecDeque::new(),
+            tombstones: HashSet::new(),
+            tombstone_order: VecDeque::new(),
+            equivocations: BTreeMap::new(),
+            rounds_completed: 0,
+            rounds_failed: 0,
+            rounds_pruned: 0,
+            stragglers: 0,
+        }
+    }
+
+    // Record a dealing. Returns the round's dealings the first time the threshold is reached.
+    // The dealing's signature must already have been verified.
+    pub fn insert(&mut self, dealing: Dealing) -> Option<BTreeMap<PublicKey, Dealing>> {
+        let round = dealing.round_id.clone();
+        // This round was already pruned, so this is a late duplicate
+        if self.is_pruned(&round) {
+            return None;
+        }
+        let now = Instant::now();
+        let is_new_round = !self.rounds.contains_key(&round);
+        let protocol_round = dealing.protocol_round;
+        let round_state = self
+            .rounds
+            .entry(round.clone())
+            .or_insert_with(|| RoundState::new(protocol_round, now));
+        if round_state.equivocators.contains(&dealing.public_key) {
+            return None;
+        }
+        // Only the first dealing from a sender counts. Whether a second one is evidence of
+        // equivocation is up to the caller, see dealing.
+        if round_state.dealings.contains_key(&dealing.public_key) {
+            return None;
+        }
+        if round_state.completed_at.is_some() {
+            // Completion already fired for this round. Note who was late, which the complaint
+            // phase needs, and optionally keep the late dealing around.
+            if round_state.stragglers.contains(&dealing.public_key) {
+                return None;
+            }
+            round_state.stragglers.push(dealing.public_key.clone());
+            if self.record_stragglers {
+                round_state.last_arrival = now;
+                round_state
+                    .dealings
+                    .insert(dealing.public_key.clone(), dealing);
+                self.stragglers += 1;
+            }
+            return None;
+        }
+        round_state.last_arrival = now;
+        round_state
+            .dealings
+            .insert(dealing.public_key.clone(), dealing);
+        // A failed round keeps recording stragglers for diagnosis but never completes
+        let completed = if !round_state.is_failed() && round_state.dealings.len() >= self.threshold
+        {
+            round_state.completed_at = Some(now);
+            self.rounds_completed += 1;
+            Some(round_state.dealings.clone())
+        } else {
+            None
+        };
+        if is_new_round {
+            self.prune_to_max_rounds();
+        }
+        completed
+    }
+
+    // The dealing a sender gave me for a round, if I kept it
+    pub fn dealing(&self, round: &RoundId, sender: &PublicKey) -> Option<&Dealing> {
+        self.rounds.get(round)?.dealings.get(sender)
+    }
+
+    // Record equivocation evidence. The evidence must already have been verified. Returns whether
+    // it was new to this node.
+    pub fn record_equivocation(&mut self, evidence: Equivocation) -> bool {
+        let round = evidence.round();
+        let key = (round.clone(), evidence.sender().clone());
+        if self.equivocations.contains_key(&key) {
+            return false;
+        }
+        if let Some(result) = self.results.get_mut(&round) {
+            if result.contributors.contains(evidence.sender()) {
+                result.tainted = true;
+                result.output = None;
+            }
+        } else if !self.is_pruned(&round) {
+            let protocol_round = evidence.first.protocol_round;
+            let round_state = self
+                .rounds
+                .entry(round.clone())
+                .or_insert_with(|| RoundState::new(protocol_round, Instant::now()));
+            Self::exclude_equivocator(round_state, evidence.sender());
+        }
+        self.equivocations.insert(key, evidence);
+        true
+    }
+
+    fn exclude_equivocator(round_state: &mut RoundState, sender: &PublicKey) {
+        // Too late to leave the dealing out of the round
+        if round_state.completed_at.is_some() && round_state.dealings.contains_key(sender) {
+            round_state.tainted = true;
+            round_state.output = None;
+        }
+        round_state.dealings.remove(sender);
+        round_state
+            .stragglers
+            .retain(|straggler| straggler != sender);
+        round_state.equivocators.insert(sender.clone());
+    }
+
+    // Record a complaint that has already been verified. A proven complaint convicts the dealer
+    // right away, a missing one stands until the dealer answers it. Returns whether it was new.
+    pub fn record_complaint(
+        &mut self,
+        round: &RoundId,
+        protocol_round: u32,
+        dealer: PublicKey,
+        complainer: PublicKey,
+        proven: bool,
+    ) -> bool {
+        let round_state = match self.tracked_round(round, protocol_round) {
+            Some(round_state) => round_state,
+            None => return false,
+        };
+        if proven {
+            return round_state.convicted.insert(dealer);
+        }
+        round_state
+            .complaints
+            .entry(dealer)
+            .or_default()
+            .insert(complainer)
+    }
+
+    // Record a dealer's answer to a missing complaint that has already been verified. Returns
+    // whether it was new.
+    pub fn record_answer(
+        &mut self,
+        round: &RoundId,
+        protocol_round: u32,
+        dealer: PublicKey,
+        complainer: PublicKey,
+    ) -> bool {
+        match self.tracked_round(round, protocol_round) {
+            Some(round_state) => round_state
+                .answered
+                .entry(dealer)
+                .or_default()
+                .insert(complainer),
+            None => false,
+        }
+    }
+
+    fn tracked_round(&mut self, round: &RoundId, protocol_round: u32) -> Option<&mut RoundState> {
+        // The round is long over
+        if self.is_pruned(round) {
+            return None;
+        }
+        Some(
+            self.rounds
+                .entry(round.clone())
+                .or_insert_with(|| RoundState::new(protocol_round, Instant::now())),
+        )
+    }
+
+    // Disqualify every dealer of a round that a complaint convicted or that left a missing
+    // complaint unanswered. Complaints and answers are broadcast, so once the complaint phase is
+    // over every honest member disqualifies the same dealers. Returns all of the round's
+    // disqualified dealers.
+    // One complaint is enough, rather than more than a threshold of them. A proven complaint
+    // carries the dealer's own signed dealing and the key to open it, so it is true no matter who
+    // sent it, and an honest dealer can answer any missing complaint, so a false one costs it
+    // nothing. Counting complaints would only let a dealer cheat up to that many members and
+    // still have its dealing combined.
+    pub fn disqualify(&mut self, round: &RoundId) -> BTreeSet<PublicKey> {
+        let round_state = match self.rounds.get_mut(round) {
+            Some(round_state) => round_state,
+            None => return BTreeSet::new(),
+        };
+        let mut disqualified = round_state.convicted.clone();
+        for (dealer, complainers) in &round_state.complaints {
+            let answered = round_state.answered.get(dealer);
+            if complainers
+                .iter()
+                .any(|complainer| !answered.is_some_and(|answered| answered.contains(complainer)))
+            {
+                disqualified.insert(dealer.clone());
+            }
+        }
+        round_state.disqualified = disqualified.clone();
+        disqualified
+    }
+
+    fn is_pruned(&self, round: &RoundId) -> bool {
+        self.tombstones.contains(round) || self.results.contains_key(round)
+    }
+
+    pub fn round(&self, round: &RoundId) -> Option<&RoundState> {
+        self.rounds.get(round)
+    }
+
+    // The result of a completed round whose dealings have already been pruned
+    pub fn result(&self, round: &RoundId) -> Option<&RoundResult> {
+        self.results.get(round)
+    }
+
+    // Attach the combined output of a completed round, unless the round turned out to be tainted
+    pub fn set_output(&mut self, round: &RoundId, output: JointShare) {
+        if let Some(round_state) = self.rounds.get_mut(round) {
+            if !round_state.tainted {
+                round_state.output = Some(output);
+            }
+        } else if let Some(result) = self.results.get_mut(round) {
+            if !result.tainted {
+                result.output = Some(output);
+            }
+        }
+    }
+
+    pub fn equivocations(&self) -> Vec<Equivocation> {
+        self.equivocations.values().cloned().collect()
+    }
+
+    // Mark every round that has been collecting for longer than the deadline as failed. The
+    // committee is the set of senders every round expects a dealing from. Returns the rounds that
+    // failed during this call.
+    pub fn expire_deadlines(
+        &mut self,
+        deadline: Duration,
+        committee: &[PublicKey],
+    ) -> Vec<FailedRound> {
+        let now = Instant::now();
+        let mut failed = Vec::new();
+        for (round, round_state) in &mut self.rounds {
+            let age = now.duration_since(round_state.first_arrival);
+            if round_state.completed_at.is_some() || round_state.is_failed() || age <= deadline {
+                continue;
+            }
+            let missing: Vec<PublicKey> = committee
+                .iter()
+                .filter(|public_key| !round_state.dealings.contains_key(*public_key))
+                .cloned()
+                .collect();
+            round_state.missing = Some(missing.clone());
+            failed.push(FailedRound {
+                round: round.clone(),
+                protocol_round: round_state.protocol_round,
+                received: round_state.dealings.len(),
+                missing,
+                age,
+            });
+        }
+        self.rounds_failed += failed.len() as u64;
+        failed
+    }
+
+    // Rounds that are still collecting this long after their first dealing arrived and whose
+    // missing senders have not been asked to retransmit yet. Returns each round with the committee
+    // members it is missing and marks them as asked.
+    pub fn stalled_rounds(
+        &mut self,
+        after: Duration,
+        committee: &[PublicKey],
+    ) -> Vec<(RoundId, Vec<PublicKey>)> {
+        let now = Instant::now();
+        let mut stalled = Vec::new();
+        for (round, round_state) in &mut self.rounds {
+            if round_state.completed_at.is_some()
+                || round_state.is_failed()
+                || round_state.retransmit_requested
+                || now.duration_since(round_state.first_arrival) <= after
+            {
+                continue;
+            }
+            round_state.retransmit_requested = true;
+            let missing: Vec<PublicKey> = committee
+                .iter()
+                .filter(|public_key| {
+                    !round_state.dealings.contains_key(*public_key)
+                        && !round_state.equivocators.contains(*public_key)
+                })
+                .cloned()
+                .collect();
+            stalled.push((round.clone(), missing));
+        }
+        stalled
+    }
+
+    // All failed rounds that have not been pruned yet
+    pub fn failed_rounds(&self) -> Vec<FailedRound> {
+        let now = Instant::now();
+        let mut failed: Vec<FailedRound> = self
+            .rounds
+            .iter()
+            .filter_map(|(round, round_state)| {
+                round_state.missing.as_ref().map(|missing| FailedRound {
+                    round: round.clone(),
+                    protocol_round: round_state.protocol_round,
+                    received: round_state.dealings.len(),
+                    missing: missing.clone(),
+                    age: now.duration_since(round_state.first_arrival),
+                })
+            })
+            .collect();
+        failed.sort_unstable_by_key(|failed_round| failed_round.age);
+        failed
+    }
+
+    // Drop rounds that are older than the retention policy allows
+    pub fn prune(&mut self) {
+        if let Some(max_age) = self.policy.max_age {
+            let now = Instant::now();
+            let expired: Vec<RoundId> = self
+                .rounds
+                .iter()
+                .filter(|(_, round_state)| now.duration_since(round_state.first_arrival) > max_age)
+                .map(|(round, _)| round.clone())
+                .collect();
+            for round in expired {
+                self.remove_round(round);
+            }
+        }
+        self.prune_to_max_rounds();
+    }
+
+    fn prune_to_max_rounds(&mut self) {
+        let max_rounds = match self.policy.max_rounds {
+            Some(max_rounds) if self.rounds.len() > max_rounds => max_rounds,
+            _ => return,
+        };
+        let mut by_age: Vec<(Instant, RoundId)> = self
+            .rounds
+            .iter()
+            .map(|(round, round_state)| (round_state.first_arrival, round.clone()))
+            .collect();
+        by_age.sort_unstable();
+        let excess = self.rounds.len() - max_rounds;
+        for (_, round) in by_age.into_iter().take(excess) {
+            self.remove_round(round);
+        }
+    }
+
+    fn remove_round(&mut self, round: RoundId) {
+        let round_state = match self.rounds.remove(&round) {
+            Some(round_state) => round_state,
+            None => return,
+        };
+        self.rounds_pruned += 1;
+        self.tombstones.insert(round.clone());
+        self.tombstone_order.push_back(round.clone());
+        while self.tombstone_order.len() > TOMBSTONE_CAPACITY {
+            if let Some(oldest) = self.tombstone_order.pop_front() {
+                self.tombstones.remove(&oldest);
+            }
+        }
+        if round_state.completed_at.is_none() || self.policy.result_capacity == 0 {
+            return;
+        }
+        let result = RoundResult {
+            contributors: round_state.dealings.into_keys().collect(),
+            output: round_state.output,
+            tainted: round_state.tainted,
+        };
+        self.results.insert(round.clone(), result);
+        self.result_order.push_back(round);
+        while self.result_order.len() > self.policy.result_capacity {
+            if let Some(oldest) = self.result_order.pop_front() {
+                self.results.remove(&oldest);
+            }
+        }
+    }
+
+    pub fn stats(&self) -> AggregatorStats {
+        // Completed and failed rounds are both finished and only held until they are pruned
+        let rounds_retained = self
+            .rounds
+            .values()
+            .filter(|round_state| round_state.completed_at.is_some() || round_state.is_failed())
+            .count();
+        let approx_bytes = self
+            .rounds
+            .values()
+            .map(RoundState::approx_bytes)
+            .sum::<usize>()
+            + self
+                .results
+                .values()
+                .map(RoundResult::approx_bytes)
+                .sum::<usize>()
+            + self
+                .tombstone_order
+                .iter()
+                .map(|round| 2 * (size_of::<RoundId>() + round.len()))
+                .sum::<usize>();
+        AggregatorStats {
+            rounds_in_flight: self.rounds.len() - rounds_retained,
+            rounds_retained,
+            rounds_completed: self.rounds_completed,
+            rounds_failed: self.rounds_failed,
+            rounds_pruned: self.rounds_pruned,
+            stragglers: self.stragglers,
+            equivocations: self.equivocations.len(),
+            results_stored: self.results.len(),
+            approx_bytes,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dealing(round: u8, sender: u8) -> Dealing {
+        Dealing {
+            round_id: vec![round].into(),
+            public_key: vec![sender].into(),
+            ..Default::default()
+        }
+    }
+
+    fn aggregator(threshold: u32, max_rounds: usize, result_capacity: usize) -> Aggregator {
+        Aggregator::new(
+            threshold,
+            false,
+            RetentionPolicy {
+                max_rounds: Some(max_rounds),
+                max_age: None,
+                result_capacity,
+            },
+        )
+    }
+
+    #[test]
+    fn completes_once_at_the_threshold() {
+        let mut aggregator = aggregator(2, 10, 10);
+        assert!(aggregator.insert(dealing(1, 1)).is_none());
+        assert!(aggregator.insert(dealing(1, 1)).is_none());
+        let dealings = aggregator.insert(dealing(1, 2)).unwrap();
+        assert_eq!(dealings.len(), 2);
+        // A late dealing only makes its sender a straggler
+        assert!(aggregator.insert(dealing(1, 3)).is_none());
+        let round_state = aggregator.round(&RoundId::from(vec![1])).unwrap();
+        assert_eq!(round_state.stragglers, vec![PublicKey::from(vec![3])]);
+        assert_eq!(round_state.dealings.len(), 2);
+        assert_eq!(aggregator.stats().rounds_completed, 1);
+    }
+
+    #[test]
+    fn pruned_rounds_do_not_start_over() {
+        let mut aggregator = aggregator(2, 1, 0);
+        aggregator.insert(dealing(1, 1));
+        // The second round pushes the first one out
+        aggregator.insert(dealing(2, 1));
+        assert!(aggregator.round(&RoundId::from(vec![1])).is_none());
+        assert!(aggregator.insert(dealing(1, 2)).is_none());
+        assert!(aggregator.round(&RoundId::from(vec![1])).is_none());
+        assert!(!aggregator.record_complaint(
+            &RoundId::from(vec![1]),
+            0,
+            PublicKey::from(vec![1]),
+            PublicKey::from(vec![2]),
+            false,
+        ));
+        let stats = aggregator.stats();
+        assert_eq!(stats.rounds_pruned, 1);
+        assert_eq!(stats.rounds_in_flight, 1);
+    }
+
+    #[test]
+    fn completed_rounds_are_summarized_when_pruned() {
+        let mut aggregator = aggregator(1, 1, 1);
+        assert!(aggregator.insert(dealing(1, 1)).is_some());
+        assert!(aggregator.insert(dealing(2, 1)).is_some());
+        let result = aggregator.result(&RoundId::from(vec![1])).unwrap();
+        assert_eq!(result.contributors, vec![PublicKey::from(vec![1])]);
+        // Evicted from the results but still remembered as pruned
+        assert!(aggregator.insert(dealing(3, 1)).is_some());
+        assert!(aggregator.result(&RoundId::from(vec![1])).is_none());
+        assert!(aggregator.insert(dealing(1, 2)).is_none());
+        assert_eq!(aggregator.stats().rounds_completed, 3);
+    }
+
+    #[test]
+    fn disqualifies_convicted_and_unanswered_dealers() {
+        let mut aggregator = aggregator(3, 10, 10);
+        let round = RoundId::from(vec![1]);
+        let member = |index: u8| PublicKey::from(vec![index]);
+        aggregator.insert(dealing(1, 1));
+        aggregator.record_complaint(&round, 0, member(1), member(4), true);
+        aggregator.record_complaint(&round, 0, member(2), member(4), false);
+        aggregator.record_complaint(&round, 0, member(3), member(4), false);
+        aggregator.record_complaint(&round, 0, member(3), member(5), false);
+        aggregator.record_answer(&round, 0, member(2), member(4));
+        aggregator.record_answer(&round, 0, member(3), member(4));
+        let disqualified = aggregator.disqualify(&round);
+        assert_eq!(disqualified, BTreeSet::from([member(1), member(3)]));
+        assert_eq!(aggregator.round(&round).unwrap().disqualified, disqualified);
+    }
+
+    fn equivocation(round: u8, sender: u8) -> Equivocation {
+        Equivocation {
+            first: dealing(round, sender),
+            first_key: Vec::new(),
+            second: dealing(round, sender),
+            second_key: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn equivocation_taints_the_rounds_the_sender_counted_towards() {
+        let mut aggregator = aggregator(1, 1, 10);
+        aggregator.insert(dealing(1, 1)).unwrap();
+        // The second round pushes the completed first one into the results
+        aggregator.insert(dealing(2, 1)).unwrap();
+        assert!(aggregator.record_equivocation(equivocation(1, 1)));
+        assert!(aggregator.result(&RoundId::from(vec![1])).unwrap().tainted);
+        assert!(!aggregator.record_equivocation(equivocation(1, 1)));
+
+        // A sender whose dealing didn't count is only excluded
+        assert!(aggregator.record_equivocation(equivocation(2, 2)));
+        let round_state = aggregator.round(&RoundId::from(vec![2])).unwrap();
+        assert!(!round_state.tainted);
+        assert!(round_state.equivocators.contains(&PublicKey::from(vec![2])));
+        assert!(aggregator.record_equivocation(equivocation(2, 1)));
+        let round_state = aggregator.round(&RoundId::from(vec![2])).unwrap();
+        assert!(round_state.tainted);
+        assert!(round_state.dealings.is_empty());
+        assert_eq!(aggregator.stats().equivocations, 3);
+    }
+
+    #[test]
+    fn retransmits_are_requested_once_per_round() {
+        let mut aggregator = aggregator(3, 10, 10);
+        let committee: Vec<PublicKey> = (1..=3)
+            .map(|sender| PublicKey::from(vec![sender]))
+            .collect();
+        aggregator.insert(dealing(1, 1));
+        std::thread::sleep(Duration::from_millis(5));
+        assert_eq!(
+            aggregator.stalled_rounds(Duration::ZERO, &committee),
+            vec![(RoundId::from(vec![1]), committee[1..].to_vec())]
+        );
+        assert!(aggregator
+            .stalled_rounds(Duration::ZERO, &committee)
+            .is_empty());
+
+        // A round that stalls later is asked about on its own, and one that completed never is
+        aggregator.insert(dealing(2, 1));
+        aggregator.insert(dealing(3, 1));
+        aggregator.insert(dealing(3, 2));
+        aggregator.insert(dealing(3, 3)).unwrap();
+        std::thread::sleep(Duration::from_millis(5));
+        assert_eq!(
+            aggregator.stalled_rounds(Duration::ZERO, &committee),
+            vec![(RoundId::from(vec![2]), committee[1..].to_vec())]
+        );
+    }
+}
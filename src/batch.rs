@@ -0,0 +1,151 @@
+// Coalesces the dealings queued for one peer stream into DealingBatch frames
+// With batching on, a frame is sent once it holds max_bytes of dealings or max_delay after its first
+// dealing was queued, whichever comes first. So a burst of dealings to a peer goes out as one frame
+// at the cost of up to max_delay of latency. With batching off every dealing is a frame of its own.
+
+// System
+use std::sync::Arc;
+
+// Third Party
+use async_stream::stream;
+use prost::Message;
+use tokio::sync::mpsc::Receiver;
+use tokio::time::Instant;
+use tokio_stream::Stream;
+
+// Local
+use super::config::BatchPolicy;
+use super::traffic::TrafficStats;
+use super::utils;
+use super::wire::EncodedDealing;
+use crate::sample::DealingBatch;
+
+pub fn batches(
+    mut receiver: Receiver<EncodedDealing>,
+    policy: BatchPolicy,
+    traffic: Arc<TrafficStats>,
+) -> impl Stream<Item = DealingBatch> {
+    stream! {
+        while let Some(first) = receiver.recv().await {
+            let mut size = first.encoded_len();
+            let mut dealings = vec![first];
+            if let Some(max_delay) = policy.max_delay {
+                let deadline = Instant::now() + max_delay;
+                while size < policy.max_bytes {
+                    match tokio::time::timeout_at(deadline, receiver.recv()).await {
+                        Ok(Some(dealing)) => {
+                            size += dealing.encoded_len();
+                            dealings.push(dealing);
+                        }
+                        // The deadline passed or the peer is gone
+                        _ => break,
+                    }
+                }
+            }
+            utils::debug_line_to_file(&dealings.len().to_string(), "batch_dealings.debug.txt");
+            let batch = DealingBatch { dealings };
+            traffic.record_raw(batch.encoded_len());
+            yield batch;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sample::Dealing;
+    use std::time::Duration;
+    use tokio::sync::mpsc;
+    use tokio_stream::StreamExt;
+
+    fn dealing(size: usize) -> EncodedDealing {
+        EncodedDealing::new(&Dealing {
+            dealing: vec![7; size].into(),
+            ..Default::default()
+        })
+    }
+
+    async fn next(batches: &mut (impl Stream<Item = DealingBatch> + Unpin)) -> DealingBatch {
+        tokio::time::timeout(Duration::from_secs(1), batches.next())
+            .await
+            .expect("no batch within a second")
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn batch_is_sent_after_the_delay() {
+        let (sender, receiver) = mpsc::channel(10);
+        let traffic = Arc::new(TrafficStats::default());
+        let policy = BatchPolicy {
+            max_delay: Some(Duration::from_millis(50)),
+            max_bytes: 64 * 1024,
+        };
+        let batches = batches(receiver, policy, traffic.clone());
+        tokio::pin!(batches);
+        let start = Instant::now();
+        for _ in 0..3 {
+            sender.send(dealing(100)).await.unwrap();
+        }
+
+        let batch = next(&mut batches).await;
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        assert_eq!(batch.dealings.len(), 3);
+        assert_eq!(traffic.raw_bytes_sent(), batch.encoded_len() as u64 + 5);
+    }
+
+    #[tokio::test]
+    async fn batch_is_sent_once_it_reaches_max_bytes() {
+        let (sender, receiver) = mpsc::channel(10);
+        let policy = BatchPolicy {
+            max_delay: Some(Duration::from_secs(60)),
+            max_bytes: 2 * dealing(100).encoded_len(),
+        };
+        let batches = batches(receiver, policy, Arc::default());
+        tokio::pin!(batches);
+        for _ in 0..3 {
+            sender.send(dealing(100)).await.unwrap();
+        }
+
+        assert_eq!(next(&mut batches).await.dealings.len(), 2);
+        // The third waits for more dealings or the delay
+        assert!(
+            tokio::time::timeout(Duration::from_millis(100), batches.next())
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn dealing_larger_than_max_bytes_is_sent_on_its_own() {
+        let (sender, receiver) = mpsc::channel(10);
+        let policy = BatchPolicy {
+            max_delay: Some(Duration::from_secs(60)),
+            max_bytes: 100,
+        };
+        let batches = batches(receiver, policy, Arc::default());
+        tokio::pin!(batches);
+        sender.send(dealing(1000)).await.unwrap();
+        sender.send(dealing(1000)).await.unwrap();
+
+        assert_eq!(next(&mut batches).await.dealings.len(), 1);
+        assert_eq!(next(&mut batches).await.dealings.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn every_dealing_is_a_batch_without_a_delay() {
+        let (sender, receiver) = mpsc::channel(10);
+        let policy = BatchPolicy {
+            max_delay: None,
+            max_bytes: 64 * 1024,
+        };
+        let batches = batches(receiver, policy, Arc::default());
+        tokio::pin!(batches);
+        for _ in 0..3 {
+            sender.send(dealing(100)).await.unwrap();
+        }
+
+        for _ in 0..3 {
+            assert_eq!(next(&mut batches).await.dealings.len(), 1);
+        }
+    }
+}
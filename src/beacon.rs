@@ -0,0 +1,364 @@
+// A public random value for every completed round
+// Every dealer committed to its secret with its Feldman commitments before any secret was revealed,
+// and a round's joint secret is the sum of the dealt secrets. Once a node has combined a round it
+// publishes its share of the joint secret. Any threshold of shares that match the joint
+// commitments reconstructs the joint secret, which is checked against the joint public key and
+// hashed into the beacon value. Withholding a share doesn't help a node since any threshold of
+// members can reconstruct without it, and no dealer learns anything about the other secrets before
+// its own is fixed, so no node can bias the value.
+// Shares only combine among nodes that left the same dealers in the round. With a round threshold
+// below the committee size different nodes could complete a round with different dealers, so the
+// beacon then requires the complaint phase, which makes every node combine the same qualified
+// dealers.
+
+// System
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// Third Party
+use curve25519_dalek::edwards::EdwardsPoint;
+use curve25519_dalek::scalar::Scalar;
+use parking_lot::Mutex;
+use prost::Message;
+use ring::digest;
+use tokio::sync::broadcast;
+
+// Local
+use super::frost::{self, FrostError, Identifier};
+use super::outbox::Outbox;
+use super::peer::PeerMap;
+use super::types::{PublicKey, RoundId};
+use super::utils;
+use super::vss::{self, JointShare};
+use crate::sample::{BeaconShare, BeaconValue, Dealing, DealingKind};
+
+const BEACON_CONTEXT: &[u8] = b"tokio-sample beacon v1";
+// How many beacon values are kept for GetBeacon
+const BEACON_CAPACITY: usize = 1000;
+
+// A round whose beacon value is not known yet
+struct PendingRound {
+    started: Instant,
+    protocol_round: u32,
+    // The dealers I combined and the joint commitments, once I have combined the round
+    dealers: Option<Vec<PublicKey>>,
+    commitments: Vec<EdwardsPoint>,
+    // Shares that match my dealers and commitments
+    verified: BTreeMap<Identifier, Scalar>,
+    // Shares that arrived before I combined the round
+    unverified: Vec<(PublicKey, BeaconShare)>,
+}
+
+impl PendingRound {
+    fn new(protocol_round: u32) -> Self {
+        Self {
+            started: Instant::now(),
+            protocol_round,
+            dealers: None,
+            commitments: Vec::new(),
+            verified: BTreeMap::new(),
+            unverified: Vec::new(),
+        }
+    }
+}
+
+struct BeaconState {
+    pending: HashMap<RoundId, PendingRound>,
+    values: HashMap<RoundId, BeaconValue>,
+    // Completion order of values so the oldest is evicted first
+    order: VecDeque<RoundId>,
+}
+
+#[derive(Clone)]
+pub struct Beacon {
+    outbox: Outbox,
+    peers: PeerMap,
+    threshold: usize,
+    state: Arc<Mutex<BeaconState>>,
+    values: broadcast::Sender<BeaconValue>,
+}
+
+impl Beacon {
+    pub fn new(outbox: Outbox, peers: PeerMap, threshold: u32) -> Self {
+        let (values, _) = broadcast::channel(1000);
+        Self {
+            outbox,
+            peers,
+            threshold: threshold as usize,
+            state: Arc::new(Mutex::new(BeaconState {
+                pending: HashMap::new(),
+                values: HashMap::new(),
+                order: VecDeque::new(),
+            })),
+            values,
+        }
+    }
+
+    // The beacon value of a round, or the latest one when round is empty
+    pub fn get(&self, round: &RoundId) -> Option<BeaconValue> {
+        let state = self.state.lock();
+        if round.is_empty() {
+            let latest = state.order.back()?;
+            return state.values.get(latest).cloned();
+        }
+        state.values.get(round).cloned()
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<BeaconValue> {
+        self.values.subscribe()
+    }
+
+    // Publish my share of a round I combined. dealers are the dealers I combined, in order. This
+    // does scalar multiplications and sends a dealing, so call it from spawn_blocking.
+    pub fn contribute(
+        &self,
+        round: &RoundId,
+        protocol_round: u32,
+        dealers: Vec<PublicKey>,
+        joint_share: &JointShare,
+    ) {
+        let share = BeaconShare {
+            dealers: utils::to_vecs(&dealers),
+            share: frost::serialize_scalar(&joint_share.secret_share),
+        };
+        {
+            let mut state = self.state.lock();
+            if state.values.contains_key(round) {
+                return;
+            }
+            let pending = state
+                .pending
+                .entry(round.clone())
+                .or_insert_with(|| PendingRound::new(protocol_round));
+            pending.dealers = Some(dealers);
+            pending.commitments = joint_share.commitments.clone();
+        }
+        // This comes back to me like everyone else's share
+        self.outbox.broadcast(Dealing {
+            dealing: share.encode_to_vec().into(),
+            round_id: round.clone(),
+            protocol_round,
+            kind: DealingKind::BeaconShare as i32,
+            ..Default::default()
+        });
+    }
+
+    // Handle a beacon share whose signature has already been verified. This does scalar
+    // multiplications, so call it from spawn_blocking.
+    pub fn handle(&self, dealing: &Dealing) {
+        let share = match BeaconShare::decode(&dealing.dealing[..]) {
+            Ok(share) => share,
+            Err(_) => {
+                println!("Dropping a malformed beacon share");
+                return;
+            }
+        };
+        let committee = self.peers.public_keys();
+        if !committee.contains(&dealing.public_key) {
+            return;
+        }
+        let mut state = self.state.lock();
+        if state.values.contains_key(&dealing.round_id) {
+            return;
+        }
+        let pending = state
+            .pending
+            .entry(dealing.round_id.clone())
+            .or_insert_with(|| PendingRound::new(dealing.protocol_round));
+        pending.unverified.push((dealing.public_key.clone(), share));
+        if pending.dealers.is_none() {
+            return;
+        }
+        for (sender, share) in std::mem::take(&mut pending.unverified) {
+            let identifier = match committee.iter().position(|member| *member == sender) {
+                Some(position) => (position + 1) as Identifier,
+                None => continue,
+            };
+            let same_dealers = pending.dealers.as_ref().is_some_and(|dealers| {
+                dealers
+                    .iter()
+                    .map(|dealer| &dealer[..])
+                    .eq(share.dealers.iter().map(Vec::as_slice))
+            });
+            if !same_dealers {
+                continue;
+            }
+            match verify_share(&share, identifier, &pending.commitments) {
+                Ok(scalar) => {
+                    pending.verified.insert(identifier, scalar);
+                }
+                Err(error) => println!(
+                    "Dropping a beacon share for round {}: {}",
+                    utils::hex(&dealing.round_id),
+                    error
+                ),
+            }
+        }
+        if pending.verified.len() < self.threshold {
+            return;
+        }
+        let pending = state.pending.remove(&dealing.round_id).unwrap();
+        let value = match reconstruct(&dealing.round_id, pending, self.threshold) {
+            Ok(value) => value,
+            Err(error) => {
+                println!(
+                    "Could not produce the beacon for round {}: {}",
+                    utils::hex(&dealing.round_id),
+                    error
+                );
+                return;
+            }
+        };
+        state.values.insert(dealing.round_id.clone(), value.clone());
+        state.order.push_back(dealing.round_id.clone());
+        while state.order.len() > BEACON_CAPACITY {
+            if let Some(oldest) = state.order.pop_front() {
+                state.values.remove(&oldest);
+            }
+        }
+        drop(state);
+        utils::debug_line_to_file("Beacon.", "beacon_produced.debug.txt");
+        // Sending only fails when nobody is subscribed
+        let _ = self.values.send(value);
+    }
+
+    // Forget rounds whose beacon could not be produced within max_age
+    pub fn prune(&self, max_age: Duration) {
+        self.state
+            .lock()
+            .pending
+            .retain(|_, pending| pending.started.elapsed() <= max_age);
+    }
+}
+
+fn verify_share(
+    share: &BeaconShare,
+    identifier: Identifier,
+    commitments: &[EdwardsPoint],
+) -> Result<Scalar, FrostError> {
+    let scalar = frost::deserialize_scalar(&share.share)?;
+    if EdwardsPoint::mul_base(&scalar) != vss::evaluate_commitments(commitments, identifier) {
+        return Err(FrostError::InvalidShare(identifier));
+    }
+    Ok(scalar)
+}
+
+// Interpolate the joint secret from threshold verified shares and hash it into the beacon value
+fn reconstruct(
+    round: &RoundId,
+    pending: PendingRound,
+    threshold: usize,
+) -> Result<BeaconValue, FrostError> {
+    let shares: Vec<(Identifier, Scalar)> = pending.verified.into_iter().take(threshold).collect();
+    let participants: Vec<Identifier> = shares.iter().map(|(identifier, _)| *identifier).collect();
+    let secret: Scalar = shares
+        .iter()
+        .map(|(identifier, share)| frost::lagrange_coefficient(*identifier, &participants) * share)
+        .sum();
+    let public_key = *pending
+        .commitments
+        .first()
+        .ok_or(FrostError::InvalidPayload)?;
+    // Every share matched the commitments, so this only fails on a bug
+    if EdwardsPoint::mul_base(&secret) != public_key {
+        return Err(FrostError::InvalidShare(0));
+    }
+    let secret = frost::serialize_scalar(&secret);
+    Ok(BeaconValue {
+        round_id: round.to_vec(),
+        protocol_round: pending.protocol_round,
+        value: beacon_value(round, &secret),
+        secret,
+        public_key: frost::serialize_point(&public_key),
+        dealers: utils::to_vecs(pending.dealers.iter().flatten()),
+    })
+}
+
+// Anyone can recompute this from the round and the revealed secret
+fn beacon_value(round: &RoundId, secret: &[u8]) -> Vec<u8> {
+    let mut context = digest::Context::new(&digest::SHA256);
+    context.update(BEACON_CONTEXT);
+    context.update(&(round.len() as u32).to_be_bytes());
+    context.update(round);
+    context.update(secret);
+    context.finish().as_ref().to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sample::VssDealing;
+
+    // Every dealer shares its secret among three members with threshold two, and every member
+    // combines all the dealings
+    fn joint_shares(secrets: &[Scalar]) -> Vec<JointShare> {
+        let dealings: Vec<VssDealing> = secrets
+            .iter()
+            .map(|secret| vss::deal_secret(*secret, 3, 2))
+            .collect();
+        (1..=3)
+            .map(|identifier| {
+                let payloads: Vec<Vec<u8>> = dealings
+                    .iter()
+                    .map(|dealing| {
+                        vss::for_recipient(dealing, identifier)
+                            .unwrap()
+                            .encode_to_vec()
+                    })
+                    .collect();
+                vss::combine(identifier, payloads.iter().map(Vec::as_slice)).unwrap()
+            })
+            .collect()
+    }
+
+    fn pending(joint_shares: &[JointShare], identifiers: &[Identifier]) -> PendingRound {
+        let mut pending = PendingRound::new(5);
+        pending.dealers = Some((0..3).map(|dealer| PublicKey::from(vec![dealer])).collect());
+        pending.commitments = joint_shares[0].commitments.clone();
+        for identifier in identifiers {
+            let share = BeaconShare {
+                dealers: Vec::new(),
+                share: frost::serialize_scalar(
+                    &joint_shares[usize::from(*identifier) - 1].secret_share,
+                ),
+            };
+            let scalar = verify_share(&share, *identifier, &pending.commitments).unwrap();
+            pending.verified.insert(*identifier, scalar);
+        }
+        pending
+    }
+
+    #[test]
+    fn any_threshold_of_shares_reconstructs_the_joint_secret() {
+        let secrets = [
+            frost::random_scalar(),
+            frost::random_scalar(),
+            frost::random_scalar(),
+        ];
+        let joint_shares = joint_shares(&secrets);
+        let round = RoundId::from(vec![8; 16]);
+        let first = reconstruct(&round, pending(&joint_shares, &[1, 2]), 2).unwrap();
+        let second = reconstruct(&round, pending(&joint_shares, &[2, 3]), 2).unwrap();
+        let secret = frost::serialize_scalar(&secrets.iter().sum());
+        assert_eq!(first.secret, secret);
+        assert_eq!(first.value, beacon_value(&round, &secret));
+        assert_eq!(first.protocol_round, 5);
+        assert_eq!(first.dealers.len(), 3);
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn rejects_a_share_that_does_not_match_the_commitments() {
+        let joint_shares = joint_shares(&[frost::random_scalar(), frost::random_scalar()]);
+        let share = BeaconShare {
+            dealers: Vec::new(),
+            share: frost::serialize_scalar(&joint_shares[0].secret_share),
+        };
+        assert!(verify_share(&share, 1, &joint_shares[0].commitments).is_ok());
+        assert!(matches!(
+            verify_share(&share, 2, &joint_shares[0].commitments),
+            Err(FrostError::InvalidShare(2))
+        ));
+    }
+}
@@ -0,0 +1,400 @@
+// Complaints about dealers whose dealing for a round was invalid or never arrived
+// A complaint about an invalid share carries the dealer's signed dealing together with the key it
+// was sealed with, so that every member can check that the share really does not match the
+// dealer's commitments. Revealing that key only exposes what the dealer sealed to the complainer in
+// that round. A complaint about a dealing that does not decrypt carries the Diffie-Hellman secret
+// instead, with a proof that it is the right one, because the complainer can't tell a wrong key from
+// a garbled payload. A missing dealing can't be proven, so the dealer has to answer the complaint by
+// revealing its dealing to the complainer the same way. A dealer that doesn't is disqualified.
+// System
+use std::fmt;
+
+// Third Party
+use prost::Message;
+
+// Local
+use super::confidential;
+use super::evidence;
+use super::frost::Identifier;
+use super::scheme::DealingScheme;
+use super::types::{PublicKey, RoundId};
+use crate::sample::{Complaint, ComplaintAnswer, ComplaintReason, Dealing, DealingKind};
+
+#[derive(Debug)]
+pub enum ComplaintError {
+    Malformed,
+    NotInCommittee,
+    // The dealing in the complaint is not the dealer's or was not sealed to the complainer
+    InvalidEvidence,
+    // The share in the complaint is fine
+    FalseComplaint,
+    // The answer does not reveal a valid share for the complainer
+    InvalidAnswer,
+}
+
+impl fmt::Display for ComplaintError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ComplaintError::Malformed => write!(f, "the complaint does not decode"),
+            ComplaintError::NotInCommittee => write!(f, "a party is not in the committee"),
+            ComplaintError::InvalidEvidence => write!(f, "the evidence does not hold up"),
+            ComplaintError::FalseComplaint => write!(f, "the share matches its commitments"),
+            ComplaintError::InvalidAnswer => write!(f, "the answer does not reveal a valid share"),
+        }
+    }
+}
+
+// A complaint that nothing usable arrived from a dealer
+pub fn missing(round: &RoundId, protocol_round: u32, dealer: &PublicKey) -> Dealing {
+    complaint_dealing(
+        round,
+        protocol_round,
+        Complaint {
+            dealer: dealer.to_vec(),
+            reason: ComplaintReason::Missing as i32,
+            ..Default::default()
+        },
+    )
+}
+
+// A complaint that the share in a dealing sealed to me does not match its commitments. key is the
+// key the dealing was sealed with.
+pub fn invalid_share(dealing: &Dealing, key: Vec<u8>) -> Dealing {
+    complaint_dealing(
+        &dealing.round_id,
+        dealing.protocol_round,
+        Complaint {
+            dealer: dealing.public_key.to_vec(),
+            reason: ComplaintReason::InvalidShare as i32,
+            dealing: Some(dealing.clone()),
+            key,
+            ..Default::default()
+        },
+    )
+}
+
+// A complaint that a dealing sealed to me does not decrypt. shared_point and proof come from
+// Sealer::prove_shared_secret.
+pub fn unreadable(dealing: &Dealing, shared_point: Vec<u8>, proof: Vec<u8>) -> Dealing {
+    complaint_dealing(
+        &dealing.round_id,
+        dealing.protocol_round,
+        Complaint {
+            dealer: dealing.public_key.to_vec(),
+            reason: ComplaintReason::Unreadable as i32,
+            dealing: Some(dealing.clone()),
+            shared_point,
+            proof,
+            ..Default::default()
+        },
+    )
+}
+
+// A dealer's answer to a complaint that its dealing is missing. dealing is the dealing I sealed to
+// the complainer and key the key it was sealed with.
+pub fn answer(dealing: &Dealing, key: Vec<u8>) -> Dealing {
+    let answer = ComplaintAnswer {
+        dealing: Some(dealing.clone()),
+        key,
+    };
+    Dealing {
+        dealing: answer.encode_to_vec().into(),
+        round_id: dealing.round_id.clone(),
+        protocol_round: dealing.protocol_round,
+        kind: DealingKind::ComplaintAnswer as i32,
+        ..Default::default()
+    }
+}
+
+fn complaint_dealing(round: &RoundId, protocol_round: u32, complaint: Complaint) -> Dealing {
+    Dealing {
+        dealing: complaint.encode_to_vec().into(),
+        round_id: round.clone(),
+        protocol_round,
+        kind: DealingKind::Complaint as i32,
+        ..Default::default()
+    }
+}
+
+// Check a complaint whose signature has already been verified. Returns the dealer it is about and
+// why. This does scalar multiplications, so call it from spawn_blocking.
+pub fn verify(
+    complaint_dealing: &Dealing,
+    committee: &[PublicKey],
+    scheme: &dyn DealingScheme,
+    threshold: u32,
+) -> Result<(PublicKey, ComplaintReason), ComplaintError> {
+    let complaint =
+        Complaint::decode(&complaint_dealing.dealing[..]).map_err(|_| ComplaintError::Malformed)?;
+    let complainer = &complaint_dealing.public_key;
+    let dealer = PublicKey::from(complaint.dealer.clone());
+    let position = committee
+        .iter()
+        .position(|member| member == complainer)
+        .ok_or(ComplaintError::NotInCommittee)?;
+    if dealer == *complainer || !committee.contains(&dealer) {
+        return Err(ComplaintError::NotInCommittee);
+    }
+    let reason = complaint.reason();
+    if reason == ComplaintReason::Missing {
+        return Ok((dealer, reason));
+    }
+    let dealing = complaint
+        .dealing
+        .as_ref()
+        .ok_or(ComplaintError::InvalidEvidence)?;
+    if dealing.kind() != DealingKind::Random
+        || dealing.public_key != dealer
+        || dealing.recipient != *complainer
+        || dealing.round_id != complaint_dealing.round_id
+        || !evidence::verify_dealing(dealing)
+    {
+        return Err(ComplaintError::InvalidEvidence);
+    }
+    let payload = if reason == ComplaintReason::Unreadable {
+        let key = confidential::key_from_shared_secret(
+            dealing,
+            &complaint.shared_point,
+            &complaint.proof,
+        )
+        .map_err(|_| ComplaintError::InvalidEvidence)?;
+        match confidential::open_with_key(&key, dealing) {
+            Ok(payload) => payload,
+            Err(_) => return Ok((dealer, reason)),
+        }
+    } else {
+        confidential::open_with_key(&complaint.key, dealing)
+            .map_err(|_| ComplaintError::InvalidEvidence)?
+    };
+    match scheme.validate(&payload, (position + 1) as Identifier, threshold) {
+        Ok(()) => Err(ComplaintError::FalseComplaint),
+        Err(_) => Ok((dealer, reason)),
+    }
+}
+
+// Check an answer to a missing complaint whose signature has already been verified. Returns the
+// revealed dealing, whose recipient is the complainer. This does scalar multiplications, so call it
+// from spawn_blocking.
+pub fn verify_answer(
+    answer_dealing: &Dealing,
+    committee: &[PublicKey],
+    scheme: &dyn DealingScheme,
+    threshold: u32,
+) -> Result<Dealing, ComplaintError> {
+    let answer = ComplaintAnswer::decode(&answer_dealing.dealing[..])
+        .map_err(|_| ComplaintError::Malformed)?;
+    let dealing = answer.dealing.ok_or(ComplaintError::InvalidEvidence)?;
+    if !committee.contains(&answer_dealing.public_key) {
+        return Err(ComplaintError::NotInCommittee);
+    }
+    let position = committee
+        .iter()
+        .position(|member| *member == dealing.recipient)
+        .ok_or(ComplaintError::NotInCommittee)?;
+    if dealing.kind() != DealingKind::Random
+        || dealing.public_key != answer_dealing.public_key
+        || dealing.recipient == dealing.public_key
+        || dealing.round_id != answer_dealing.round_id
+        || !evidence::verify_dealing(&dealing)
+    {
+        return Err(ComplaintError::InvalidEvidence);
+    }
+    let payload = confidential::open_with_key(&answer.key, &dealing)
+        .map_err(|_| ComplaintError::InvalidAnswer)?;
+    scheme
+        .validate(&payload, (position + 1) as Identifier, threshold)
+        .map_err(|_| ComplaintError::InvalidAnswer)?;
+    Ok(dealing)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::confidential::Sealer;
+    use crate::scheme::FeldmanVss;
+    use ring::rand::{SecureRandom, SystemRandom};
+    use ring::signature::{Ed25519KeyPair, KeyPair};
+
+    const THRESHOLD: u32 = 2;
+
+    struct Member {
+        key: Ed25519KeyPair,
+        sealer: Sealer,
+        public_key: PublicKey,
+    }
+
+    impl Member {
+        fn new() -> Self {
+            let mut seed = [0u8; 32];
+            SystemRandom::new().fill(&mut seed).unwrap();
+            let key = Ed25519KeyPair::from_seed_unchecked(&seed).unwrap();
+            let public_key = PublicKey::copy_from_slice(key.public_key().as_ref());
+            Self {
+                sealer: Sealer::new(&seed, public_key.clone()),
+                key,
+                public_key,
+            }
+        }
+
+        fn sign(&self, mut dealing: Dealing) -> Dealing {
+            dealing.public_key = self.public_key.clone();
+            dealing.signature = self
+                .key
+                .sign(&evidence::signing_payload(&dealing))
+                .as_ref()
+                .to_vec()
+                .into();
+            dealing
+        }
+
+        // A dealing with payload sealed to recipient, the way Outbox::broadcast_sealed sends it
+        fn seal(&self, recipient: &Member, payload: &[u8]) -> Dealing {
+            let mut dealing = Dealing {
+                public_key: self.public_key.clone(),
+                round_id: vec![1; 16].into(),
+                protocol_round: 3,
+                kind: DealingKind::Random as i32,
+                recipient: recipient.public_key.clone(),
+                ..Default::default()
+            };
+            dealing.dealing = self
+                .sealer
+                .seal(&recipient.public_key, &dealing, payload)
+                .unwrap()
+                .into();
+            self.sign(dealing)
+        }
+    }
+
+    // Members sorted the way the committee is, and every member's payload of one dealing
+    fn committee() -> (Vec<Member>, Vec<PublicKey>, Vec<Vec<u8>>) {
+        let mut members: Vec<Member> = (0..3).map(|_| Member::new()).collect();
+        members.sort_unstable_by(|a, b| a.public_key.cmp(&b.public_key));
+        let committee: Vec<PublicKey> = members.iter().map(|m| m.public_key.clone()).collect();
+        let payloads = FeldmanVss
+            .deal(&committee, THRESHOLD, 0)
+            .into_values()
+            .collect();
+        (members, committee, payloads)
+    }
+
+    fn verify_complaint(
+        complaint: Dealing,
+        complainer: &Member,
+        committee: &[PublicKey],
+    ) -> Result<(PublicKey, ComplaintReason), ComplaintError> {
+        verify(
+            &complainer.sign(complaint),
+            committee,
+            &FeldmanVss,
+            THRESHOLD,
+        )
+    }
+
+    #[test]
+    fn an_invalid_share_convicts_only_when_it_is_invalid() {
+        let (members, committee, payloads) = committee();
+        let (dealer, complainer) = (&members[0], &members[1]);
+        // The third member's share doesn't match the second member's identifier
+        let invalid = dealer.seal(complainer, &payloads[2]);
+        let key = complainer.sealer.reveal_key(&invalid).unwrap();
+        let (convicted, reason) =
+            verify_complaint(invalid_share(&invalid, key), complainer, &committee).unwrap();
+        assert_eq!(convicted, dealer.public_key);
+        assert_eq!(reason, ComplaintReason::InvalidShare);
+        let valid = dealer.seal(complainer, &payloads[1]);
+        let key = complainer.sealer.reveal_key(&valid).unwrap();
+        assert!(matches!(
+            verify_complaint(invalid_share(&valid, key), complainer, &committee),
+            Err(ComplaintError::FalseComplaint)
+        ));
+    }
+
+    #[test]
+    fn an_unreadable_dealing_convicts_with_a_shared_secret_proof() {
+        let (members, committee, payloads) = committee();
+        let (dealer, complainer) = (&members[2], &members[0]);
+        let mut garbled = dealer.seal(complainer, &payloads[0]);
+        let mut sealed = garbled.dealing.to_vec();
+        sealed[20] ^= 1;
+        garbled.dealing = sealed.into();
+        let garbled = dealer.sign(garbled);
+        let (shared_point, proof) = complainer.sealer.prove_shared_secret(&garbled).unwrap();
+        let (convicted, reason) = verify_complaint(
+            unreadable(&garbled, shared_point, proof),
+            complainer,
+            &committee,
+        )
+        .unwrap();
+        assert_eq!(convicted, dealer.public_key);
+        assert_eq!(reason, ComplaintReason::Unreadable);
+        let valid = dealer.seal(complainer, &payloads[0]);
+        let (shared_point, proof) = complainer.sealer.prove_shared_secret(&valid).unwrap();
+        assert!(matches!(
+            verify_complaint(
+                unreadable(&valid, shared_point, proof),
+                complainer,
+                &committee
+            ),
+            Err(ComplaintError::FalseComplaint)
+        ));
+    }
+
+    #[test]
+    fn complaints_stay_within_the_committee() {
+        let (members, committee, _) = committee();
+        let outsider = Member::new();
+        let round = RoundId::from(vec![1; 16]);
+        assert!(matches!(
+            verify_complaint(
+                missing(&round, 3, &outsider.public_key),
+                &members[0],
+                &committee
+            ),
+            Err(ComplaintError::NotInCommittee)
+        ));
+        assert!(matches!(
+            verify_complaint(
+                missing(&round, 3, &members[0].public_key),
+                &outsider,
+                &committee
+            ),
+            Err(ComplaintError::NotInCommittee)
+        ));
+        let (dealer, reason) = verify_complaint(
+            missing(&round, 3, &members[0].public_key),
+            &members[1],
+            &committee,
+        )
+        .unwrap();
+        assert_eq!(dealer, members[0].public_key);
+        assert_eq!(reason, ComplaintReason::Missing);
+    }
+
+    #[test]
+    fn answers_must_reveal_a_valid_share() {
+        let (members, committee, payloads) = committee();
+        let (dealer, complainer) = (&members[1], &members[2]);
+        let valid = dealer.seal(complainer, &payloads[2]);
+        let key = dealer.sealer.sealing_key(&valid).unwrap();
+        let revealed = verify_answer(
+            &dealer.sign(answer(&valid, key)),
+            &committee,
+            &FeldmanVss,
+            THRESHOLD,
+        )
+        .unwrap();
+        assert_eq!(revealed, valid);
+        let invalid = dealer.seal(complainer, &payloads[0]);
+        let key = dealer.sealer.sealing_key(&invalid).unwrap();
+        assert!(matches!(
+            verify_answer(
+                &dealer.sign(answer(&invalid, key)),
+                &committee,
+                &FeldmanVss,
+                THRESHOLD
+            ),
+            Err(ComplaintError::InvalidAnswer)
+        ));
+    }
+}
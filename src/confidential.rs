@@ -0,0 +1,383 @@
+// Per-recipient encryption of dealing payloads
+// Every node's X25519 key is derived from its Ed25519 identity: the secret is the Ed25519 secret
+// scalar and the public key is the Montgomery form of the Ed25519 public key. So a node can seal a
+// payload to any peer whose identity it knows, and only that peer can open it.
+// Payloads are sealed with ChaCha20-Poly1305 under a key derived with HKDF from the static
+// Diffie-Hellman secret of sender and recipient, both identities and the round. The dealing's
+// round, kind and index are authenticated as associated data so that a sealed payload can't be
+// replayed into another round.
+// A recipient can reveal the key of one dealing, for example to prove that its share was invalid.
+// That key only opens what the same sender sealed to it in the same round. To prove that a dealing
+// doesn't open at all, the recipient instead reveals the Diffie-Hellman secret it shares with the
+// sender, along with a Chaum-Pedersen proof that it is correct. That exposes everything the two
+// ever sealed to each other, which is only done for a sender that is misbehaving.
+
+// System
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+// Third Party
+use curve25519_dalek::edwards::{CompressedEdwardsY, EdwardsPoint};
+use curve25519_dalek::scalar::{clamp_integer, Scalar};
+use parking_lot::Mutex;
+use ring::aead::{self, Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305, NONCE_LEN};
+use ring::digest;
+use ring::hkdf;
+use ring::rand::{SecureRandom, SystemRandom};
+
+// Local
+use super::frost;
+use super::types::PublicKey;
+use crate::sample::Dealing;
+
+const SEAL_SALT: &[u8] = b"tokio-sample dealing seal v1";
+const SHARED_SECRET_PROOF_CONTEXT: &[u8] = b"tokio-sample shared secret proof v1";
+
+#[derive(Debug)]
+pub enum SealError {
+    InvalidPublicKey,
+    NotForMe,
+    NotMine,
+    DecryptionFailed,
+    InvalidProof,
+}
+
+impl fmt::Display for SealError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SealError::InvalidPublicKey => write!(f, "the peer's identity is not a usable key"),
+            SealError::NotForMe => write!(f, "the payload is sealed to another node"),
+            SealError::NotMine => write!(f, "the payload was sealed by another node"),
+            SealError::DecryptionFailed => write!(f, "the payload does not decrypt"),
+            SealError::InvalidProof => write!(f, "the shared secret proof does not verify"),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Sealer {
+    // The clamped X25519 secret
+    secret: [u8; 32],
+    public_key: PublicKey,
+    // The Diffie-Hellman secret with each peer, which costs a scalar multiplication to compute
+    shared_secrets: Arc<Mutex<HashMap<PublicKey, [u8; 32]>>>,
+}
+
+impl Sealer {
+    // Derive the X25519 key from the Ed25519 seed the same way Ed25519 derives its secret scalar
+    pub fn new(seed: &[u8], public_key: PublicKey) -> Self {
+        let hash = digest::digest(&digest::SHA512, seed);
+        let mut secret = [0u8; 32];
+        secret.copy_from_slice(&hash.as_ref()[..32]);
+        Self {
+            secret: clamp_integer(secret),
+            public_key,
+            shared_secrets: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    // Encrypt a payload for the recipient of a dealing. The dealing's round, kind and index must
+    // already be set.
+    pub fn seal(
+        &self,
+        recipient: &PublicKey,
+        dealing: &Dealing,
+        plaintext: &[u8],
+    ) -> Result<Vec<u8>, SealError> {
+        let key = self.key(recipient, &self.public_key, recipient, dealing)?;
+        let mut nonce = [0u8; NONCE_LEN];
+        SystemRandom::new().fill(&mut nonce).unwrap();
+        let mut sealed = plaintext.to_vec();
+        key.seal_in_place_append_tag(
+            Nonce::assume_unique_for_key(nonce),
+            Aad::from(associated_data(dealing)),
+            &mut sealed,
+        )
+        .map_err(|_| SealError::DecryptionFailed)?;
+        Ok([&nonce[..], &sealed].concat())
+    }
+
+    // Decrypt the payload of a dealing sealed to me
+    pub fn open(&self, dealing: &Dealing) -> Result<Vec<u8>, SealError> {
+        open_with_key(&self.reveal_key(dealing)?, dealing)
+    }
+
+    // The key a dealing sealed to me was sealed with
+    pub fn reveal_key(&self, dealing: &Dealing) -> Result<Vec<u8>, SealError> {
+        if dealing.recipient != self.public_key {
+            return Err(SealError::NotForMe);
+        }
+        self.key_bytes(
+            &dealing.public_key,
+            &dealing.public_key,
+            &self.public_key,
+            dealing,
+        )
+    }
+
+    // The key I sealed a dealing to its recipient with
+    pub fn sealing_key(&self, dealing: &Dealing) -> Result<Vec<u8>, SealError> {
+        if dealing.public_key != self.public_key {
+            return Err(SealError::NotMine);
+        }
+        self.key_bytes(
+            &dealing.recipient,
+            &self.public_key,
+            &dealing.recipient,
+            dealing,
+        )
+    }
+
+    // The Diffie-Hellman secret I share with the sender of a dealing sealed to me, as an Edwards
+    // point, and a proof that it is the sender's key times my secret. See key_from_shared_secret.
+    pub fn prove_shared_secret(&self, dealing: &Dealing) -> Result<(Vec<u8>, Vec<u8>), SealError> {
+        if dealing.recipient != self.public_key {
+            return Err(SealError::NotForMe);
+        }
+        let sender = prime_order_point(&dealing.public_key)?;
+        let secret = Scalar::from_bytes_mod_order(self.secret);
+        let shared = sender * secret;
+        let nonce = frost::random_scalar();
+        let challenge = proof_challenge(
+            &self.public_key,
+            &dealing.public_key,
+            &shared,
+            &EdwardsPoint::mul_base(&nonce),
+            &(sender * nonce),
+        );
+        let response = nonce + challenge * secret;
+        Ok((
+            shared.compress().to_bytes().to_vec(),
+            [challenge.to_bytes(), response.to_bytes()].concat(),
+        ))
+    }
+
+    fn key(
+        &self,
+        peer: &PublicKey,
+        sender: &PublicKey,
+        recipient: &PublicKey,
+        dealing: &Dealing,
+    ) -> Result<LessSafeKey, SealError> {
+        less_safe_key(&self.key_bytes(peer, sender, recipient, dealing)?)
+    }
+
+    fn key_bytes(
+        &self,
+        peer: &PublicKey,
+        sender: &PublicKey,
+        recipient: &PublicKey,
+        dealing: &Dealing,
+    ) -> Result<Vec<u8>, SealError> {
+        derive_key(&self.shared_secret(peer)?, sender, recipient, dealing)
+    }
+
+    fn shared_secret(&self, peer: &PublicKey) -> Result<[u8; 32], SealError> {
+        if let Some(shared_secret) = self.shared_secrets.lock().get(peer) {
+            return Ok(*shared_secret);
+        }
+        let peer_point = CompressedEdwardsY::from_slice(peer)
+            .ok()
+            .and_then(|point| point.decompress())
+            .ok_or(SealError::InvalidPublicKey)?;
+        let shared_secret = peer_point
+            .to_montgomery()
+            .mul_clamped(self.secret)
+            .to_bytes();
+        // A small order point would make the secret predictable
+        if shared_secret == [0u8; 32] {
+            return Err(SealError::InvalidPublicKey);
+        }
+        self.shared_secrets
+            .lock()
+            .insert(peer.clone(), shared_secret);
+        Ok(shared_secret)
+    }
+}
+
+fn derive_key(
+    shared_secret: &[u8; 32],
+    sender: &PublicKey,
+    recipient: &PublicKey,
+    dealing: &Dealing,
+) -> Result<Vec<u8>, SealError> {
+    let info = [
+        &sender[..],
+        &recipient[..],
+        &(dealing.round_id.len() as u32).to_be_bytes(),
+        &dealing.round_id,
+    ];
+    let pseudorandom_key = hkdf::Salt::new(hkdf::HKDF_SHA256, SEAL_SALT).extract(shared_secret);
+    let mut key = vec![0u8; CHACHA20_POLY1305.key_len()];
+    pseudorandom_key
+        .expand(&info, &CHACHA20_POLY1305)
+        .and_then(|okm| okm.fill(&mut key))
+        .map_err(|_| SealError::InvalidPublicKey)?;
+    Ok(key)
+}
+
+// The key a dealing was sealed with, from the Diffie-Hellman secret its recipient revealed with
+// prove_shared_secret. Fails unless the proof shows that the secret is the sender's key times the
+// recipient's secret, which is what both of them derive their keys from.
+pub fn key_from_shared_secret(
+    dealing: &Dealing,
+    shared_point: &[u8],
+    proof: &[u8],
+) -> Result<Vec<u8>, SealError> {
+    let recipient = prime_order_point(&dealing.recipient)?;
+    let sender = prime_order_point(&dealing.public_key)?;
+    let shared = frost::deserialize_point(shared_point).map_err(|_| SealError::InvalidProof)?;
+    if proof.len() != 64 {
+        return Err(SealError::InvalidProof);
+    }
+    let challenge = frost::deserialize_scalar(&proof[..32]).map_err(|_| SealError::InvalidProof)?;
+    let response = frost::deserialize_scalar(&proof[32..]).map_err(|_| SealError::InvalidProof)?;
+    let expected = proof_challenge(
+        &dealing.recipient,
+        &dealing.public_key,
+        &shared,
+        &(EdwardsPoint::mul_base(&response) - recipient * challenge),
+        &(sender * response - shared * challenge),
+    );
+    if expected != challenge {
+        return Err(SealError::InvalidProof);
+    }
+    derive_key(
+        &shared.to_montgomery().to_bytes(),
+        &dealing.public_key,
+        &dealing.recipient,
+        dealing,
+    )
+}
+
+// Decrypt the payload of a dealing with a revealed key
+pub fn open_with_key(key: &[u8], dealing: &Dealing) -> Result<Vec<u8>, SealError> {
+    if dealing.dealing.len() < NONCE_LEN {
+        return Err(SealError::DecryptionFailed);
+    }
+    let key = less_safe_key(key)?;
+    let (nonce, sealed) = dealing.dealing.split_at(NONCE_LEN);
+    let nonce = Nonce::try_assume_unique_for_key(nonce).unwrap();
+    let mut plaintext = sealed.to_vec();
+    let length = key
+        .open_in_place(nonce, Aad::from(associated_data(dealing)), &mut plaintext)
+        .map_err(|_| SealError::DecryptionFailed)?
+        .len();
+    plaintext.truncate(length);
+    Ok(plaintext)
+}
+
+// A node identity as a point of the prime order group. Identities with a small order component
+// can't be used in a shared secret proof.
+fn prime_order_point(public_key: &PublicKey) -> Result<EdwardsPoint, SealError> {
+    frost::deserialize_point(public_key).map_err(|_| SealError::InvalidPublicKey)
+}
+
+fn proof_challenge(
+    recipient: &PublicKey,
+    sender: &PublicKey,
+    shared: &EdwardsPoint,
+    first_commitment: &EdwardsPoint,
+    second_commitment: &EdwardsPoint,
+) -> Scalar {
+    let mut context = digest::Context::new(&digest::SHA512);
+    context.update(SHARED_SECRET_PROOF_CONTEXT);
+    context.update(recipient);
+    context.update(sender);
+    context.update(shared.compress().as_bytes());
+    context.update(first_commitment.compress().as_bytes());
+    context.update(second_commitment.compress().as_bytes());
+    let hash: [u8; 64] = context.finish().as_ref().try_into().unwrap();
+    Scalar::from_bytes_mod_order_wide(&hash)
+}
+
+fn less_safe_key(key: &[u8]) -> Result<LessSafeKey, SealError> {
+    let key = UnboundKey::new(&CHACHA20_POLY1305, key).map_err(|_| SealError::DecryptionFailed)?;
+    Ok(aead::LessSafeKey::new(key))
+}
+
+fn associated_data(dealing: &Dealing) -> Vec<u8> {
+    let mut associated_data = Vec::with_capacity(dealing.round_id.len() + 12);
+    associated_data.extend_from_slice(&(dealing.round_id.len() as u32).to_be_bytes());
+    associated_data.extend_from_slice(&dealing.round_id);
+    associated_data.extend_from_slice(&dealing.protocol_round.to_be_bytes());
+    associated_data.extend_from_slice(&dealing.kind.to_be_bytes());
+    associated_data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ring::signature::{Ed25519KeyPair, KeyPair};
+
+    fn sealer() -> Sealer {
+        let mut seed = [0u8; 32];
+        SystemRandom::new().fill(&mut seed).unwrap();
+        let key = Ed25519KeyPair::from_seed_unchecked(&seed).unwrap();
+        Sealer::new(&seed, PublicKey::copy_from_slice(key.public_key().as_ref()))
+    }
+
+    fn sealed(sender: &Sealer, recipient: &Sealer, plaintext: &[u8]) -> Dealing {
+        let mut dealing = Dealing {
+            public_key: sender.public_key.clone(),
+            round_id: vec![1, 2, 3].into(),
+            protocol_round: 4,
+            recipient: recipient.public_key.clone(),
+            ..Default::default()
+        };
+        dealing.dealing = sender
+            .seal(&recipient.public_key, &dealing, plaintext)
+            .unwrap()
+            .into();
+        dealing
+    }
+
+    #[test]
+    fn only_the_recipient_opens() {
+        let (sender, recipient, other) = (sealer(), sealer(), sealer());
+        let dealing = sealed(&sender, &recipient, b"share");
+        assert_eq!(recipient.open(&dealing).unwrap(), b"share");
+        assert!(matches!(other.open(&dealing), Err(SealError::NotForMe)));
+        let mut replayed = dealing.clone();
+        replayed.round_id = vec![9].into();
+        assert!(matches!(
+            recipient.open(&replayed),
+            Err(SealError::DecryptionFailed)
+        ));
+    }
+
+    #[test]
+    fn revealed_keys_open_the_dealing() {
+        let (sender, recipient) = (sealer(), sealer());
+        let dealing = sealed(&sender, &recipient, b"share");
+        let key = recipient.reveal_key(&dealing).unwrap();
+        assert_eq!(key, sender.sealing_key(&dealing).unwrap());
+        assert_eq!(open_with_key(&key, &dealing).unwrap(), b"share");
+        assert!(matches!(
+            recipient.sealing_key(&dealing),
+            Err(SealError::NotMine)
+        ));
+    }
+
+    #[test]
+    fn shared_secret_proof() {
+        let (sender, recipient) = (sealer(), sealer());
+        let dealing = sealed(&sender, &recipient, b"share");
+        let (shared_point, proof) = recipient.prove_shared_secret(&dealing).unwrap();
+        let key = key_from_shared_secret(&dealing, &shared_point, &proof).unwrap();
+        assert_eq!(key, recipient.reveal_key(&dealing).unwrap());
+        let mut altered = proof.clone();
+        altered[40] ^= 1;
+        assert!(matches!(
+            key_from_shared_secret(&dealing, &shared_point, &altered),
+            Err(SealError::InvalidProof)
+        ));
+        // The proof is bound to the sender, so it can't blame another one
+        let framed = sealed(&sealer(), &recipient, b"share");
+        assert!(matches!(
+            key_from_shared_secret(&framed, &shared_point, &proof),
+            Err(SealError::InvalidProof)
+        ));
+    }
+}
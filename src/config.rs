@@ -0,0 +1,260 @@
+// System
+use std::env;
+use std::path::PathBuf;
+use std::time::Duration;
+
+// Third Party
+use tonic::codec::CompressionEncoding;
+use tonic::transport::{Endpoint, Server};
+
+// Local
+use super::scheme;
+use super::utils;
+
+// How long completed and abandoned rounds are kept by the aggregator
+#[derive(Clone, Debug, Default)]
+pub struct RetentionPolicy {
+    // Keep at most this many rounds, dropping the oldest first
+    pub max_rounds: Option<usize>,
+    // Drop rounds whose first dealing arrived longer ago than this
+    pub max_age: Option<Duration>,
+    // Completed rounds are summarized into the result store, which keeps this many entries
+    pub result_capacity: usize,
+}
+
+// How the dealings queued for a peer are coalesced into frames on its stream
+#[derive(Clone, Debug)]
+pub struct BatchPolicy {
+    // Hold a frame open for more dealings this long after its first dealing. Every dealing is sent
+    // in a frame of its own when this is unset.
+    pub max_delay: Option<Duration>,
+    // Send a frame as soon as its dealings add up to this many bytes
+    pub max_bytes: usize,
+}
+
+// HTTP/2 and TCP settings for the server and the channels to peers, except for the two stream
+// limits, which only the server applies. Unset values keep tonic's defaults.
+#[derive(Clone, Debug, Default)]
+pub struct TransportTuning {
+    pub initial_stream_window: Option<u32>,
+    pub initial_connection_window: Option<u32>,
+    // Overrides both initial windows when enabled
+    pub adaptive_window: Option<bool>,
+    pub tcp_nodelay: Option<bool>,
+    pub keepalive_interval: Option<Duration>,
+    pub keepalive_timeout: Option<Duration>,
+    // Only advertised by the server to the peers that connect to it
+    pub max_concurrent_streams: Option<u32>,
+    pub max_frame_size: Option<u32>,
+}
+
+impl TransportTuning {
+    pub fn server(&self, mut server: Server) -> Server {
+        server = server
+            .initial_stream_window_size(self.initial_stream_window)
+            .initial_connection_window_size(self.initial_connection_window)
+            .http2_adaptive_window(self.adaptive_window)
+            .http2_keepalive_interval(self.keepalive_interval)
+            .http2_keepalive_timeout(self.keepalive_timeout)
+            .max_concurrent_streams(self.max_concurrent_streams)
+            .max_frame_size(self.max_frame_size);
+        if let Some(tcp_nodelay) = self.tcp_nodelay {
+            server = server.tcp_nodelay(tcp_nodelay);
+        }
+        server
+    }
+
+    pub fn endpoint(&self, mut endpoint: Endpoint) -> Endpoint {
+        endpoint = endpoint
+            .initial_stream_window_size(self.initial_stream_window)
+            .initial_connection_window_size(self.initial_connection_window);
+        if let Some(adaptive_window) = self.adaptive_window {
+            endpoint = endpoint.http2_adaptive_window(adaptive_window);
+        }
+        if let Some(tcp_nodelay) = self.tcp_nodelay {
+            endpoint = endpoint.tcp_nodelay(tcp_nodelay);
+        }
+        // Keep pinging while the channel is idle too, or a quiet peer's dead connection goes
+        // unnoticed until the next dealing
+        if let Some(keepalive_interval) = self.keepalive_interval {
+            endpoint = endpoint
+                .http2_keep_alive_interval(keepalive_interval)
+                .keep_alive_while_idle(true);
+        }
+        if let Some(keepalive_timeout) = self.keepalive_timeout {
+            endpoint = endpoint.keep_alive_timeout(keepalive_timeout);
+        }
+        endpoint
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct NodeConfig {
+    pub node_count: u32, // the total number of nodes in the network
+    pub hostname: String,
+    pub retention: RetentionPolicy,
+    // A round that has not completed this long after its first dealing arrived is marked failed
+    pub round_deadline: Option<Duration>,
+    // A round completes once this many dealings have arrived
+    pub threshold: u32,
+    // Record dealings that arrive after their round completed instead of dropping them
+    pub record_stragglers: bool,
+    // Where this node's Ed25519 identity is stored
+    pub identity_path: PathBuf,
+    // Where the DKG stores this node's key share
+    pub key_path: PathBuf,
+    // The threshold of every key generation. All members must agree on it.
+    pub dkg_threshold: u32,
+    // A key generation or reshare that has not completed this long after it started fails
+    pub dkg_deadline: Duration,
+    // Refresh the shares of the group key this often
+    pub key_refresh: Option<Duration>,
+    // While this node holds no group key, it takes a share of this one from a reshare and of no
+    // other
+    pub reshare_join_key: Option<Vec<u8>>,
+    // How many presignatures to keep ready for signing requests this node coordinates
+    pub presignature_target: usize,
+    // Refill the presignature pool once it drops below this many
+    pub presignature_low_watermark: usize,
+    // The name of the registered scheme random dealings use
+    pub dealing_scheme: String,
+    // How many rounds of random dealings to remember per peer
+    pub peer_history_rounds: usize,
+    // Ask the senders missing from a round that is still collecting this long after its first
+    // dealing arrived to send their dealings again
+    pub retransmit_after: Option<Duration>,
+    // Deliver random dealings through reliable broadcast so that all nodes agree on every sender's
+    // dealing
+    pub reliable_broadcast: bool,
+    // Run a complaint phase of this length, twice over, after each round completes and before it
+    // is combined
+    pub complaint_window: Option<Duration>,
+    // Reconstruct every completed round's joint secret into a public random value
+    pub beacon: bool,
+    pub batching: BatchPolicy,
+    // Compress what this node sends on the Sample service. Compressed requests and responses are
+    // always accepted.
+    pub compression: Option<CompressionEncoding>,
+    pub transport: TransportTuning,
+}
+
+impl NodeConfig {
+    // Everything except the node count and hostname is optional and read from the environment so
+    // that the docker compose file can tune it per deployment
+    pub fn new(node_count: u32, hostname: String) -> Self {
+        let retention = RetentionPolicy {
+            max_rounds: env_var("ROUND_RETENTION_COUNT"),
+            max_age: env_var("ROUND_RETENTION_SECS").map(Duration::from_secs),
+            result_capacity: env_var("ROUND_RESULT_CAPACITY").unwrap_or(0),
+        };
+        let threshold = env_var("ROUND_THRESHOLD").unwrap_or(node_count);
+        assert!(
+            threshold >= 1 && threshold <= node_count,
+            "ROUND_THRESHOLD must be between 1 and the node count"
+        );
+        let batching = BatchPolicy {
+            max_delay: env_var("DEALING_BATCH_DELAY_MS").map(Duration::from_millis),
+            max_bytes: env_var("DEALING_BATCH_MAX_BYTES").unwrap_or(64 * 1024),
+        };
+        let transport = TransportTuning {
+            initial_stream_window: env_var("HTTP2_STREAM_WINDOW_BYTES"),
+            initial_connection_window: env_var("HTTP2_CONNECTION_WINDOW_BYTES"),
+            adaptive_window: env_var::<u8>("HTTP2_ADAPTIVE_WINDOW").map(|enabled| enabled != 0),
+            tcp_nodelay: env_var::<u8>("TCP_NODELAY").map(|enabled| enabled != 0),
+            keepalive_interval: env_var("HTTP2_KEEPALIVE_INTERVAL_SECS").map(Duration::from_secs),
+            keepalive_timeout: env_var("HTTP2_KEEPALIVE_TIMEOUT_SECS").map(Duration::from_secs),
+            max_concurrent_streams: env_var("HTTP2_MAX_CONCURRENT_STREAMS"),
+            max_frame_size: env_var("HTTP2_MAX_FRAME_SIZE"),
+        };
+        // The limits HTTP/2 puts on these settings
+        for window in [
+            transport.initial_stream_window,
+            transport.initial_connection_window,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            assert!(
+                window < 1 << 31,
+                "HTTP/2 window sizes must be below 2^31 bytes"
+            );
+        }
+        if let Some(max_frame_size) = transport.max_frame_size {
+            assert!(
+                ((1 << 14)..1 << 24).contains(&max_frame_size),
+                "HTTP2_MAX_FRAME_SIZE must be between 16384 and 16777215"
+            );
+        }
+        let presignature_target = env_var("PRESIGNATURE_POOL_SIZE").unwrap_or(0);
+        // Nodes in the docker deployment share a working directory, so the default is per host
+        let identity_path = env_var("NODE_IDENTITY_PATH")
+            .unwrap_or_else(|| PathBuf::from(format!("{}.identity.bin", hostname)));
+        let key_path = env_var("GROUP_KEY_PATH")
+            .unwrap_or_else(|| PathBuf::from(format!("{}.group_key.bin", hostname)));
+        let dkg_threshold = env_var("DKG_THRESHOLD").unwrap_or(threshold);
+        assert!(
+            dkg_threshold >= 1 && dkg_threshold <= node_count,
+            "DKG_THRESHOLD must be between 1 and the node count"
+        );
+        let reshare_join_key = env_var::<String>("RESHARE_JOIN_KEY").map(|hex| {
+            utils::unhex(&hex)
+                .filter(|key| key.len() == 32)
+                .expect("RESHARE_JOIN_KEY must be a group public key in hex")
+        });
+        let complaint_window = env_var("COMPLAINT_WINDOW_MS").map(Duration::from_millis);
+        let beacon = env_var::<u8>("BEACON").unwrap_or(0) != 0;
+        // Beacon shares only combine between nodes that combined the same dealers. Below the node
+        // count, which dealings complete a round first depends on delivery timing, and whoever
+        // controls that could pick among several values. The complaint phase makes every node
+        // combine the same qualified dealers instead.
+        assert!(
+            !beacon || threshold == node_count || complaint_window.is_some(),
+            "BEACON needs ROUND_THRESHOLD to equal the node count or COMPLAINT_WINDOW_MS to be set"
+        );
+        Self {
+            node_count,
+            hostname,
+            retention,
+            round_deadline: env_var("ROUND_DEADLINE_SECS").map(Duration::from_secs),
+            threshold,
+            record_stragglers: env_var::<u8>("ROUND_RECORD_STRAGGLERS").unwrap_or(0) != 0,
+            identity_path,
+            key_path,
+            dkg_threshold,
+            dkg_deadline: Duration::from_secs(env_var("DKG_DEADLINE_SECS").unwrap_or(60)),
+            key_refresh: env_var("KEY_REFRESH_SECS").map(Duration::from_secs),
+            reshare_join_key,
+            presignature_target,
+            presignature_low_watermark: env_var("PRESIGNATURE_LOW_WATERMARK")
+                .unwrap_or(presignature_target.div_ceil(2)),
+            dealing_scheme: env_var("DEALING_SCHEME")
+                .unwrap_or_else(|| scheme::DEFAULT_SCHEME.to_string()),
+            peer_history_rounds: env_var("PEER_HISTORY_ROUNDS").unwrap_or(1000),
+            retransmit_after: env_var("RETRANSMIT_AFTER_SECS").map(Duration::from_secs),
+            reliable_broadcast: env_var::<u8>("RELIABLE_BROADCAST").unwrap_or(0) != 0,
+            complaint_window,
+            beacon,
+            batching,
+            compression: compression(),
+            transport,
+        }
+    }
+}
+
+fn compression() -> Option<CompressionEncoding> {
+    match env_var::<String>("COMPRESSION").as_deref() {
+        None | Some("none") => None,
+        Some("gzip") => Some(CompressionEncoding::Gzip),
+        Some("zstd") => Some(CompressionEncoding::Zstd),
+        Some(_) => panic!("Environment variable COMPRESSION has an invalid value"),
+    }
+}
+
+fn env_var<T: std::str::FromStr>(name: &str) -> Option<T> {
+    let value = env::var(name).ok().filter(|value| !value.is_empty());
+    value.map(|value| {
+        value
+            .parse()
+            .unwrap_or_else(|_| panic!("Environment variable {} has an invalid value", name))
+    })
+}
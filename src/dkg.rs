@@ -0,0 +1,1385 @@
+// Pedersen-style distributed key generation over the dealing streams
+// Every committee member deals a Feldman verifiable secret sharing of a fresh random secret as a
+// DKG_DEALING. The group secret is the sum of all of the dealt secrets, so no node ever learns it,
+// and a member's key share is the sum of the shares it was dealt. The group public key and every
+// member's verifying share follow from the summed coefficient commitments.
+// Any member can start a key generation. The others join as soon as they see its first dealing,
+// after checking that the round ID matches their own view of the committee and that the dealing
+// uses their own configured threshold. A member that already holds a group key doesn't join until
+// its operator asks it to replace the key, so no key is ever replaced behind an operator's back.
+// The key is only installed once every member's dealing has arrived and verified, so that all
+// honest members combine the same set of dealers and end up with the same group key.
+// A key can later be reshared to the current committee with a new threshold. Every holder of the
+// key that is still in the committee deals a sharing of its own key share, and each new member
+// combines what it was dealt with the Lagrange coefficients of the dealers. The group key stays
+// the same. Any old threshold of dealers interpolates the key, but different sets of dealers give
+// different sharings of it, so every member must combine the same dealers. The round's leader, a
+// committee member picked by the round ID, broadcasts the first old threshold of dealers whose
+// dealings verified for it, and every member combines exactly those.
+// A periodic refresh is a reshare to the same committee and threshold: the shares change, so
+// shares stolen before a refresh are useless when combined with shares stolen after. Refreshes
+// are keyed by the refresh period the wall clock is in, so the whole committee runs one refresh
+// per period no matter how many timers fire.
+// Every member is sent only its own share, sealed to it.
+// There is no complaint phase for key generation. A dealer that sends a bad share or no share at
+// all keeps a key generation from completing, and a reshare whose selected dealers don't all
+// deliver never combines. Both fail once they have been running for DKG_DEADLINE_SECS, naming the
+// dealers that are missing, and the operator starts over with a new nonce. Ended sessions are
+// forgotten after the deadline, but their round IDs are kept so that no late dealing reopens them.
+
+// System
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// Third Party
+use curve25519_dalek::edwards::EdwardsPoint;
+use curve25519_dalek::scalar::Scalar;
+use curve25519_dalek::traits::Identity;
+use parking_lot::Mutex;
+use prost::Message;
+
+// Local
+use super::confidential::Sealer;
+use super::config::NodeConfig;
+use super::frost::{self, FrostError, Identifier, KeyPackage};
+use super::outbox::Outbox;
+use super::peer::PeerMap;
+use super::session;
+use super::signing::{self, FrostSigner};
+use super::types::{PublicKey, RoundId};
+use super::utils;
+use super::vss;
+use crate::sample::{
+    Dealing, DealingKind, DkgDealing, FrostKeyShare, FrostParticipant, ReshareDealing,
+    ReshareSelection, VssDealing,
+};
+
+const DKG_NONCE_PREFIX: &[u8] = b"dkg:";
+const RESHARE_NONCE_PREFIX: &[u8] = b"reshare:";
+// Followed by the refresh period, so every node refreshing the same key in the same period derives
+// the same round ID
+const REFRESH_NONCE_PREFIX: &[u8] = b"refresh:";
+// How many selections each peer may have waiting for a reshare I haven't seen a dealing of yet
+const EARLY_SELECTIONS_PER_PEER: usize = 4;
+// How many ended sessions to remember
+const TOMBSTONE_CAPACITY: usize = 1000;
+
+// The node identity behind each identifier of a key
+type Participants = BTreeMap<Identifier, PublicKey>;
+
+struct DkgSession {
+    started: Instant,
+    committee: Vec<PublicKey>,
+    threshold: usize,
+    dealt: bool,
+    // My operator asked me to replace my group key with this one
+    replace_key: bool,
+    // The coefficient commitments of every dealer whose dealing verified, and my share from it
+    dealings: BTreeMap<PublicKey, (Vec<EdwardsPoint>, Scalar)>,
+    complete: bool,
+}
+
+// What every dealing of one reshare must agree on
+#[derive(Clone, PartialEq)]
+struct ReshareParams {
+    key_round: RoundId,
+    group_public_key: EdwardsPoint,
+    old_threshold: usize,
+    old_committee: Vec<PublicKey>,
+    threshold: usize,
+}
+
+struct ReshareSession {
+    started: Instant,
+    committee: Vec<PublicKey>,
+    params: ReshareParams,
+    // The identifiers, in the key being reshared, of its holders that are still in the committee
+    dealers: Vec<Identifier>,
+    dealt: bool,
+    dealings: BTreeMap<Identifier, (Vec<EdwardsPoint>, Scalar)>,
+    // I am the leader and have sent my selection of dealers
+    selected: bool,
+    // The refresh period, if this is a refresh
+    refresh_epoch: Option<u64>,
+    complete: bool,
+}
+
+// The round IDs of ended sessions, oldest first
+#[derive(Default)]
+struct Tombstones {
+    rounds: HashSet<RoundId>,
+    order: VecDeque<RoundId>,
+}
+
+impl Tombstones {
+    fn insert(&mut self, round_id: RoundId) {
+        if !self.rounds.insert(round_id.clone()) {
+            return;
+        }
+        self.order.push_back(round_id);
+        while self.order.len() > TOMBSTONE_CAPACITY {
+            if let Some(oldest) = self.order.pop_front() {
+                self.rounds.remove(&oldest);
+            }
+        }
+    }
+
+    fn contains(&self, round_id: &RoundId) -> bool {
+        self.rounds.contains(round_id)
+    }
+}
+
+#[derive(Clone)]
+pub struct Dkg {
+    sessions: Arc<Mutex<HashMap<RoundId, DkgSession>>>,
+    reshares: Arc<Mutex<HashMap<RoundId, ReshareSession>>>,
+    // The dealers each reshare's leader picked, which may arrive before any dealing
+    selections: Arc<Mutex<HashMap<RoundId, Vec<Identifier>>>>,
+    // Selections for reshares that have no session yet, by sender, oldest first
+    early_selections: Arc<Mutex<HashMap<PublicKey, VecDeque<Dealing>>>>,
+    // Always locked after sessions or reshares
+    tombstones: Arc<Mutex<Tombstones>>,
+    peers: PeerMap,
+    outbox: Outbox,
+    signer: FrostSigner,
+    sealer: Sealer,
+    key_path: PathBuf,
+    // The threshold of every key generation, from the local configuration
+    threshold: usize,
+    key_refresh: Option<Duration>,
+    // The group key my operator asked me to join by reshare while I hold none
+    join_key: Option<EdwardsPoint>,
+    // The key generation that produced the installed key
+    key_round: Arc<Mutex<Option<RoundId>>>,
+    // The refresh period of the refresh that produced the installed key, if it was one
+    key_refresh_epoch: Arc<Mutex<Option<u64>>>,
+}
+
+impl Dkg {
+    // Install the key share left on disk by an earlier run, if there is one
+    pub fn new(
+        config: &NodeConfig,
+        peers: PeerMap,
+        outbox: Outbox,
+        signer: FrostSigner,
+        sealer: Sealer,
+    ) -> Self {
+        let key_path = config.key_path.clone();
+        let mut key_round = None;
+        match load(&key_path) {
+            Ok(Some((key_package, participants, round_id))) => {
+                // A share generated for another identity can't sign with this one
+                if participants.get(&key_package.identifier) != Some(outbox.public_key()) {
+                    panic!(
+                        "The group key stored at {} belongs to another node identity",
+                        key_path.display()
+                    );
+                }
+                println!("Loaded the group key from {}", key_path.display());
+                signer.install_key(key_package, participants);
+                key_round = Some(round_id);
+            }
+            Ok(None) => {}
+            Err(error) => println!(
+                "Ignoring the group key stored at {}: {}",
+                key_path.display(),
+                error
+            ),
+        }
+        let join_key = config.reshare_join_key.as_ref().map(|join_key| {
+            frost::deserialize_point(join_key).expect("RESHARE_JOIN_KEY is not a valid group key")
+        });
+        Self {
+            sessions: Arc::new(Mutex::new(HashMap::new())),
+            reshares: Arc::new(Mutex::new(HashMap::new())),
+            selections: Arc::new(Mutex::new(HashMap::new())),
+            early_selections: Arc::new(Mutex::new(HashMap::new())),
+            tombstones: Arc::new(Mutex::new(Tombstones::default())),
+            peers,
+            outbox,
+            signer,
+            sealer,
+            key_path,
+            threshold: config.dkg_threshold as usize,
+            key_refresh: config.key_refresh,
+            join_key,
+            key_round: Arc::new(Mutex::new(key_round)),
+            key_refresh_epoch: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    pub fn key_round(&self) -> Option<RoundId> {
+        self.key_round.lock().clone()
+    }
+
+    // Start a key generation across the current committee by dealing my contribution to it, or
+    // join one that is waiting for me. With replace_key it replaces the key I hold. This sends
+    // dealings, so it must be called from spawn_blocking.
+    pub fn start(&self, session_nonce: &[u8], replace_key: bool) -> RoundId {
+        let committee = self.peers.public_keys();
+        let round_id = dkg_round_id(&committee, session_nonce);
+        self.deal(&round_id, session_nonce, committee, replace_key);
+        round_id
+    }
+
+    // Handle one inbound DKG dealing whose signature has already been verified. This does scalar
+    // multiplications and may send dealings, so it must be called from spawn_blocking.
+    pub fn handle(&self, dealing: Dealing) {
+        let result = self
+            .sealer
+            .open(&dealing)
+            .map_err(|error| error.to_string())
+            .and_then(|payload| {
+                self.handle_dealing(&dealing, &payload)
+                    .map_err(|error| error.to_string())
+            });
+        if let Err(error) = result {
+            println!(
+                "Dropping a DKG dealing for round {}: {}",
+                utils::hex(&dealing.round_id),
+                error
+            );
+            utils::debug_line_to_file("Rejected.", "dealing_rejected.debug.txt");
+        }
+    }
+
+    fn handle_dealing(&self, dealing: &Dealing, payload: &[u8]) -> Result<(), FrostError> {
+        let dkg_dealing = DkgDealing::decode(payload).map_err(|_| FrostError::InvalidPayload)?;
+        let vss_dealing = dkg_dealing.vss.ok_or(FrostError::InvalidPayload)?;
+        let committee = self.peers.public_keys();
+        // A different round ID means the sender sees a different committee
+        if dkg_round_id(&committee, &dkg_dealing.session_nonce) != dealing.round_id
+            || !committee.contains(&dealing.public_key)
+        {
+            return Err(FrostError::InvalidPayload);
+        }
+        // The dealer doesn't get to pick the threshold
+        let threshold = self.threshold;
+        if vss_dealing.commitments.len() != threshold {
+            return Err(FrostError::InvalidPayload);
+        }
+        // Join a key generation someone else started
+        self.deal(
+            &dealing.round_id,
+            &dkg_dealing.session_nonce,
+            committee.clone(),
+            false,
+        );
+        let identifier = my_identifier(&committee, self.outbox.public_key());
+        let verified = vss::verify_dealing(&vss_dealing, identifier, threshold)?;
+
+        let mut sessions = self.sessions.lock();
+        let dkg_session = sessions
+            .get_mut(&dealing.round_id)
+            .ok_or(FrostError::SessionEnded)?;
+        if dkg_session.threshold != threshold {
+            return Err(FrostError::InvalidPayload);
+        }
+        // Keep the first dealing from each dealer
+        dkg_session
+            .dealings
+            .entry(dealing.public_key.clone())
+            .or_insert(verified);
+        // My own dealing is needed too, so a key generation I didn't consent to never completes
+        if dkg_session.complete || dkg_session.dealings.len() < dkg_session.committee.len() {
+            return Ok(());
+        }
+        dkg_session.complete = true;
+        let (key_package, participants) = derive_key(dkg_session, identifier)?;
+        drop(sessions);
+
+        self.install(key_package, participants, &dealing.round_id, None);
+        utils::debug_line_to_file("Done.", "dkg_complete.debug.txt");
+        Ok(())
+    }
+
+    // Persist a new key share and start signing with it
+    fn install(
+        &self,
+        key_package: KeyPackage,
+        participants: Participants,
+        round_id: &RoundId,
+        refresh_epoch: Option<u64>,
+    ) {
+        println!(
+            "Installed group key {} with threshold {} from round {}",
+            utils::hex(&frost::serialize_point(&key_package.group_public_key)),
+            key_package.threshold,
+            utils::hex(round_id)
+        );
+        if let Err(error) = save(&self.key_path, &key_package, &participants, round_id) {
+            println!(
+                "Could not persist the group key to {}: {}",
+                self.key_path.display(),
+                error
+            );
+        }
+        self.signer.install_key(key_package, participants);
+        *self.key_round.lock() = Some(round_id.clone());
+        *self.key_refresh_epoch.lock() = refresh_epoch;
+    }
+
+    // Broadcast my dealing for a key generation unless I already have, or unless it would replace
+    // the key I hold without my operator asking for that
+    fn deal(
+        &self,
+        round_id: &RoundId,
+        session_nonce: &[u8],
+        committee: Vec<PublicKey>,
+        replace_key: bool,
+    ) {
+        let participants = committee.len() as u16;
+        let threshold = self.threshold;
+        let holds_key = self.signer.key().is_some();
+        {
+            let mut sessions = self.sessions.lock();
+            if self.tombstones.lock().contains(round_id) {
+                println!(
+                    "Not joining key generation {} because it ended",
+                    utils::hex(round_id)
+                );
+                return;
+            }
+            let dkg_session = sessions
+                .entry(round_id.clone())
+                .or_insert_with(|| DkgSession {
+                    started: Instant::now(),
+                    committee: committee.clone(),
+                    threshold,
+                    dealt: false,
+                    replace_key: false,
+                    dealings: BTreeMap::new(),
+                    complete: false,
+                });
+            dkg_session.replace_key |= replace_key;
+            if dkg_session.dealt {
+                return;
+            }
+            if holds_key && !dkg_session.replace_key {
+                println!(
+                    "Not joining key generation {} because this node already holds a group key",
+                    utils::hex(round_id)
+                );
+                return;
+            }
+            dkg_session.dealt = true;
+        }
+        let vss_dealing = vss::deal(participants, threshold as u16);
+        self.outbox.broadcast_sealed(
+            Dealing {
+                round_id: round_id.clone(),
+                kind: DealingKind::DkgDealing as i32,
+                ..Default::default()
+            },
+            |recipient| {
+                let dkg_dealing = DkgDealing {
+                    session_nonce: session_nonce.to_vec(),
+                    vss: Some(vss_for(&vss_dealing, &committee, recipient)?),
+                };
+                Some(dkg_dealing.encode_to_vec())
+            },
+        );
+    }
+
+    // Reshare the installed key to the current committee. Returns None when this node holds no
+    // key to reshare. This sends dealings, so it must be called from spawn_blocking.
+    pub fn start_reshare(&self, session_nonce: &[u8], threshold: Option<usize>) -> Option<RoundId> {
+        let (key_package, participants) = self.signer.key()?;
+        let key_round = self.key_round()?;
+        let committee = self.peers.public_keys();
+        let params = ReshareParams {
+            key_round,
+            group_public_key: key_package.group_public_key,
+            old_threshold: key_package.threshold as usize,
+            old_committee: participants.values().cloned().collect(),
+            threshold: threshold.unwrap_or(key_package.threshold as usize),
+        };
+        let round_id = reshare_round_id(&committee, &params.key_round, session_nonce);
+        self.reshare_deal(&round_id, session_nonce, committee, params);
+        Some(round_id)
+    }
+
+    // Refresh every share of the installed key without changing the group key, unless the key
+    // already came out of the refresh for this period or a later one
+    pub fn refresh(&self, epoch: u64) -> Option<RoundId> {
+        if self
+            .key_refresh_epoch
+            .lock()
+            .is_some_and(|refreshed| refreshed >= epoch)
+        {
+            return None;
+        }
+        self.start_reshare(&refresh_nonce(epoch), None)
+    }
+
+    // Handle one inbound reshare dealing whose signature has already been verified. This does
+    // scalar multiplications and may send dealings, so it must be called from spawn_blocking.
+    pub fn handle_reshare(&self, dealing: Dealing) {
+        let result = self
+            .sealer
+            .open(&dealing)
+            .map_err(|error| error.to_string())
+            .and_then(|payload| {
+                self.handle_reshare_dealing(&dealing, &payload)
+                    .map_err(|error| error.to_string())
+            });
+        if let Err(error) = result {
+            println!(
+                "Dropping a reshare dealing for round {}: {}",
+                utils::hex(&dealing.round_id),
+                error
+            );
+            utils::debug_line_to_file("Rejected.", "dealing_rejected.debug.txt");
+        }
+    }
+
+    fn handle_reshare_dealing(&self, dealing: &Dealing, payload: &[u8]) -> Result<(), FrostError> {
+        let reshare_dealing =
+            ReshareDealing::decode(payload).map_err(|_| FrostError::InvalidPayload)?;
+        let vss_dealing = reshare_dealing.vss.ok_or(FrostError::InvalidPayload)?;
+        let committee = self.peers.public_keys();
+        // A different round ID means the sender sees a different committee
+        if reshare_round_id(
+            &committee,
+            &reshare_dealing.key_round,
+            &reshare_dealing.session_nonce,
+        ) != dealing.round_id
+        {
+            return Err(FrostError::InvalidPayload);
+        }
+        let params = ReshareParams {
+            key_round: reshare_dealing.key_round.into(),
+            group_public_key: frost::deserialize_point(&reshare_dealing.group_public_key)?,
+            old_threshold: reshare_dealing.old_threshold as usize,
+            old_committee: reshare_dealing
+                .old_committee
+                .into_iter()
+                .map(PublicKey::from)
+                .collect(),
+            threshold: reshare_dealing.threshold as usize,
+        };
+        if vss_dealing.commitments.len() != params.threshold
+            || params.threshold == 0
+            || params.threshold > committee.len()
+            || params.old_threshold == 0
+            || params.old_threshold > params.old_committee.len()
+        {
+            return Err(FrostError::InvalidPayload);
+        }
+        // The sender must be the holder of the share it claims to be dealing
+        let dealer = signing::identifier(reshare_dealing.dealer)?;
+        if params.old_committee.get(dealer as usize - 1) != Some(&dealing.public_key)
+            || !committee.contains(&dealing.public_key)
+        {
+            return Err(FrostError::InvalidShare(dealer));
+        }
+        // A dealer could otherwise hand me a share of any key it likes and overwrite mine
+        if !self.takes_part_in(&params) {
+            return Err(FrostError::ForeignKey);
+        }
+        if let Some(epoch) = refresh_epoch(&reshare_dealing.session_nonce) {
+            self.check_refresh(epoch, &params)?;
+        }
+        // Join a reshare someone else started
+        self.reshare_deal(
+            &dealing.round_id,
+            &reshare_dealing.session_nonce,
+            committee.clone(),
+            params.clone(),
+        );
+        let identifier = my_identifier(&committee, self.outbox.public_key());
+        let (commitments, share) = vss::verify_dealing(&vss_dealing, identifier, params.threshold)?;
+        // A holder of the key can check that the dealer shared its actual key share. New members
+        // only learn whether all of the dealers together shared the right key, when they combine.
+        if let Some(verifying_shares) = self.verifying_shares_for(&params) {
+            if verifying_shares.get(&dealer) != Some(&commitments[0]) {
+                return Err(FrostError::InvalidShare(dealer));
+            }
+        }
+
+        let mut reshares = self.reshares.lock();
+        let reshare_session = reshares
+            .get_mut(&dealing.round_id)
+            .ok_or(FrostError::SessionEnded)?;
+        if reshare_session.params != params || reshare_session.dealers.len() < params.old_threshold
+        {
+            return Err(FrostError::InvalidPayload);
+        }
+        // Keep the first dealing from each dealer
+        reshare_session
+            .dealings
+            .entry(dealer)
+            .or_insert((commitments, share));
+        // As the leader, pick the first old threshold of dealers to arrive
+        let leads = reshare_leader(&reshare_session.committee, &dealing.round_id)
+            == Some(self.outbox.public_key());
+        let selection = (leads
+            && !reshare_session.selected
+            && reshare_session.dealings.len() >= params.old_threshold)
+            .then(|| {
+                reshare_session.selected = true;
+                reshare_session
+                    .dealings
+                    .keys()
+                    .take(params.old_threshold)
+                    .map(|dealer| u32::from(*dealer))
+                    .collect()
+            });
+        drop(reshares);
+
+        // This comes back to me like it reaches everyone else
+        if let Some(dealers) = selection {
+            self.outbox.broadcast(Dealing {
+                dealing: ReshareSelection { dealers }.encode_to_vec().into(),
+                round_id: dealing.round_id.clone(),
+                kind: DealingKind::ReshareSelection as i32,
+                ..Default::default()
+            });
+        }
+        self.complete_reshare(&dealing.round_id)
+    }
+
+    // A refresh keeps the threshold, belongs to the current period and runs once per period
+    fn check_refresh(&self, epoch: u64, params: &ReshareParams) -> Result<(), FrostError> {
+        if params.threshold != params.old_threshold {
+            return Err(FrostError::InvalidPayload);
+        }
+        if let Some(key_refresh) = self.key_refresh {
+            let current = refresh_epoch_at(SystemTime::now(), key_refresh);
+            if epoch + 1 < current || epoch > current + 1 {
+                return Err(FrostError::InvalidPayload);
+            }
+        }
+        let refreshed = self.key_round().as_ref() == Some(&params.key_round)
+            && self
+                .key_refresh_epoch
+                .lock()
+                .is_some_and(|refreshed| refreshed >= epoch);
+        if refreshed {
+            return Err(FrostError::InvalidPayload);
+        }
+        Ok(())
+    }
+
+    // Handle the dealers a reshare's leader picked, once the selection's signature has been
+    // verified. This may combine the reshare, so it must be called from spawn_blocking.
+    pub fn handle_reshare_selection(&self, dealing: Dealing) {
+        if let Err(error) = self.handle_selection(&dealing) {
+            println!(
+                "Dropping a reshare selection for round {}: {}",
+                utils::hex(&dealing.round_id),
+                error
+            );
+            utils::debug_line_to_file("Rejected.", "dealing_rejected.debug.txt");
+        }
+    }
+
+    fn handle_selection(&self, dealing: &Dealing) -> Result<(), FrostError> {
+        let selection = ReshareSelection::decode(&dealing.dealing[..])
+            .map_err(|_| FrostError::InvalidPayload)?;
+        let committee = match self.reshares.lock().get(&dealing.round_id) {
+            Some(reshare_session) => reshare_session.committee.clone(),
+            // Hold on to a selection that arrives before any dealing of its reshare
+            None => return self.park_selection(dealing),
+        };
+        if reshare_leader(&committee, &dealing.round_id) != Some(&dealing.public_key) {
+            return Err(FrostError::InvalidPayload);
+        }
+        let dealers = selection
+            .dealers
+            .into_iter()
+            .map(signing::identifier)
+            .collect::<Result<Vec<Identifier>, FrostError>>()?;
+        if !utils::has_unique_elements(&dealers) {
+            return Err(FrostError::InvalidPayload);
+        }
+        // Keep the leader's first selection
+        self.selections
+            .lock()
+            .entry(dealing.round_id.clone())
+            .or_insert(dealers);
+        self.complete_reshare(&dealing.round_id)
+    }
+
+    // Each committee member gets a few places, so no peer can crowd out another's selections
+    fn park_selection(&self, dealing: &Dealing) -> Result<(), FrostError> {
+        if dealing.round_id.len() != session::ROUND_ID_LEN
+            || !self.peers.public_keys().contains(&dealing.public_key)
+        {
+            return Err(FrostError::InvalidPayload);
+        }
+        if self.tombstones.lock().contains(&dealing.round_id) {
+            return Err(FrostError::SessionEnded);
+        }
+        let mut early_selections = self.early_selections.lock();
+        let parked = early_selections
+            .entry(dealing.public_key.clone())
+            .or_default();
+        if parked.len() == EARLY_SELECTIONS_PER_PEER {
+            parked.pop_front();
+        }
+        parked.push_back(dealing.clone());
+        Ok(())
+    }
+
+    // The selections that arrived before the reshare they belong to
+    fn take_early_selections(&self, round_id: &RoundId) -> Vec<Dealing> {
+        let mut taken = Vec::new();
+        for parked in self.early_selections.lock().values_mut() {
+            parked.retain(|selection| {
+                if selection.round_id != *round_id {
+                    return true;
+                }
+                taken.push(selection.clone());
+                false
+            });
+        }
+        taken
+    }
+
+    // Combine a reshare once the leader's selection and the dealings of every selected dealer
+    // have arrived
+    fn complete_reshare(&self, round_id: &RoundId) -> Result<(), FrostError> {
+        let selection = match self.selections.lock().get(round_id) {
+            Some(selection) => selection.clone(),
+            None => return Ok(()),
+        };
+        let mut reshares = self.reshares.lock();
+        let reshare_session = match reshares.get_mut(round_id) {
+            Some(reshare_session) => reshare_session,
+            None => return Ok(()),
+        };
+        if reshare_session.complete
+            || !selection
+                .iter()
+                .all(|dealer| reshare_session.dealings.contains_key(dealer))
+        {
+            return Ok(());
+        }
+        reshare_session.complete = true;
+        if selection.len() < reshare_session.params.old_threshold {
+            return Err(FrostError::InvalidPayload);
+        }
+        let identifier = my_identifier(&reshare_session.committee, self.outbox.public_key());
+        let (key_package, participants) =
+            derive_reshared_key(reshare_session, &selection, identifier)?;
+        let refresh_epoch = reshare_session.refresh_epoch;
+        let params = reshare_session.params.clone();
+        drop(reshares);
+
+        // My key may have changed since the reshare began
+        if !self.takes_part_in(&params) {
+            return Err(FrostError::ForeignKey);
+        }
+        self.install(key_package, participants, round_id, refresh_epoch);
+        utils::debug_line_to_file("Done.", "reshare_complete.debug.txt");
+        Ok(())
+    }
+
+    // I take part in a reshare of the key I hold, or of the key my operator asked me to join while
+    // I hold none. A joining node can't check the dealers' shares against the key's holders, so it
+    // only ever takes a share of the key its operator named.
+    fn takes_part_in(&self, params: &ReshareParams) -> bool {
+        match self.signer.key() {
+            Some(_) => self.verifying_shares_for(params).is_some(),
+            None => self.join_key == Some(params.group_public_key),
+        }
+    }
+
+    // The verifying shares of the key being reshared, if it is the key this node holds
+    fn verifying_shares_for(
+        &self,
+        params: &ReshareParams,
+    ) -> Option<BTreeMap<Identifier, EdwardsPoint>> {
+        let (key_package, participants) = self.signer.key()?;
+        let holds_key = self.key_round().as_ref() == Some(&params.key_round)
+            && key_package.group_public_key == params.group_public_key
+            && key_package.threshold as usize == params.old_threshold
+            && participants.values().eq(params.old_committee.iter());
+        holds_key.then_some(key_package.verifying_shares)
+    }
+
+    // Broadcast my share of the key being reshared unless I already have, or hold no share of it
+    fn reshare_deal(
+        &self,
+        round_id: &RoundId,
+        session_nonce: &[u8],
+        committee: Vec<PublicKey>,
+        params: ReshareParams,
+    ) {
+        let my_key = self
+            .signer
+            .key()
+            .filter(|_| self.verifying_shares_for(&params).is_some());
+        let public_key = self.outbox.public_key();
+        let (opened, dealt) = {
+            let mut reshares = self.reshares.lock();
+            if self.tombstones.lock().contains(round_id) {
+                println!(
+                    "Not joining reshare {} because it ended",
+                    utils::hex(round_id)
+                );
+                return;
+            }
+            let opened = !reshares.contains_key(round_id);
+            let reshare_session = reshares.entry(round_id.clone()).or_insert_with(|| {
+                let dealers = params
+                    .old_committee
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, member)| committee.contains(member))
+                    .map(|(position, _)| (position + 1) as Identifier)
+                    .collect();
+                ReshareSession {
+                    started: Instant::now(),
+                    committee: committee.clone(),
+                    params: params.clone(),
+                    dealers,
+                    dealt: false,
+                    dealings: BTreeMap::new(),
+                    selected: false,
+                    refresh_epoch: refresh_epoch(session_nonce),
+                    complete: false,
+                }
+            });
+            let dealt = reshare_session.dealt;
+            reshare_session.dealt = true;
+            (opened, dealt)
+        };
+        if opened {
+            for selection in self.take_early_selections(round_id) {
+                self.handle_reshare_selection(selection);
+            }
+        }
+        if dealt {
+            return;
+        }
+        let key_package = match my_key {
+            Some((key_package, participants))
+                if participants.get(&key_package.identifier) == Some(public_key)
+                    && committee.contains(public_key) =>
+            {
+                key_package
+            }
+            // I only receive a share of the reshared key
+            _ => return,
+        };
+        let vss_dealing = vss::deal_secret(
+            key_package.secret_share,
+            committee.len() as u16,
+            params.threshold as u16,
+        );
+        self.outbox.broadcast_sealed(
+            Dealing {
+                round_id: round_id.clone(),
+                kind: DealingKind::ReshareDealing as i32,
+                ..Default::default()
+            },
+            |recipient| {
+                let reshare_dealing = ReshareDealing {
+                    session_nonce: session_nonce.to_vec(),
+                    key_round: params.key_round.to_vec(),
+                    group_public_key: frost::serialize_point(&params.group_public_key),
+                    old_threshold: params.old_threshold as u32,
+                    old_committee: utils::to_vecs(&params.old_committee),
+                    dealer: u32::from(key_package.identifier),
+                    threshold: params.threshold as u32,
+                    vss: Some(vss_for(&vss_dealing, &committee, recipient)?),
+                };
+                Some(reshare_dealing.encode_to_vec())
+            },
+        );
+    }
+
+    // Fail the key generations and reshares that haven't completed within the deadline and forget
+    // every session that started longer ago than it
+    pub fn expire(&self, deadline: Duration) {
+        let mut ended = Vec::new();
+        {
+            let mut sessions = self.sessions.lock();
+            let mut tombstones = self.tombstones.lock();
+            sessions.retain(|round_id, dkg_session| {
+                if dkg_session.started.elapsed() < deadline {
+                    return true;
+                }
+                if !dkg_session.complete {
+                    let missing = dkg_session
+                        .committee
+                        .iter()
+                        .filter(|member| !dkg_session.dealings.contains_key(*member))
+                        .map(|member| utils::hex(member))
+                        .collect::<Vec<String>>();
+                    println!(
+                        "Key generation {} failed without dealings from {}",
+                        utils::hex(round_id),
+                        missing.join(", ")
+                    );
+                    utils::debug_line_to_file("Failed.", "dkg_failed.debug.txt");
+                }
+                tombstones.insert(round_id.clone());
+                false
+            });
+        }
+        {
+            let mut reshares = self.reshares.lock();
+            let mut tombstones = self.tombstones.lock();
+            reshares.retain(|round_id, reshare_session| {
+                if reshare_session.started.elapsed() < deadline {
+                    return true;
+                }
+                if !reshare_session.complete {
+                    let missing = reshare_session
+                        .dealers
+                        .iter()
+                        .filter(|dealer| !reshare_session.dealings.contains_key(*dealer))
+                        .map(|dealer| {
+                            utils::hex(&reshare_session.params.old_committee[*dealer as usize - 1])
+                        })
+                        .collect::<Vec<String>>();
+                    println!(
+                        "Reshare {} failed without dealings from {}",
+                        utils::hex(round_id),
+                        missing.join(", ")
+                    );
+                    utils::debug_line_to_file("Failed.", "reshare_failed.debug.txt");
+                }
+                tombstones.insert(round_id.clone());
+                ended.push(round_id.clone());
+                false
+            });
+        }
+        let mut selections = self.selections.lock();
+        for round_id in ended {
+            selections.remove(&round_id);
+        }
+        drop(selections);
+        // Selections parked by members that have since left the committee
+        let committee = self.peers.public_keys();
+        self.early_selections
+            .lock()
+            .retain(|public_key, _| committee.contains(public_key));
+    }
+}
+
+// Key generations use their own nonce space so they never collide with random dealing rounds
+fn dkg_round_id(committee: &[PublicKey], session_nonce: &[u8]) -> RoundId {
+    let nonce = [DKG_NONCE_PREFIX, session_nonce].concat();
+    session::round_id(committee, &nonce, 0)
+}
+
+// Reshares are keyed by the key they reshare, so every refresh of a key gets a fresh round ID
+fn reshare_round_id(committee: &[PublicKey], key_round: &[u8], session_nonce: &[u8]) -> RoundId {
+    let nonce = [RESHARE_NONCE_PREFIX, key_round, session_nonce].concat();
+    session::round_id(committee, &nonce, 0)
+}
+
+// The committee member that picks the dealers of a reshare. None if the round ID can't be one.
+fn reshare_leader<'a>(committee: &'a [PublicKey], round_id: &RoundId) -> Option<&'a PublicKey> {
+    if round_id.len() != session::ROUND_ID_LEN || committee.is_empty() {
+        return None;
+    }
+    let prefix = round_id[..8].try_into().ok()?;
+    committee.get((u64::from_be_bytes(prefix) % committee.len() as u64) as usize)
+}
+
+fn refresh_nonce(epoch: u64) -> Vec<u8> {
+    [REFRESH_NONCE_PREFIX, &epoch.to_be_bytes()].concat()
+}
+
+// The refresh period a reshare belongs to, if it is a refresh
+fn refresh_epoch(session_nonce: &[u8]) -> Option<u64> {
+    let epoch = session_nonce.strip_prefix(REFRESH_NONCE_PREFIX)?;
+    Some(u64::from_be_bytes(epoch.try_into().ok()?))
+}
+
+// Refresh periods are counted from the Unix epoch
+fn refresh_epoch_at(time: SystemTime, key_refresh: Duration) -> u64 {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    since_epoch.as_secs() / key_refresh.as_secs().max(1)
+}
+
+// The next refresh period and how long until it starts
+pub fn next_refresh(key_refresh: Duration) -> (u64, Duration) {
+    let now = SystemTime::now();
+    let epoch = refresh_epoch_at(now, key_refresh) + 1;
+    let start = UNIX_EPOCH + Duration::from_secs(epoch * key_refresh.as_secs().max(1));
+    (epoch, start.duration_since(now).unwrap_or_default())
+}
+
+// The part of my dealing meant for one committee member
+fn vss_for(
+    vss_dealing: &VssDealing,
+    committee: &[PublicKey],
+    recipient: &PublicKey,
+) -> Option<VssDealing> {
+    let position = committee.iter().position(|member| member == recipient)?;
+    vss::for_recipient(vss_dealing, (position + 1) as Identifier)
+}
+
+fn my_identifier(committee: &[PublicKey], public_key: &PublicKey) -> Identifier {
+    let position = committee
+        .iter()
+        .position(|member| member == public_key)
+        .expect("I am always part of my own committee");
+    (position + 1) as Identifier
+}
+
+// Sum every dealer's contribution into my key package
+fn derive_key(
+    dkg_session: &DkgSession,
+    identifier: Identifier,
+) -> Result<(KeyPackage, Participants), FrostError> {
+    let mut commitments = vec![EdwardsPoint::identity(); dkg_session.threshold];
+    let mut secret_share = Scalar::ZERO;
+    for (dealer_commitments, share) in dkg_session.dealings.values() {
+        for (sum, commitment) in commitments.iter_mut().zip(dealer_commitments) {
+            *sum += commitment;
+        }
+        secret_share += share;
+    }
+    let participants: Participants = dkg_session
+        .committee
+        .iter()
+        .enumerate()
+        .map(|(position, public_key)| ((position + 1) as Identifier, public_key.clone()))
+        .collect();
+    let key_package = KeyPackage {
+        identifier,
+        secret_share,
+        group_public_key: commitments[0],
+        verifying_shares: participants
+            .keys()
+            .map(|participant| {
+                (
+                    *participant,
+                    vss::evaluate_commitments(&commitments, *participant),
+                )
+            })
+            .collect(),
+        threshold: dkg_session.threshold as u16,
+    };
+    if !key_package.is_consistent() {
+        return Err(FrostError::InvalidShare(identifier));
+    }
+    Ok((key_package, participants))
+}
+
+// Interpolate the selected dealers' sharings of their key shares into my share of the same group
+// secret
+fn derive_reshared_key(
+    reshare_session: &ReshareSession,
+    selection: &[Identifier],
+    identifier: Identifier,
+) -> Result<(KeyPackage, Participants), FrostError> {
+    if !selection
+        .iter()
+        .all(|dealer| reshare_session.dealers.contains(dealer))
+    {
+        return Err(FrostError::InvalidPayload);
+    }
+    let threshold = reshare_session.params.threshold;
+    let mut commitments = vec![EdwardsPoint::identity(); threshold];
+    let mut secret_share = Scalar::ZERO;
+    for dealer in selection {
+        let (dealer_commitments, share) = &reshare_session.dealings[dealer];
+        let lambda = frost::lagrange_coefficient(*dealer, selection);
+        for (sum, commitment) in commitments.iter_mut().zip(dealer_commitments) {
+            *sum += commitment * lambda;
+        }
+        secret_share += lambda * share;
+    }
+    // This is where a new member learns whether the dealers shared the right key
+    if commitments[0] != reshare_session.params.group_public_key {
+        return Err(FrostError::InvalidPayload);
+    }
+    let participants: Participants = reshare_session
+        .committee
+        .iter()
+        .enumerate()
+        .map(|(position, public_key)| ((position + 1) as Identifier, public_key.clone()))
+        .collect();
+    let key_package = KeyPackage {
+        identifier,
+        secret_share,
+        group_public_key: commitments[0],
+        verifying_shares: participants
+            .keys()
+            .map(|participant| {
+                (
+                    *participant,
+                    vss::evaluate_commitments(&commitments, *participant),
+                )
+            })
+            .collect(),
+        threshold: threshold as u16,
+    };
+    if !key_package.is_consistent() {
+        return Err(FrostError::InvalidShare(identifier));
+    }
+    Ok((key_package, participants))
+}
+
+// Write the key share next to the node so it survives a restart
+fn save(
+    path: &Path,
+    key_package: &KeyPackage,
+    participants: &Participants,
+    round_id: &RoundId,
+) -> std::io::Result<()> {
+    let key_share = FrostKeyShare {
+        identifier: u32::from(key_package.identifier),
+        secret_share: frost::serialize_scalar(&key_package.secret_share),
+        group_public_key: frost::serialize_point(&key_package.group_public_key),
+        threshold: u32::from(key_package.threshold),
+        participants: participants
+            .iter()
+            .map(|(identifier, public_key)| FrostParticipant {
+                identifier: u32::from(*identifier),
+                public_key: public_key.to_vec(),
+                verifying_share: frost::serialize_point(&key_package.verifying_shares[identifier]),
+            })
+            .collect(),
+        round_id: round_id.to_vec(),
+    };
+    utils::write_private(path, &key_share.encode_to_vec())
+}
+
+// Read back a key share written by save. Returns None when no key generation has finished yet.
+fn load(path: &Path) -> Result<Option<(KeyPackage, Participants, RoundId)>, FrostError> {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(_) => return Ok(None),
+    };
+    let key_share = FrostKeyShare::decode(&bytes[..]).map_err(|_| FrostError::InvalidPayload)?;
+    let mut participants = BTreeMap::new();
+    let mut verifying_shares = BTreeMap::new();
+    for participant in key_share.participants {
+        let identifier = signing::identifier(participant.identifier)?;
+        participants.insert(identifier, participant.public_key.into());
+        verifying_shares.insert(
+            identifier,
+            frost::deserialize_point(&participant.verifying_share)?,
+        );
+    }
+    let key_package = KeyPackage {
+        identifier: signing::identifier(key_share.identifier)?,
+        secret_share: frost::deserialize_scalar(&key_share.secret_share)?,
+        group_public_key: frost::deserialize_point(&key_share.group_public_key)?,
+        verifying_shares,
+        threshold: u16::try_from(key_share.threshold).map_err(|_| FrostError::InvalidPayload)?,
+    };
+    if !key_package.is_consistent() {
+        return Err(FrostError::InvalidShare(key_package.identifier));
+    }
+    Ok(Some((key_package, participants, key_share.round_id.into())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{TestCommittee, TestNode};
+    use curve25519_dalek::constants::ED25519_BASEPOINT_POINT;
+
+    // A Dkg for every node of the committee, each with the given threshold
+    fn dkgs(committee: &mut TestCommittee, threshold: u32) -> Vec<Dkg> {
+        committee
+            .nodes
+            .iter_mut()
+            .map(|node| {
+                node.config.dkg_threshold = threshold;
+                node_dkg(node)
+            })
+            .collect()
+    }
+
+    fn node_dkg(node: &TestNode) -> Dkg {
+        Dkg::new(
+            &node.config,
+            node.peers.clone(),
+            node.outbox.clone(),
+            FrostSigner::new(node.outbox.clone()),
+            node.node_setup.sealer.clone(),
+        )
+    }
+
+    // Deliver every dealing until the committee goes quiet
+    fn run(committee: &mut TestCommittee, dkgs: &[Dkg]) {
+        loop {
+            let sent = committee.sent();
+            if sent.is_empty() {
+                return;
+            }
+            for (recipient, dealing) in sent {
+                deliver(&dkgs[recipient], dealing);
+            }
+        }
+    }
+
+    fn deliver(dkg: &Dkg, dealing: Dealing) {
+        match dealing.kind() {
+            DealingKind::DkgDealing => dkg.handle(dealing),
+            DealingKind::ReshareDealing => dkg.handle_reshare(dealing),
+            DealingKind::ReshareSelection => dkg.handle_reshare_selection(dealing),
+            _ => {}
+        }
+    }
+
+    // The group secret the shares of these members interpolate to, as a public key
+    fn interpolated_key(dkgs: &[Dkg], members: &[usize]) -> EdwardsPoint {
+        let identifiers: Vec<Identifier> = members
+            .iter()
+            .map(|member| (member + 1) as Identifier)
+            .collect();
+        let secret: Scalar = members
+            .iter()
+            .zip(&identifiers)
+            .map(|(member, identifier)| {
+                let (key_package, _) = dkgs[*member].signer.key().unwrap();
+                frost::lagrange_coefficient(*identifier, &identifiers) * key_package.secret_share
+            })
+            .sum();
+        ED25519_BASEPOINT_POINT * secret
+    }
+
+    #[test]
+    fn three_of_four_key_generation_agrees_on_a_group_key() {
+        let mut committee = TestCommittee::new(4, "dkg-three-of-four");
+        let dkgs = dkgs(&mut committee, 3);
+        let round_id = dkgs[0].start(b"key one", false);
+        run(&mut committee, &dkgs);
+
+        let (first, _) = dkgs[0].signer.key().unwrap();
+        for (position, dkg) in dkgs.iter().enumerate() {
+            let (key_package, participants) = dkg.signer.key().unwrap();
+            assert_eq!(key_package.group_public_key, first.group_public_key);
+            assert_eq!(key_package.verifying_shares, first.verifying_shares);
+            assert_eq!(key_package.threshold, 3);
+            assert_eq!(key_package.identifier, (position + 1) as Identifier);
+            assert_eq!(
+                participants.values().cloned().collect::<Vec<PublicKey>>(),
+                committee.nodes[0].peers.public_keys()
+            );
+            assert_eq!(dkg.key_round(), Some(round_id.clone()));
+        }
+        // Any three shares give the same secret, behind the group key
+        assert_eq!(interpolated_key(&dkgs, &[0, 1, 2]), first.group_public_key);
+        assert_eq!(interpolated_key(&dkgs, &[1, 2, 3]), first.group_public_key);
+        // And two don't
+        assert_ne!(interpolated_key(&dkgs, &[0, 1]), first.group_public_key);
+    }
+
+    #[test]
+    fn key_generation_with_a_missing_dealer_fails_at_the_deadline() {
+        let mut committee = TestCommittee::new(3, "dkg-deadline");
+        let dkgs = dkgs(&mut committee, 2);
+        let round_id = dkgs[0].start(b"key one", false);
+        // The last node never receives or sends anything
+        let mut held_back = Vec::new();
+        loop {
+            let sent = committee.sent();
+            if sent.is_empty() {
+                break;
+            }
+            for (recipient, dealing) in sent {
+                if recipient == 2 || dealing.public_key == committee.nodes[2].node_setup.public_key
+                {
+                    held_back.push((recipient, dealing));
+                } else {
+                    deliver(&dkgs[recipient], dealing);
+                }
+            }
+        }
+        assert!(dkgs[0].signer.key().is_none());
+        assert!(dkgs[0].sessions.lock().contains_key(&round_id));
+
+        for dkg in &dkgs {
+            dkg.expire(Duration::ZERO);
+        }
+        assert!(dkgs[0].sessions.lock().is_empty());
+        // The missing dealer catches up and deals late, which doesn't start the key generation
+        // over for the others
+        for (recipient, dealing) in held_back {
+            deliver(&dkgs[recipient], dealing);
+        }
+        let late = committee.sent();
+        assert!(late.iter().any(|(recipient, _)| *recipient != 2));
+        for (recipient, dealing) in late {
+            deliver(&dkgs[recipient], dealing);
+        }
+        assert!(committee.sent().is_empty());
+        for dkg in &dkgs[..2] {
+            assert!(dkg.sessions.lock().is_empty());
+            assert!(dkg.signer.key().is_none());
+        }
+    }
+
+    // Four nodes that ran a three of four key generation
+    fn keyed_committee(name: &str) -> (TestCommittee, Vec<Dkg>) {
+        let mut committee = TestCommittee::new(4, name);
+        let dkgs = dkgs(&mut committee, 3);
+        dkgs[0].start(b"key one", false);
+        run(&mut committee, &dkgs);
+        assert!(dkgs.iter().all(|dkg| dkg.signer.key().is_some()));
+        (committee, dkgs)
+    }
+
+    fn group_key(dkg: &Dkg) -> EdwardsPoint {
+        dkg.signer.key().unwrap().0.group_public_key
+    }
+
+    #[test]
+    fn reshare_keeps_the_group_key() {
+        let (mut committee, dkgs) = keyed_committee("dkg-reshare");
+        let group_public_key = group_key(&dkgs[0]);
+        let round_id = dkgs[1].start_reshare(b"move one", Some(2)).unwrap();
+        run(&mut committee, &dkgs);
+
+        for dkg in &dkgs {
+            let (key_package, _) = dkg.signer.key().unwrap();
+            assert_eq!(key_package.group_public_key, group_public_key);
+            assert_eq!(key_package.threshold, 2);
+            assert_eq!(dkg.key_round(), Some(round_id.clone()));
+        }
+        assert_eq!(interpolated_key(&dkgs, &[0, 3]), group_public_key);
+        assert_eq!(interpolated_key(&dkgs, &[1, 2]), group_public_key);
+    }
+
+    #[test]
+    fn node_without_a_key_only_joins_the_key_it_was_told_to() {
+        let (mut committee, mut dkgs) = keyed_committee("dkg-join");
+        let group_public_key = group_key(&dkgs[0]);
+        // The last node lost its key share
+        let _ = fs::remove_file(&committee.nodes[3].config.key_path);
+        dkgs[3] = node_dkg(&committee.nodes[3]);
+
+        dkgs[0].start_reshare(b"move one", None).unwrap();
+        run(&mut committee, &dkgs);
+        assert!(dkgs[3].signer.key().is_none());
+
+        committee.nodes[3].config.reshare_join_key =
+            Some(frost::serialize_point(&group_public_key));
+        dkgs[3] = node_dkg(&committee.nodes[3]);
+        let round_id = dkgs[0].start_reshare(b"move two", None).unwrap();
+        run(&mut committee, &dkgs);
+        assert_eq!(group_key(&dkgs[3]), group_public_key);
+        assert_eq!(dkgs[3].key_round(), Some(round_id));
+        assert_eq!(interpolated_key(&dkgs, &[1, 2, 3]), group_public_key);
+    }
+
+    #[test]
+    fn reshare_of_another_key_is_rejected() {
+        let (mut committee, dkgs) = keyed_committee("dkg-forged-params");
+        let key_rounds: Vec<Option<RoundId>> = dkgs.iter().map(Dkg::key_round).collect();
+        let group_public_key = group_key(&dkgs[0]);
+
+        // The last node claims to hold the only share of a key it made up, and reshares it
+        let attacker = committee.nodes[3].outbox.public_key().clone();
+        let secret_share = Scalar::from(7u64);
+        let forged_key = ED25519_BASEPOINT_POINT * secret_share;
+        let key_package = KeyPackage {
+            identifier: 1,
+            secret_share,
+            group_public_key: forged_key,
+            verifying_shares: BTreeMap::from([(1, forged_key)]),
+            threshold: 1,
+        };
+        dkgs[3].install(
+            key_package,
+            BTreeMap::from([(1, attacker)]),
+            &RoundId::from_static(b"forged"),
+            None,
+        );
+        dkgs[3].start_reshare(b"steal", None).unwrap();
+        run(&mut committee, &dkgs);
+
+        for (dkg, key_round) in dkgs.iter().zip(&key_rounds).take(3) {
+            assert_eq!(group_key(dkg), group_public_key);
+            assert_eq!(&dkg.key_round(), key_round);
+            assert!(dkg.reshares.lock().is_empty());
+        }
+    }
+
+    #[test]
+    fn selection_from_anyone_but_the_leader_is_rejected() {
+        let (mut committee, dkgs) = keyed_committee("dkg-forged-leader");
+        let members = committee.nodes[0].peers.public_keys();
+        let round_id = dkgs[0].start_reshare(b"move one", None).unwrap();
+        let leader = reshare_leader(&members, &round_id).unwrap().clone();
+        let impostor = members.iter().position(|member| member != &leader).unwrap();
+
+        // Every node opens the reshare, but none has combined it yet
+        for (recipient, dealing) in committee.sent() {
+            deliver(&dkgs[recipient], dealing);
+        }
+        let forged = |round_id: RoundId| Dealing {
+            public_key: members[impostor].clone(),
+            round_id,
+            kind: DealingKind::ReshareSelection as i32,
+            dealing: ReshareSelection {
+                dealers: vec![1, 2, 3],
+            }
+            .encode_to_vec()
+            .into(),
+            ..Default::default()
+        };
+        for dkg in &dkgs {
+            assert!(matches!(
+                dkg.handle_selection(&forged(round_id.clone())),
+                Err(FrostError::InvalidPayload)
+            ));
+            assert!(!dkg.selections.lock().contains_key(&round_id));
+            // A round ID too short to pick a leader from is dropped too
+            assert!(dkg
+                .handle_selection(&forged(RoundId::from_static(b"short")))
+                .is_err());
+        }
+        assert_eq!(
+            reshare_leader(&members, &RoundId::from_static(b"short")),
+            None
+        );
+
+        run(&mut committee, &dkgs);
+        assert!(dkgs
+            .iter()
+            .all(|dkg| dkg.key_round() == Some(round_id.clone())));
+    }
+
+    #[test]
+    fn refresh_more_than_one_period_off_is_rejected() {
+        let mut committee = TestCommittee::new(3, "dkg-refresh");
+        let key_refresh = Duration::from_secs(24 * 60 * 60);
+        for node in committee.nodes.iter_mut() {
+            node.config.key_refresh = Some(key_refresh);
+        }
+        let dkgs = dkgs(&mut committee, 2);
+        let key_round = dkgs[0].start(b"key one", false);
+        run(&mut committee, &dkgs);
+        let group_public_key = group_key(&dkgs[0]);
+        let current = refresh_epoch_at(SystemTime::now(), key_refresh);
+
+        for epoch in [current - 2, current + 2] {
+            dkgs[0].refresh(epoch).unwrap();
+            run(&mut committee, &dkgs);
+            assert!(dkgs
+                .iter()
+                .all(|dkg| dkg.key_round() == Some(key_round.clone())));
+        }
+
+        let round_id = dkgs[0].refresh(current).unwrap();
+        run(&mut committee, &dkgs);
+        for dkg in &dkgs {
+            assert_eq!(dkg.key_round(), Some(round_id.clone()));
+            assert_eq!(group_key(dkg), group_public_key);
+            assert_eq!(*dkg.key_refresh_epoch.lock(), Some(current));
+        }
+        // Once refreshed, the same period isn't refreshed again
+        assert_eq!(dkgs[0].refresh(current), None);
+    }
+}
@@ -0,0 +1,202 @@
+// Third Party
+use ring::signature;
+
+// Local
+use super::confidential;
+use super::rbc::{Digest, ReliableBroadcast};
+use super::scheme::DealingScheme;
+use super::types::{PublicKey, RoundId};
+use crate::sample::{Dealing, DealingKind};
+
+// Domain separation so a dealing signature can never be mistaken for any other signature made
+// with the node's identity key
+const DEALING_SIGNATURE_CONTEXT: &[u8] = b"tokio-sample dealing v2";
+
+// The bytes a sender signs for a dealing: everything that identifies it except the signature
+pub fn signing_payload(dealing: &Dealing) -> Vec<u8> {
+    let mut payload = Vec::with_capacity(
+        DEALING_SIGNATURE_CONTEXT.len()
+            + 20
+            + dealing.round_id.len()
+            + dealing.public_key.len()
+            + dealing.recipient.len()
+            + dealing.dealing.len(),
+    );
+    payload.extend_from_slice(DEALING_SIGNATURE_CONTEXT);
+    payload.extend_from_slice(&(dealing.round_id.len() as u32).to_be_bytes());
+    payload.extend_from_slice(&dealing.round_id);
+    payload.extend_from_slice(&dealing.protocol_round.to_be_bytes());
+    payload.extend_from_slice(&dealing.kind.to_be_bytes());
+    payload.extend_from_slice(&(dealing.public_key.len() as u32).to_be_bytes());
+    payload.extend_from_slice(&dealing.public_key);
+    payload.extend_from_slice(&(dealing.recipient.len() as u32).to_be_bytes());
+    payload.extend_from_slice(&dealing.recipient);
+    payload.extend_from_slice(&dealing.dealing);
+    payload
+}
+
+// Check that a dealing was signed by the public key it claims to come from
+pub fn verify_dealing(dealing: &Dealing) -> bool {
+    signature::UnparsedPublicKey::new(&signature::ED25519, &dealing.public_key)
+        .verify(&signing_payload(dealing), &dealing.signature)
+        .is_ok()
+}
+
+// Proof that one sender signed two dealings for the same round whose commitments differ
+// Every recipient gets its own sealed payload, so what has to match is the part the dealer sends
+// everyone alike, the same digest reliable broadcast agrees on. Each dealing comes with the key it
+// was sealed with, revealed by its recipient, so any node can open both and compare them.
+#[derive(Clone)]
+pub struct Equivocation {
+    pub first: Dealing,
+    pub first_key: Vec<u8>,
+    pub second: Dealing,
+    pub second_key: Vec<u8>,
+}
+
+impl Equivocation {
+    pub fn round(&self) -> RoundId {
+        self.first.round_id.clone()
+    }
+
+    pub fn sender(&self) -> &PublicKey {
+        &self.first.public_key
+    }
+
+    // Evidence is only valid if it convicts its sender on its own, so that it can be forwarded
+    // to and checked by nodes that never saw either dealing
+    pub fn verify(&self, scheme: &dyn DealingScheme) -> bool {
+        if self.first.public_key != self.second.public_key
+            || self.first.round_id != self.second.round_id
+            || self.first.kind() != DealingKind::Random
+            || self.second.kind() != DealingKind::Random
+            || !verify_dealing(&self.first)
+            || !verify_dealing(&self.second)
+        {
+            return false;
+        }
+        match (
+            public_digest(&self.first, &self.first_key, scheme),
+            public_digest(&self.second, &self.second_key, scheme),
+        ) {
+            (Some(first), Some(second)) => first != second,
+            _ => false,
+        }
+    }
+}
+
+// The digest of the part of a dealing its sender sends every recipient alike
+fn public_digest(dealing: &Dealing, key: &[u8], scheme: &dyn DealingScheme) -> Option<Digest> {
+    let payload = confidential::open_with_key(key, dealing).ok()?;
+    let public_part = scheme.public_part(&payload).ok()?;
+    Some(ReliableBroadcast::digest(
+        &dealing.round_id,
+        &dealing.public_key,
+        &public_part,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scheme::FeldmanVss;
+    use crate::testing::TestCommittee;
+
+    // Node 1 deals to node 0 twice with different polynomials, and deals the first polynomial to
+    // node 2 as well
+    fn dealings(committee: &TestCommittee) -> (Dealing, Dealing, Dealing) {
+        let round_id = RoundId::from_static(&[7; 32]);
+        let members = committee.nodes[0].peers.public_keys();
+        let first = FeldmanVss.deal(&members, 2, 0);
+        let second = FeldmanVss.deal(&members, 2, 0);
+        (
+            committee.random_dealing(1, 0, &round_id, &first[&members[0]]),
+            committee.random_dealing(1, 0, &round_id, &second[&members[0]]),
+            committee.random_dealing(1, 2, &round_id, &first[&members[2]]),
+        )
+    }
+
+    fn revealed(committee: &TestCommittee, recipient: usize, dealing: &Dealing) -> Vec<u8> {
+        committee.nodes[recipient]
+            .node_setup
+            .sealer
+            .reveal_key(dealing)
+            .unwrap()
+    }
+
+    #[test]
+    fn dealings_with_different_commitments_convict_their_sender() {
+        let committee = TestCommittee::new(3, "evidence-convicts");
+        let (first, second, _) = dealings(&committee);
+        let evidence = Equivocation {
+            first_key: revealed(&committee, 0, &first),
+            first,
+            second_key: revealed(&committee, 0, &second),
+            second,
+        };
+        assert!(evidence.verify(&FeldmanVss));
+        assert_eq!(evidence.sender(), &committee.public_key(1));
+    }
+
+    #[test]
+    fn dealings_with_the_same_commitments_are_no_evidence() {
+        let committee = TestCommittee::new(3, "evidence-same-commitments");
+        let (first, _, to_another) = dealings(&committee);
+        // The sealed payloads differ, but the dealer sent both recipients the same polynomial
+        assert_ne!(first.dealing, to_another.dealing);
+        let evidence = Equivocation {
+            first_key: revealed(&committee, 0, &first),
+            first,
+            second_key: revealed(&committee, 2, &to_another),
+            second: to_another,
+        };
+        assert!(!evidence.verify(&FeldmanVss));
+    }
+
+    #[test]
+    fn evidence_that_does_not_stand_on_its_own_is_rejected() {
+        let committee = TestCommittee::new(3, "evidence-rejected");
+        let (first, second, _) = dealings(&committee);
+        let evidence = Equivocation {
+            first_key: revealed(&committee, 0, &first),
+            first,
+            second_key: revealed(&committee, 0, &second),
+            second,
+        };
+
+        let mut forged_signature = evidence.clone();
+        let mut signature = forged_signature.second.signature.to_vec();
+        signature[0] ^= 1;
+        forged_signature.second.signature = signature.into();
+        assert!(!forged_signature.verify(&FeldmanVss));
+
+        let mut wrong_key = evidence.clone();
+        wrong_key.second_key = vec![0; wrong_key.second_key.len()];
+        assert!(!wrong_key.verify(&FeldmanVss));
+
+        // Two dealings of another round, or from another sender, don't add up to evidence either
+        let members = committee.nodes[0].peers.public_keys();
+        let payloads = FeldmanVss.deal(&members, 2, 0);
+        let other_round = committee.random_dealing(
+            1,
+            0,
+            &RoundId::from_static(&[8; 32]),
+            &payloads[&members[0]],
+        );
+        let other_sender =
+            committee.random_dealing(2, 0, &evidence.round(), &payloads[&members[0]]);
+        for second in [other_round, other_sender] {
+            let mut mismatched = evidence.clone();
+            mismatched.second_key = revealed(&committee, 0, &second);
+            mismatched.second = second;
+            assert!(!mismatched.verify(&FeldmanVss));
+        }
+
+        let mut not_random = evidence.clone();
+        not_random.second.kind = DealingKind::DkgDealing as i32;
+        committee.nodes[1]
+            .node_setup
+            .sign_dealing(&mut not_random.second);
+        assert!(!not_random.verify(&FeldmanVss));
+    }
+}
@@ -0,0 +1,623 @@
+// FROST(Ed25519, SHA-512) two-round threshold signing as specified in RFC 9591
+// The resulting signatures are plain Ed25519 signatures under the group public key.
+
+// System
+use std::collections::BTreeMap;
+use std::fmt;
+
+// Third Party
+use curve25519_dalek::edwards::{CompressedEdwardsY, EdwardsPoint};
+use curve25519_dalek::scalar::Scalar;
+use curve25519_dalek::traits::IsIdentity;
+use ring::digest;
+use ring::rand::{SecureRandom, SystemRandom};
+
+const CONTEXT_STRING: &[u8] = b"FROST-ED25519-SHA512-v1";
+
+// A participant's identifier is its non-zero x coordinate on the sharing polynomial
+pub type Identifier = u16;
+
+#[derive(Debug)]
+pub enum FrostError {
+    InvalidScalar,
+    InvalidPoint,
+    InvalidShare(Identifier),
+    MissingCommitment(Identifier),
+    DuplicateIdentifier(Identifier),
+    // The commitment listed for a participant is not the one it made
+    AlteredCommitment(Identifier),
+    InvalidSignature,
+    InvalidPayload,
+    // The signing key was replaced while a session was under way
+    KeyRotated,
+    // A reshare of a key this node neither holds nor was asked to join
+    ForeignKey,
+    // A dealing for a key generation or reshare that has already ended
+    SessionEnded,
+}
+
+impl fmt::Display for FrostError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FrostError::InvalidScalar => write!(f, "invalid scalar encoding"),
+            FrostError::InvalidPoint => write!(f, "invalid point encoding"),
+            FrostError::InvalidShare(identifier) => {
+                write!(f, "invalid share from participant {}", identifier)
+            }
+            FrostError::MissingCommitment(identifier) => {
+                write!(f, "no commitment from participant {}", identifier)
+            }
+            FrostError::DuplicateIdentifier(identifier) => {
+                write!(f, "participant {} appears twice", identifier)
+            }
+            FrostError::AlteredCommitment(identifier) => {
+                write!(f, "participant {}'s commitment was altered", identifier)
+            }
+            FrostError::InvalidSignature => write!(f, "the group signature does not verify"),
+            FrostError::InvalidPayload => write!(f, "malformed FROST message"),
+            FrostError::KeyRotated => write!(f, "key rotated"),
+            FrostError::ForeignKey => write!(f, "not the key this node holds or may join"),
+            FrostError::SessionEnded => write!(f, "the session has ended"),
+        }
+    }
+}
+
+// Everything a participant needs to take part in signing for one group key
+#[derive(Clone)]
+pub struct KeyPackage {
+    pub identifier: Identifier,
+    pub secret_share: Scalar,
+    pub group_public_key: EdwardsPoint,
+    // The public counterpart of every participant's secret share, used to check signature shares
+    pub verifying_shares: BTreeMap<Identifier, EdwardsPoint>,
+    pub threshold: u16,
+}
+
+// The two single-use nonces a participant commits to in round one
+pub struct SigningNonces {
+    hiding: Scalar,
+    binding: Scalar,
+}
+
+#[derive(Clone, Copy)]
+pub struct SigningCommitment {
+    pub identifier: Identifier,
+    pub hiding: EdwardsPoint,
+    pub binding: EdwardsPoint,
+}
+
+impl KeyPackage {
+    // Check that the secret share matches the verifying share published for it
+    pub fn is_consistent(&self) -> bool {
+        self.verifying_shares.get(&self.identifier)
+            == Some(&EdwardsPoint::mul_base(&self.secret_share))
+    }
+}
+
+// Round one: generate nonces and the commitment to send to the coordinator
+pub fn commit(key_package: &KeyPackage) -> (SigningNonces, SigningCommitment) {
+    let nonces = SigningNonces {
+        hiding: nonce_generate(&key_package.secret_share),
+        binding: nonce_generate(&key_package.secret_share),
+    };
+    let commitment = SigningCommitment {
+        identifier: key_package.identifier,
+        hiding: EdwardsPoint::mul_base(&nonces.hiding),
+        binding: EdwardsPoint::mul_base(&nonces.binding),
+    };
+    (nonces, commitment)
+}
+
+// Round two: produce this participant's share of the signature. The nonces are consumed so they
+// can never be used twice.
+pub fn sign(
+    key_package: &KeyPackage,
+    nonces: SigningNonces,
+    message: &[u8],
+    commitments: &[SigningCommitment],
+) -> Result<Scalar, FrostError> {
+    let commitments = sorted_commitments(commitments)?;
+    // RFC 9591 section 5.2: my own commitment must be in the list, exactly as I made it
+    let mine = commitments
+        .iter()
+        .find(|c| c.identifier == key_package.identifier)
+        .ok_or(FrostError::MissingCommitment(key_package.identifier))?;
+    if mine.hiding != EdwardsPoint::mul_base(&nonces.hiding)
+        || mine.binding != EdwardsPoint::mul_base(&nonces.binding)
+    {
+        return Err(FrostError::AlteredCommitment(key_package.identifier));
+    }
+    let binding_factors =
+        compute_binding_factors(&key_package.group_public_key, &commitments, message);
+    let binding_factor = binding_factors
+        .get(&key_package.identifier)
+        .ok_or(FrostError::MissingCommitment(key_package.identifier))?;
+    let group_commitment = compute_group_commitment(&commitments, &binding_factors);
+    let participants: Vec<Identifier> = commitments.iter().map(|c| c.identifier).collect();
+    let lambda = lagrange_coefficient(key_package.identifier, &participants);
+    let challenge = compute_challenge(&group_commitment, &key_package.group_public_key, message);
+    Ok(nonces.hiding
+        + nonces.binding * binding_factor
+        + lambda * key_package.secret_share * challenge)
+}
+
+// Check one participant's signature share before aggregating it
+pub fn verify_signature_share(
+    identifier: Identifier,
+    verifying_share: &EdwardsPoint,
+    signature_share: &Scalar,
+    group_public_key: &EdwardsPoint,
+    message: &[u8],
+    commitments: &[SigningCommitment],
+) -> Result<(), FrostError> {
+    let commitments = sorted_commitments(commitments)?;
+    let binding_factors = compute_binding_factors(group_public_key, &commitments, message);
+    let commitment = commitments
+        .iter()
+        .find(|c| c.identifier == identifier)
+        .ok_or(FrostError::MissingCommitment(identifier))?;
+    let binding_factor = binding_factors[&identifier];
+    let group_commitment = compute_group_commitment(&commitments, &binding_factors);
+    let participants: Vec<Identifier> = commitments.iter().map(|c| c.identifier).collect();
+    let lambda = lagrange_coefficient(identifier, &participants);
+    let challenge = compute_challenge(&group_commitment, group_public_key, message);
+    let commitment_share = commitment.hiding + commitment.binding * binding_factor;
+    if EdwardsPoint::mul_base(signature_share)
+        == commitment_share + verifying_share * (challenge * lambda)
+    {
+        Ok(())
+    } else {
+        Err(FrostError::InvalidShare(identifier))
+    }
+}
+
+// Combine the signature shares of every participant in the commitment list into a 64 byte
+// Ed25519 signature
+pub fn aggregate(
+    group_public_key: &EdwardsPoint,
+    message: &[u8],
+    commitments: &[SigningCommitment],
+    signature_shares: &BTreeMap<Identifier, Scalar>,
+) -> Result<Vec<u8>, FrostError> {
+    let commitments = sorted_commitments(commitments)?;
+    let binding_factors = compute_binding_factors(group_public_key, &commitments, message);
+    let group_commitment = compute_group_commitment(&commitments, &binding_factors);
+    let mut z = Scalar::ZERO;
+    for commitment in &commitments {
+        z += signature_shares
+            .get(&commitment.identifier)
+            .ok_or(FrostError::InvalidShare(commitment.identifier))?;
+    }
+    let mut signature = group_commitment.compress().to_bytes().to_vec();
+    signature.extend_from_slice(z.as_bytes());
+    Ok(signature)
+}
+
+// Standard Ed25519 verification, so anything that verifies here verifies everywhere
+pub fn verify_signature(
+    group_public_key: &EdwardsPoint,
+    message: &[u8],
+    signature: &[u8],
+) -> Result<(), FrostError> {
+    ring::signature::UnparsedPublicKey::new(
+        &ring::signature::ED25519,
+        group_public_key.compress().as_bytes(),
+    )
+    .verify(message, signature)
+    .map_err(|_| FrostError::InvalidSignature)
+}
+
+pub fn serialize_scalar(scalar: &Scalar) -> Vec<u8> {
+    scalar.to_bytes().to_vec()
+}
+
+pub fn deserialize_scalar(bytes: &[u8]) -> Result<Scalar, FrostError> {
+    let bytes: [u8; 32] = bytes.try_into().map_err(|_| FrostError::InvalidScalar)?;
+    Option::from(Scalar::from_canonical_bytes(bytes)).ok_or(FrostError::InvalidScalar)
+}
+
+pub fn serialize_point(point: &EdwardsPoint) -> Vec<u8> {
+    point.compress().to_bytes().to_vec()
+}
+
+// Rejects the identity, which is never a valid commitment or key, and points with a small order
+// component, which are not in the prime order group
+pub fn deserialize_point(bytes: &[u8]) -> Result<EdwardsPoint, FrostError> {
+    let point = CompressedEdwardsY::from_slice(bytes)
+        .map_err(|_| FrostError::InvalidPoint)?
+        .decompress()
+        .ok_or(FrostError::InvalidPoint)?;
+    if point.is_identity() || !point.is_torsion_free() {
+        return Err(FrostError::InvalidPoint);
+    }
+    Ok(point)
+}
+
+pub fn random_scalar() -> Scalar {
+    let mut bytes = [0u8; 64];
+    SystemRandom::new().fill(&mut bytes).unwrap();
+    Scalar::from_bytes_mod_order_wide(&bytes)
+}
+
+pub fn identifier_scalar(identifier: Identifier) -> Scalar {
+    Scalar::from(u64::from(identifier))
+}
+
+pub fn evaluate_polynomial(coefficients: &[Scalar], identifier: Identifier) -> Scalar {
+    let x = identifier_scalar(identifier);
+    coefficients
+        .iter()
+        .rev()
+        .fold(Scalar::ZERO, |value, coefficient| value * x + coefficient)
+}
+
+// The Lagrange coefficient of participant i for interpolating at zero over the participants
+pub fn lagrange_coefficient(identifier: Identifier, participants: &[Identifier]) -> Scalar {
+    let x_i = identifier_scalar(identifier);
+    let mut numerator = Scalar::ONE;
+    let mut denominator = Scalar::ONE;
+    for participant in participants {
+        if *participant == identifier {
+            continue;
+        }
+        let x_j = identifier_scalar(*participant);
+        numerator *= x_j;
+        denominator *= x_j - x_i;
+    }
+    numerator * denominator.invert()
+}
+
+fn nonce_generate(secret: &Scalar) -> Scalar {
+    let mut random_bytes = [0u8; 32];
+    SystemRandom::new().fill(&mut random_bytes).unwrap();
+    hash_to_scalar(&[CONTEXT_STRING, b"nonce", &random_bytes, secret.as_bytes()])
+}
+
+fn sorted_commitments(
+    commitments: &[SigningCommitment],
+) -> Result<Vec<SigningCommitment>, FrostError> {
+    let mut sorted = commitments.to_vec();
+    sorted.sort_unstable_by_key(|c| c.identifier);
+    for pair in sorted.windows(2) {
+        if pair[0].identifier == pair[1].identifier {
+            return Err(FrostError::DuplicateIdentifier(pair[0].identifier));
+        }
+    }
+    Ok(sorted)
+}
+
+fn compute_binding_factors(
+    group_public_key: &EdwardsPoint,
+    commitments: &[SigningCommitment],
+    message: &[u8],
+) -> BTreeMap<Identifier, Scalar> {
+    let mut encoded_commitments = Vec::with_capacity(commitments.len() * 96);
+    for commitment in commitments {
+        encoded_commitments.extend_from_slice(identifier_scalar(commitment.identifier).as_bytes());
+        encoded_commitments.extend_from_slice(commitment.hiding.compress().as_bytes());
+        encoded_commitments.extend_from_slice(commitment.binding.compress().as_bytes());
+    }
+    let message_hash = hash(&[CONTEXT_STRING, b"msg", message]);
+    let commitments_hash = hash(&[CONTEXT_STRING, b"com", &encoded_commitments]);
+    let group_public_key = group_public_key.compress();
+    commitments
+        .iter()
+        .map(|commitment| {
+            let binding_factor = hash_to_scalar(&[
+                CONTEXT_STRING,
+                b"rho",
+                group_public_key.as_bytes(),
+                &message_hash,
+                &commitments_hash,
+                identifier_scalar(commitment.identifier).as_bytes(),
+            ]);
+            (commitment.identifier, binding_factor)
+        })
+        .collect()
+}
+
+fn compute_group_commitment(
+    commitments: &[SigningCommitment],
+    binding_factors: &BTreeMap<Identifier, Scalar>,
+) -> EdwardsPoint {
+    commitments
+        .iter()
+        .map(|c| c.hiding + c.binding * binding_factors[&c.identifier])
+        .sum()
+}
+
+// This is the Ed25519 challenge, without FROST's context string, which is what makes the output a
+// standard Ed25519 signature
+fn compute_challenge(
+    group_commitment: &EdwardsPoint,
+    group_public_key: &EdwardsPoint,
+    message: &[u8],
+) -> Scalar {
+    hash_to_scalar(&[
+        group_commitment.compress().as_bytes(),
+        group_public_key.compress().as_bytes(),
+        message,
+    ])
+}
+
+fn hash(parts: &[&[u8]]) -> Vec<u8> {
+    let mut context = digest::Context::new(&digest::SHA512);
+    for part in parts {
+        context.update(part);
+    }
+    context.finish().as_ref().to_vec()
+}
+
+fn hash_to_scalar(parts: &[&[u8]]) -> Scalar {
+    let digest: [u8; 64] = hash(parts).try_into().unwrap();
+    Scalar::from_bytes_mod_order_wide(&digest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    fn scalar(hex: &str) -> Scalar {
+        deserialize_scalar(&decode(hex)).unwrap()
+    }
+
+    fn key_packages(
+        coefficients: &[Scalar],
+        identifiers: &[Identifier],
+        group_public_key: EdwardsPoint,
+    ) -> Vec<KeyPackage> {
+        let verifying_shares: BTreeMap<Identifier, EdwardsPoint> = identifiers
+            .iter()
+            .map(|identifier| {
+                let share = evaluate_polynomial(coefficients, *identifier);
+                (*identifier, EdwardsPoint::mul_base(&share))
+            })
+            .collect();
+        identifiers
+            .iter()
+            .map(|identifier| KeyPackage {
+                identifier: *identifier,
+                secret_share: evaluate_polynomial(coefficients, *identifier),
+                group_public_key,
+                verifying_shares: verifying_shares.clone(),
+                threshold: coefficients.len() as u16,
+            })
+            .collect()
+    }
+
+    // The key generation part of RFC 9591 appendix E.1
+    #[test]
+    fn rfc_9591_key_shares() {
+        let group_secret =
+            scalar("7b1c33d3f5291d85de664833beb1ad469f7fb6025a0ec78b3a790c6e13a98304");
+        let coefficients = [
+            group_secret,
+            scalar("178199860edd8c62f5212ee91eff1295d0d670ab4ed4506866bae57e7030b204"),
+        ];
+        assert_eq!(
+            serialize_point(&EdwardsPoint::mul_base(&group_secret)),
+            decode("15d21ccd7ee42959562fc8aa63224c8851fb3ec85a3faf66040d380fb9738673")
+        );
+        let shares = [
+            "929dcc590407aae7d388761cddb0c0db6f5627aea8e217f4a033f2ec83d93509",
+            "a91e66e012e4364ac9aaa405fcafd370402d9859f7b6685c07eed76bf409e80d",
+            "d3cb090a075eb154e82fdb4b3cb507f110040905468bb9c46da8bdea643a9a02",
+        ];
+        for (identifier, share) in (1..).zip(shares) {
+            assert_eq!(
+                evaluate_polynomial(&coefficients, identifier),
+                scalar(share)
+            );
+        }
+        let participants = [1, 3];
+        let interpolated: Scalar = participants
+            .iter()
+            .map(|identifier| {
+                lagrange_coefficient(*identifier, &participants)
+                    * evaluate_polynomial(&coefficients, *identifier)
+            })
+            .sum();
+        assert_eq!(interpolated, group_secret);
+    }
+
+    // The signing part of RFC 9591 appendix E.1: participants 1 and 3 sign "test"
+    #[test]
+    fn rfc_9591_signature() {
+        let group_secret =
+            scalar("7b1c33d3f5291d85de664833beb1ad469f7fb6025a0ec78b3a790c6e13a98304");
+        let coefficients = [
+            group_secret,
+            scalar("178199860edd8c62f5212ee91eff1295d0d670ab4ed4506866bae57e7030b204"),
+        ];
+        let group_public_key = EdwardsPoint::mul_base(&group_secret);
+        let signers = key_packages(&coefficients, &[1, 3], group_public_key);
+        let message = decode("74657374");
+        // Each nonce is derived from the vector's randomness and the signer's share
+        let vectors = [
+            (
+                "0fd2e39e111cdc266f6c0f4d0fd45c947761f1f5d3cb583dfcb9bbaf8d4c9fec",
+                "812d6104142944d5a55924de6d49940956206909f2acaeedecda2b726e630407",
+                "b5aa8ab305882a6fc69cbee9327e5a45e54c08af61ae77cb8207be3d2ce13de3",
+                "69cd85f631d5f7f2721ed5e40519b1366f340a87c2f6856363dbdcda348a7501",
+                "b1110165fc2334149750b28dd813a39244f315cff14d4e89e6142f262ed83301",
+                "67e98ab55aa310c3120418e5050c9cf76cf387cb20ac9e4b6fdb6f82a469f932",
+            ),
+            (
+                "86d64a260059e495d0fb4fcc17ea3da7452391baa494d4b00321098ed2a0062f",
+                "c256de65476204095ebdc01bd11dc10e57b36bc96284595b8215222374f99c0e",
+                "cfbdb165bd8aad6eb79deb8d287bcc0ab6658ae57fdcc98ed12c0669e90aec91",
+                "13e6b25afb2eba51716a9a7d44130c0dbae0004a9ef8d7b5550c8a0e07c61775",
+                "243d71944d929063bc51205714ae3c2218bd3451d0214dfb5aeec2a90c35180d",
+                "7487bc41a6e712eea2f2af24681b58b1cf1da278ea11fe4e8b78398965f13552",
+            ),
+        ];
+        let mut nonces = Vec::new();
+        let mut commitments = Vec::new();
+        for (
+            key_package,
+            (
+                hiding_randomness,
+                hiding,
+                hiding_commitment,
+                binding_randomness,
+                binding,
+                binding_commitment,
+            ),
+        ) in signers.iter().zip(vectors)
+        {
+            let secret = key_package.secret_share.as_bytes();
+            for (randomness, nonce) in [(hiding_randomness, hiding), (binding_randomness, binding)]
+            {
+                let derived =
+                    hash_to_scalar(&[CONTEXT_STRING, b"nonce", &decode(randomness), secret]);
+                assert_eq!(derived, scalar(nonce));
+            }
+            let signing_nonces = SigningNonces {
+                hiding: scalar(hiding),
+                binding: scalar(binding),
+            };
+            let commitment = SigningCommitment {
+                identifier: key_package.identifier,
+                hiding: EdwardsPoint::mul_base(&signing_nonces.hiding),
+                binding: EdwardsPoint::mul_base(&signing_nonces.binding),
+            };
+            assert_eq!(
+                serialize_point(&commitment.hiding),
+                decode(hiding_commitment)
+            );
+            assert_eq!(
+                serialize_point(&commitment.binding),
+                decode(binding_commitment)
+            );
+            nonces.push(signing_nonces);
+            commitments.push(commitment);
+        }
+
+        let binding_factors = compute_binding_factors(&group_public_key, &commitments, &message);
+        assert_eq!(
+            binding_factors[&1],
+            scalar("f2cb9d7dd9beff688da6fcc83fa89046b3479417f47f55600b106760eb3b5603")
+        );
+        assert_eq!(
+            binding_factors[&3],
+            scalar("b087686bf35a13f3dc78e780a34b0fe8a77fef1b9938c563f5573d71d8d7890f")
+        );
+
+        let expected_shares = [
+            "001719ab5a53ee1a12095cd088fd149702c0720ce5fd2f29dbecf24b7281b603",
+            "bd86125de990acc5e1f13781d8e32c03a9bbd4c53539bbc106058bfd14326007",
+        ];
+        let mut signature_shares = BTreeMap::new();
+        for ((key_package, nonces), expected) in signers.iter().zip(nonces).zip(expected_shares) {
+            let share = sign(key_package, nonces, &message, &commitments).unwrap();
+            assert_eq!(share, scalar(expected));
+            signature_shares.insert(key_package.identifier, share);
+        }
+        let signature =
+            aggregate(&group_public_key, &message, &commitments, &signature_shares).unwrap();
+        assert_eq!(
+            signature,
+            decode(
+                "36282629c383bb820a88b71cae937d41f2f2adfcc3d02e55507e2fb9e2dd3cbe\
+                 bd9d2b0844e49ae0f3fa935161e1419aab7b47d21a37ebeae1f17d4987b3160b"
+            )
+        );
+        verify_signature(&group_public_key, &message, &signature).unwrap();
+    }
+
+    // Split the key and the nonce of RFC 8032's first Ed25519 test vector between two of three
+    // signers. With the binding nonces left at zero the group commitment is the Ed25519 nonce
+    // commitment, so the aggregate must be the vector's signature byte for byte.
+    #[test]
+    fn matches_rfc_8032_signature() {
+        let seed = decode("9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60");
+        let expanded = hash(&[&seed]);
+        let mut secret: [u8; 32] = expanded[..32].try_into().unwrap();
+        secret[0] &= 248;
+        secret[31] &= 127;
+        secret[31] |= 64;
+        let group_secret = Scalar::from_bytes_mod_order(secret);
+        let message = b"";
+        let nonce = hash_to_scalar(&[&expanded[32..], message]);
+        let group_public_key = EdwardsPoint::mul_base(&group_secret);
+        assert_eq!(
+            serialize_point(&group_public_key),
+            decode("d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a")
+        );
+        let signers = key_packages(&[group_secret, random_scalar()], &[1, 3], group_public_key);
+        let first_nonce = random_scalar();
+        let nonces = [first_nonce, nonce - first_nonce].map(|hiding| SigningNonces {
+            hiding,
+            binding: Scalar::ZERO,
+        });
+        let commitments: Vec<SigningCommitment> = signers
+            .iter()
+            .zip(&nonces)
+            .map(|(key_package, nonces)| SigningCommitment {
+                identifier: key_package.identifier,
+                hiding: EdwardsPoint::mul_base(&nonces.hiding),
+                binding: EdwardsPoint::mul_base(&nonces.binding),
+            })
+            .collect();
+        let mut signature_shares = BTreeMap::new();
+        for (key_package, nonces) in signers.iter().zip(nonces) {
+            let share = sign(key_package, nonces, message, &commitments).unwrap();
+            verify_signature_share(
+                key_package.identifier,
+                &key_package.verifying_shares[&key_package.identifier],
+                &share,
+                &group_public_key,
+                message,
+                &commitments,
+            )
+            .unwrap();
+            signature_shares.insert(key_package.identifier, share);
+        }
+        let signature =
+            aggregate(&group_public_key, message, &commitments, &signature_shares).unwrap();
+        assert_eq!(
+            signature,
+            decode(
+                "e5564300c360ac729086e2cc806e828a84877f1eb8e5d974d873e065224901555fb8821590a33bac\
+                 c61e39701cf9b46bd25bf5f0595bbe24655141438e7a100b"
+            )
+        );
+        verify_signature(&group_public_key, message, &signature).unwrap();
+    }
+
+    #[test]
+    fn rejects_an_altered_share() {
+        let group_secret = random_scalar();
+        let group_public_key = EdwardsPoint::mul_base(&group_secret);
+        let signers = key_packages(&[group_secret, random_scalar()], &[2, 3], group_public_key);
+        let (nonces, commitments): (Vec<_>, Vec<_>) = signers.iter().map(commit).unzip();
+        let message = b"altered";
+        let mut signature_shares = BTreeMap::new();
+        for (key_package, nonces) in signers.iter().zip(nonces) {
+            let share = sign(key_package, nonces, message, &commitments).unwrap();
+            signature_shares.insert(key_package.identifier, share);
+        }
+        let signature =
+            aggregate(&group_public_key, message, &commitments, &signature_shares).unwrap();
+        verify_signature(&group_public_key, message, &signature).unwrap();
+        let altered = signature_shares[&3] + Scalar::ONE;
+        assert!(matches!(
+            verify_signature_share(
+                3,
+                &signers[1].verifying_shares[&3],
+                &altered,
+                &group_public_key,
+                message,
+                &commitments,
+            ),
+            Err(FrostError::InvalidShare(3))
+        ));
+    }
+}
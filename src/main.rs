@@ -2,14 +2,35 @@
 use std::env;
 
 // Third Party
+use tonic::codec::CompressionEncoding;
 use tonic::transport::Server;
 
 // Local
+mod aggregator;
+mod batch;
+mod beacon;
+mod complaint;
+mod confidential;
+mod config;
+mod dkg;
+mod evidence;
+mod frost;
 mod node_setup;
+mod outbox;
 mod peer;
+mod rbc;
+mod rounds;
 mod sample_grpc;
+mod scheme;
+mod session;
+mod signing;
+#[cfg(test)]
+mod testing;
+mod traffic;
 mod types;
 mod utils;
+mod vss;
+mod wire;
 #[allow(clippy::pedantic)]
 #[rustfmt::skip]
 pub mod sample { // This code is generated by prost
@@ -18,8 +39,11 @@ pub mod sample { // This code is generated by prost
     pub(crate) const FILE_DESCRIPTOR_SET: &[u8] =
         tonic::include_file_descriptor_set!("sample_descriptor");
 }
+use config::NodeConfig;
 use sample::sample_server::SampleServer;
 use sample_grpc::MySample;
+use scheme::Schemes;
+use traffic::MeteredServer;
 
 #[tokio::main]
 // Start a node's gRPC server
@@ -29,16 +53,33 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let hostname: String = args[2].parse().unwrap();
     let addr = "[::0]:2323".parse().unwrap();
 
-    let sample = MySample::new(node_count, hostname);
+    // Register any additional dealing schemes here to make them selectable with DEALING_SCHEME
+    let schemes = Schemes::default();
+    let config = NodeConfig::new(node_count, hostname);
+    let sample = MySample::new(config.clone(), &schemes);
+    let traffic = sample.traffic();
+    // Compressed requests are always accepted so that nodes with different settings interoperate
+    let mut sample_server = SampleServer::new(sample)
+        .accept_compressed(CompressionEncoding::Gzip)
+        .accept_compressed(CompressionEncoding::Zstd);
+    if let Some(compression) = config.compression {
+        sample_server = sample_server.send_compressed(compression);
+    }
     // The reflection service is for grpcurl command line compatibility for testing
     let reflection_service = tonic_reflection::server::Builder::configure()
         .register_encoded_file_descriptor_set(sample::FILE_DESCRIPTOR_SET)
         .build()
         .unwrap();
     utils::debug_line_to_file("Running.", "servers_running.debug.txt");
-    Server::builder()
+    config
+        .transport
+        .server(Server::builder())
         .add_service(reflection_service)
-        .add_service(SampleServer::new(sample))
+        .add_service(MeteredServer::new(
+            sample_server,
+            traffic,
+            config.compression,
+        ))
         .serve(addr)
         .await?;
     Ok(())
@@ -3,47 +3,71 @@ use ring::{
     rand,
     signature::{self, KeyPair},
 };
+use std::fs;
+use std::path::Path;
 use std::sync::Arc;
 
-// Third Party
-use parking_lot::RwLock;
-
 // Local
-use super::types::ProtocolRoundIndex;
+use super::confidential::Sealer;
+use super::evidence;
+use super::types::PublicKey;
+use super::utils;
+use crate::sample::Dealing;
 
 #[derive(Clone)]
 pub struct NodeSetup {
-    pub receivers: u32,
-    pub ad: Vec<u8>,
-    _key: Arc<signature::Ed25519KeyPair>,
-    pub public_key: Vec<u8>,
-    // See here for thread safe interior mutability: https://ricardomartins.cc/2016/06/25/interior-mutability-thread-safety
-    // Cell and RefCell are interior mutability on a single thread only
-    protocol_round: Arc<RwLock<ProtocolRoundIndex>>,
+    key: Arc<signature::Ed25519KeyPair>,
+    pub public_key: PublicKey,
+    // Seals payloads to peers and opens the ones sealed to me, with a key derived from my identity
+    pub sealer: Sealer,
 }
 
 impl NodeSetup {
-    pub fn new(receivers: u32) -> Result<Self, ()> {
-        let ad: Vec<u8> = "asdfasdfasdfasdfasdfasdf".as_bytes().to_vec();
-
-        let rng = rand::SystemRandom::new();
-        let pkcs8_bytes = signature::Ed25519KeyPair::generate_pkcs8(&rng).unwrap();
-        let _key = Arc::new(signature::Ed25519KeyPair::from_pkcs8(pkcs8_bytes.as_ref()).unwrap());
-        let public_key = _key.public_key().as_ref().to_vec();
+    // Load this node's identity from identity_path, or generate one and store it there. The group
+    // key share is only usable with the identity it was generated for.
+    pub fn new(identity_path: &Path) -> Result<Self, ()> {
+        // Keep the seed rather than a PKCS#8 document so that the sealing key can be derived from it
+        let seed = load_or_generate_seed(identity_path);
+        let key = Arc::new(signature::Ed25519KeyPair::from_seed_unchecked(&seed).unwrap());
+        let public_key: PublicKey = key.public_key().as_ref().to_vec().into();
+        let sealer = Sealer::new(&seed, public_key.clone());
 
         Ok(Self {
-            receivers,
-            ad,
-            _key,
+            key,
             public_key,
-            protocol_round: Arc::new(RwLock::new(0)),
+            sealer,
         })
     }
 
-    pub fn get_next_round(&self) -> ProtocolRoundIndex {
-        let mut round = self.protocol_round.write();
-        let current_round: ProtocolRoundIndex = *round;
-        *round = current_round + 1; // increment for next round
-        current_round
+    // Sign a dealing with this node's identity key so that receivers can hold it as evidence
+    pub fn sign_dealing(&self, dealing: &mut Dealing) {
+        dealing.signature = self
+            .key
+            .sign(&evidence::signing_payload(dealing))
+            .as_ref()
+            .to_vec()
+            .into();
+    }
+}
+
+fn load_or_generate_seed(identity_path: &Path) -> [u8; 32] {
+    if let Ok(bytes) = fs::read(identity_path) {
+        return bytes.try_into().unwrap_or_else(|_| {
+            panic!(
+                "The node identity at {} is not a 32 byte seed",
+                identity_path.display()
+            )
+        });
     }
+    let rng = rand::SystemRandom::new();
+    let mut seed = [0u8; 32];
+    rand::SecureRandom::fill(&rng, &mut seed).unwrap();
+    utils::write_private(identity_path, &seed).unwrap_or_else(|error| {
+        panic!(
+            "Could not store the node identity at {}: {}",
+            identity_path.display(),
+            error
+        )
+    });
+    seed
 }
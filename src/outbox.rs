@@ -0,0 +1,126 @@
+// System
+use std::time::Instant;
+
+// Third Party
+use tokio::sync::broadcast;
+
+// Local
+use super::node_setup::NodeSetup;
+use super::peer::PeerMap;
+use super::types::PublicKey;
+use super::utils;
+use super::wire::EncodedDealing;
+use crate::sample::{Dealing, DealingKind};
+
+// Signs and delivers this node's outgoing dealings
+// Dealings addressed to myself skip the network and go straight into the inbound dealing channel,
+// so every dealing reaches the aggregator through the same path.
+// All methods block while a peer's channel is full, so call them from spawn_blocking.
+#[derive(Clone)]
+pub struct Outbox {
+    peers: PeerMap,
+    node_setup: NodeSetup,
+    inbound_dealing_sender: broadcast::Sender<Dealing>,
+}
+
+impl Outbox {
+    pub fn new(
+        peers: PeerMap,
+        node_setup: NodeSetup,
+        inbound_dealing_sender: broadcast::Sender<Dealing>,
+    ) -> Self {
+        Self {
+            peers,
+            node_setup,
+            inbound_dealing_sender,
+        }
+    }
+
+    pub fn public_key(&self) -> &PublicKey {
+        &self.node_setup.public_key
+    }
+
+    // Send a dealing to every peer, including myself
+    pub fn broadcast(&self, mut dealing: Dealing) {
+        dealing.public_key = self.node_setup.public_key.clone();
+        self.node_setup.sign_dealing(&mut dealing);
+        // Encoded once here rather than once per peer stream
+        let encoded = EncodedDealing::new(&dealing);
+        self.inbound_dealing_sender.send(dealing).unwrap();
+        // Fan out over a snapshot of the peers so that no lock is held while a full peer
+        // channel blocks this thread
+        let broadcast_start = Instant::now();
+        for peer in self.peers.snapshot().values() {
+            // I already have my dealing. Send the new dealing across all peer streams
+            if peer.public_key != self.node_setup.public_key {
+                peer.blocking_send(encoded.clone());
+            }
+        }
+        utils::debug_line_to_file(
+            &broadcast_start.elapsed().as_micros().to_string(),
+            "broadcast_micros.debug.txt",
+        );
+    }
+
+    // Send every peer, including myself, its own payload sealed to it. The rest of the dealing is
+    // the same for everyone. Peers for which payload returns None get nothing.
+    pub fn broadcast_sealed(
+        &self,
+        mut dealing: Dealing,
+        payload: impl Fn(&PublicKey) -> Option<Vec<u8>>,
+    ) {
+        dealing.public_key = self.node_setup.public_key.clone();
+        let broadcast_start = Instant::now();
+        for peer in self.peers.snapshot().values() {
+            let plaintext = match payload(&peer.public_key) {
+                Some(plaintext) => plaintext,
+                None => continue,
+            };
+            let mut sealed_dealing = dealing.clone();
+            sealed_dealing.recipient = peer.public_key.clone();
+            sealed_dealing.dealing =
+                match self
+                    .node_setup
+                    .sealer
+                    .seal(&peer.public_key, &sealed_dealing, &plaintext)
+                {
+                    Ok(sealed) => sealed.into(),
+                    Err(error) => {
+                        println!("Could not seal a dealing to a peer: {}", error);
+                        continue;
+                    }
+                };
+            self.node_setup.sign_dealing(&mut sealed_dealing);
+            if peer.public_key == self.node_setup.public_key {
+                self.inbound_dealing_sender.send(sealed_dealing).unwrap();
+            } else {
+                // Kept so that the peer can ask for it again
+                if sealed_dealing.kind() == DealingKind::Random {
+                    peer.random_dealings.lock().record_sent(&sealed_dealing);
+                }
+                peer.blocking_send(sealed_dealing);
+            }
+        }
+        utils::debug_line_to_file(
+            &broadcast_start.elapsed().as_micros().to_string(),
+            "broadcast_micros.debug.txt",
+        );
+    }
+
+    // Send a dealing to a single peer, which may be myself. Returns false if I have no such peer.
+    pub fn send_to(&self, public_key: &PublicKey, mut dealing: Dealing) -> bool {
+        dealing.public_key = self.node_setup.public_key.clone();
+        self.node_setup.sign_dealing(&mut dealing);
+        if public_key == &self.node_setup.public_key {
+            self.inbound_dealing_sender.send(dealing).unwrap();
+            return true;
+        }
+        match self.peers.snapshot().get(public_key) {
+            Some(peer) => {
+                peer.blocking_send(dealing);
+                true
+            }
+            None => false,
+        }
+    }
+}
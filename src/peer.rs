@@ -1,76 +1,154 @@
 // System
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, VecDeque};
 use std::sync::Arc;
 
 // Third Party
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 use tokio::sync::mpsc::Sender;
-use tonic::transport::Channel;
-use tonic::Status;
 
 // Local
-use super::types::{DealingValue, NodeIndex, ProtocolRoundIndex, PublicKey};
+use super::types::{NodeIndex, PublicKey, RoundId};
 use super::utils;
-use crate::sample::sample_client::SampleClient;
+use super::wire::EncodedDealing;
 use crate::sample::Dealing;
 
+#[derive(Clone)]
 pub struct Peer {
     pub address: String,
     pub public_key: PublicKey,
-    // This is the tonic TLS connection
-    pub connection: Option<SampleClient<Channel>>,
     // The receive_dealing() server side sends dealings here
-    pub server_dealing_sender: Option<Sender<Result<Dealing, Status>>>,
+    pub server_dealing_sender: Option<Sender<EncodedDealing>>,
     // The receive_dealing() client side sends dealings here
-    pub client_dealing_sender: Option<Sender<Dealing>>,
-    pub random_dealings: BTreeMap<ProtocolRoundIndex, DealingValue>,
+    pub client_dealing_sender: Option<Sender<EncodedDealing>>,
+    // Shared by every snapshot's copy of this peer so that recording a dealing never copies the
+    // peer map
+    pub random_dealings: Arc<Mutex<DealingHistory>>,
+}
+
+// The random dealings exchanged with one peer in a round, exactly as they were signed
+#[derive(Clone, Default)]
+pub struct ExchangedDealings {
+    pub received: Option<Dealing>,
+    pub sent: Option<Dealing>,
+}
+
+// The random dealings exchanged with one peer in its most recent rounds
+pub struct DealingHistory {
+    capacity: usize,
+    rounds: BTreeMap<RoundId, ExchangedDealings>,
+    // Insertion order of rounds so the oldest is evicted first
+    order: VecDeque<RoundId>,
+}
+
+impl DealingHistory {
+    // Keep at most capacity rounds. Nothing is recorded when capacity is 0.
+    pub fn shared(capacity: usize) -> Arc<Mutex<Self>> {
+        Arc::new(Mutex::new(Self {
+            capacity,
+            rounds: BTreeMap::new(),
+            order: VecDeque::new(),
+        }))
+    }
+
+    // Only the first dealing of a round is kept, so a later one can't overwrite it
+    pub fn record_received(&mut self, dealing: &Dealing) {
+        if let Some(exchanged) = self.round_mut(&dealing.round_id) {
+            exchanged.received.get_or_insert_with(|| dealing.clone());
+        }
+    }
+
+    pub fn record_sent(&mut self, dealing: &Dealing) {
+        if let Some(exchanged) = self.round_mut(&dealing.round_id) {
+            exchanged.sent.get_or_insert_with(|| dealing.clone());
+        }
+    }
+
+    pub fn get(&self, round: &RoundId) -> Option<&ExchangedDealings> {
+        self.rounds.get(round)
+    }
+
+    fn round_mut(&mut self, round: &RoundId) -> Option<&mut ExchangedDealings> {
+        if self.capacity == 0 {
+            return None;
+        }
+        if !self.rounds.contains_key(round) {
+            self.order.push_back(round.clone());
+            while self.order.len() > self.capacity {
+                if let Some(oldest) = self.order.pop_front() {
+                    self.rounds.remove(&oldest);
+                }
+            }
+        }
+        Some(self.rounds.entry(round.clone()).or_default())
+    }
+}
+
+impl Peer {
+    // Send a dealing over whichever stream this peer is reachable by. This blocks while the
+    // peer's channel is full, so it must not be called from async code.
+    pub fn blocking_send(&self, dealing: impl Into<EncodedDealing>) {
+        let dealing = dealing.into();
+        #[allow(clippy::option_if_let_else)]
+        if let Some(client_dealing_sender) = self.client_dealing_sender.clone() {
+            client_dealing_sender.blocking_send(dealing).unwrap();
+            utils::debug_line_to_file("Sent.", "client_sent.debug.txt");
+            utils::debug_line_to_file("Sent.", "dealing_sent.debug.txt");
+        } else if let Some(server_dealing_sender) = self.server_dealing_sender.clone() {
+            server_dealing_sender.blocking_send(dealing).unwrap();
+            utils::debug_line_to_file("Sent.", "server_sent.debug.txt");
+            utils::debug_line_to_file("Sent.", "dealing_sent.debug.txt");
+        } else {
+            panic!("Nowhere to send a dealing to this peer");
+        }
+    }
 }
 
+// An immutable view of the peers at one point in time. Holding one never blocks writers.
+pub type PeerSnapshot = Arc<BTreeMap<PublicKey, Arc<Peer>>>;
+
+// Copy-on-write registry of peers
+// Readers clone the current snapshot under a very short read lock and then iterate it without
+// holding any lock. Writers copy the map only if a snapshot is still alive, modify the copy and
+// swap it in. Peer changes are rare compared to dealing fan-out so this is the cheaper side.
 #[derive(Clone)]
 pub struct PeerMap {
-    inner: Arc<RwLock<BTreeMap<PublicKey, Peer>>>,
+    inner: Arc<RwLock<PeerSnapshot>>,
 }
 
 impl PeerMap {
     pub fn new() -> Self {
-        let inner = Arc::new(RwLock::new(BTreeMap::new()));
+        let inner = Arc::new(RwLock::new(Arc::new(BTreeMap::new())));
         PeerMap { inner }
     }
 
+    pub fn snapshot(&self) -> PeerSnapshot {
+        self.inner.read().clone()
+    }
+
     pub fn public_keys(&self) -> Vec<PublicKey> {
-        let lock = self.inner.read();
-        return lock.iter().map(|(_, v)| v.public_key.clone()).collect();
+        self.snapshot().keys().cloned().collect()
     }
 
     pub fn index_of_public_key(&self, public_key: PublicKey) -> NodeIndex {
-        let lock = self.inner.read();
-        return lock
-            .iter()
-            .position(|(peer_public_key, _)| peer_public_key == &public_key)
-            .unwrap() as u32;
+        self.snapshot()
+            .keys()
+            .position(|peer_public_key| peer_public_key == &public_key)
+            .unwrap() as u32
     }
 
     pub fn contains_public_key(&self, public_key: PublicKey) -> bool {
-        let lock = self.inner.read();
-        return lock.get(&public_key.clone()).is_some();
-    }
-
-    pub fn with_map<F, T>(&self, func: F) -> T
-    where
-        F: FnOnce(&mut BTreeMap<PublicKey, Peer>) -> T,
-    {
-        let mut lock = self.inner.write();
-        func(&mut *lock)
+        self.snapshot().contains_key(&public_key)
     }
 
     pub fn set_peer_server_dealing_sender(
         &self,
         peer_public_key: PublicKey,
-        sender: Sender<Result<Dealing, Status>>,
+        sender: Sender<EncodedDealing>,
     ) {
         let mut lock = self.inner.write();
-        if let Some(mut peer) = lock.get_mut(&peer_public_key.clone()) {
-            peer.server_dealing_sender = Some(sender);
+        let peers = Arc::make_mut(&mut lock);
+        if let Some(peer) = peers.get_mut(&peer_public_key) {
+            Arc::make_mut(peer).server_dealing_sender = Some(sender);
         } else {
             panic!("Attempted to create a receive_dealings stream for a peer I don't have!");
         }
@@ -79,21 +157,86 @@ impl PeerMap {
     pub fn add_peer(&self, new_peer: Peer, node_count: u32) {
         let mut lock = self.inner.write();
         // Don't add the peer if it's already there
-        let public_keys: Vec<PublicKey> = lock.iter().map(|(_, v)| v.public_key.clone()).collect();
         if !lock.contains_key(&new_peer.public_key) {
-            assert!(lock.get(&new_peer.public_key.clone()).is_none());
             utils::debug_line_to_file("Added Peer.", "added_peer.debug.txt");
-            lock.insert(new_peer.public_key.clone(), new_peer);
+            Arc::make_mut(&mut lock).insert(new_peer.public_key.clone(), Arc::new(new_peer));
         }
+        let peers = &**lock;
+        let public_keys: Vec<PublicKey> = peers.values().map(|v| v.public_key.clone()).collect();
         if !utils::has_unique_elements(public_keys) {
             panic!("There is a duplicate public key in my peers!");
         }
-        let addresses: Vec<String> = lock.iter().map(|(_, v)| v.address.clone()).collect();
+        let addresses: Vec<String> = peers.values().map(|v| v.address.clone()).collect();
         if !utils::has_unique_elements(addresses) {
             panic!("There is a duplicate address in my peers!");
         }
-        if lock.len() == node_count as usize {
+        if peers.len() == node_count as usize {
             utils::debug_line_to_file("Done.", "all_peers_added.debug.txt");
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dealing(round: u8, payload: &[u8]) -> Dealing {
+        Dealing {
+            round_id: vec![round].into(),
+            dealing: payload.to_vec().into(),
+            ..Default::default()
+        }
+    }
+
+    fn round(round: u8) -> RoundId {
+        RoundId::from(vec![round])
+    }
+
+    #[test]
+    fn history_evicts_the_oldest_round_beyond_its_capacity() {
+        let history = DealingHistory::shared(2);
+        let mut history = history.lock();
+        for round in 1..=3 {
+            history.record_sent(&dealing(round, b"sent"));
+        }
+        assert!(history.get(&round(1)).is_none());
+        assert_eq!(
+            history.get(&round(2)).unwrap().sent,
+            Some(dealing(2, b"sent"))
+        );
+        assert_eq!(
+            history.get(&round(3)).unwrap().sent,
+            Some(dealing(3, b"sent"))
+        );
+
+        // Another dealing for a round it holds evicts nothing
+        history.record_received(&dealing(2, b"received"));
+        assert_eq!(
+            history.get(&round(2)).unwrap().received,
+            Some(dealing(2, b"received"))
+        );
+        assert!(history.get(&round(3)).is_some());
+    }
+
+    #[test]
+    fn only_the_first_dealing_of_a_round_is_kept() {
+        let history = DealingHistory::shared(10);
+        let mut history = history.lock();
+        history.record_received(&dealing(1, b"first"));
+        history.record_received(&dealing(1, b"second"));
+        history.record_sent(&dealing(1, b"first"));
+        history.record_sent(&dealing(1, b"second"));
+        let exchanged = history.get(&round(1)).unwrap();
+        assert_eq!(exchanged.received, Some(dealing(1, b"first")));
+        assert_eq!(exchanged.sent, Some(dealing(1, b"first")));
+    }
+
+    #[test]
+    fn history_of_zero_rounds_records_nothing() {
+        let history = DealingHistory::shared(0);
+        let mut history = history.lock();
+        history.record_received(&dealing(1, b"received"));
+        history.record_sent(&dealing(1, b"sent"));
+        assert!(history.get(&round(1)).is_none());
+    }
+}
@@ -0,0 +1,394 @@
+// Bracha-style reliable broadcast of random dealings
+// Every recipient gets its own sealed payload, so what the committee agrees on is the digest of the
+// part of a dealing that the dealer sends everyone alike. After validating a sender's dealing a
+// node echoes its digest to the committee. A node that sees echoes from more than (n + f) / 2
+// members, or readies from f + 1, for a digest sends ready, and once 2f + 1 readies for a digest
+// arrive the dealing with that digest is delivered. So every honest node delivers the same dealing
+// per sender and round, or none at all. A node whose own payload has a different digest than the
+// delivered one never uses it.
+
+// System
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// Third Party
+use parking_lot::Mutex;
+use prost::Message;
+use ring::digest;
+
+// Local
+use super::outbox::Outbox;
+use super::peer::PeerMap;
+use super::types::{PublicKey, RoundId};
+use super::utils;
+use crate::sample::{Dealing, DealingKind, RbcVote};
+
+const DIGEST_CONTEXT: &[u8] = b"tokio-sample reliable broadcast v1";
+
+pub type Digest = Vec<u8>;
+
+// One sender's broadcast in one round
+struct Instance {
+    started: Instant,
+    // The validated dealing the sender gave me and its digest
+    mine: Option<(Digest, Dealing)>,
+    echoes: HashMap<Digest, BTreeSet<PublicKey>>,
+    readies: HashMap<Digest, BTreeSet<PublicKey>>,
+    echo_sent: bool,
+    ready_sent: bool,
+    delivered: Option<Digest>,
+    // Whether my dealing has been handed over for aggregation, or found not to match
+    settled: bool,
+}
+
+impl Instance {
+    fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            mine: None,
+            echoes: HashMap::new(),
+            readies: HashMap::new(),
+            echo_sent: false,
+            ready_sent: false,
+            delivered: None,
+            settled: false,
+        }
+    }
+
+    // My dealing, the first time the delivered digest is known and matches it
+    fn take_delivery(&mut self, round: &RoundId) -> Option<Dealing> {
+        if self.settled {
+            return None;
+        }
+        let delivered = self.delivered.as_ref()?;
+        let (digest, dealing) = self.mine.as_ref()?;
+        self.settled = true;
+        if digest != delivered {
+            println!(
+                "The dealing I got for round {} differs from the one the committee delivered",
+                utils::hex(round)
+            );
+            utils::debug_line_to_file("Mismatch.", "rbc_mismatch.debug.txt");
+            return None;
+        }
+        utils::debug_line_to_file("Delivered.", "rbc_delivered.debug.txt");
+        Some(dealing.clone())
+    }
+}
+
+#[derive(Clone)]
+pub struct ReliableBroadcast {
+    outbox: Outbox,
+    peers: PeerMap,
+    instances: Arc<Mutex<HashMap<(RoundId, PublicKey), Instance>>>,
+}
+
+impl ReliableBroadcast {
+    pub fn new(outbox: Outbox, peers: PeerMap) -> Self {
+        Self {
+            outbox,
+            peers,
+            instances: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    // What the committee agrees on for a sender's dealing. public_part is the part of the payload
+    // every recipient gets alike, see DealingScheme::public_part.
+    pub fn digest(round: &RoundId, sender: &PublicKey, public_part: &[u8]) -> Digest {
+        let mut context = digest::Context::new(&digest::SHA256);
+        context.update(DIGEST_CONTEXT);
+        context.update(&(round.len() as u32).to_be_bytes());
+        context.update(round);
+        context.update(&(sender.len() as u32).to_be_bytes());
+        context.update(sender);
+        context.update(public_part);
+        context.finish().as_ref().to_vec()
+    }
+
+    // Join the broadcast of a dealing I received and validated. Returns the dealing if the
+    // committee has already delivered it. This sends votes, so call it from spawn_blocking.
+    pub fn receive(&self, dealing: Dealing, digest: Digest) -> Option<Dealing> {
+        let round = dealing.round_id.clone();
+        let sender = dealing.public_key.clone();
+        let (echo, delivered) = {
+            let mut instances = self.instances.lock();
+            let instance = instances
+                .entry((round.clone(), sender.clone()))
+                .or_insert_with(Instance::new);
+            // Only the first dealing from a sender counts
+            if instance.mine.is_some() {
+                return None;
+            }
+            instance.mine = Some((digest.clone(), dealing));
+            let echo = !instance.echo_sent;
+            instance.echo_sent = true;
+            (echo, instance.take_delivery(&round))
+        };
+        if echo {
+            self.vote(DealingKind::RbcEcho, &round, &sender, digest);
+        }
+        delivered
+    }
+
+    // Handle an echo or ready from a committee member whose signature has already been verified.
+    // Returns the dealing it delivers, if any. This sends votes, so call it from spawn_blocking.
+    pub fn handle_vote(&self, vote_dealing: &Dealing) -> Option<Dealing> {
+        let vote = match RbcVote::decode(&vote_dealing.dealing[..]) {
+            Ok(vote) => vote,
+            Err(_) => {
+                println!("Dropping a malformed reliable broadcast vote");
+                return None;
+            }
+        };
+        let committee = self.peers.public_keys();
+        if !committee.contains(&vote_dealing.public_key) {
+            return None;
+        }
+        let faulty = (committee.len().saturating_sub(1)) / 3;
+        let echo_quorum = (committee.len() + faulty) / 2 + 1;
+        let ready_quorum = 2 * faulty + 1;
+        let round = vote_dealing.round_id.clone();
+        let sender = PublicKey::from(vote.sender);
+        let (ready, delivered) = {
+            let mut instances = self.instances.lock();
+            let instance = instances
+                .entry((round.clone(), sender.clone()))
+                .or_insert_with(Instance::new);
+            let votes = match vote_dealing.kind() {
+                DealingKind::RbcEcho => &mut instance.echoes,
+                _ => &mut instance.readies,
+            };
+            votes
+                .entry(vote.digest)
+                .or_default()
+                .insert(vote_dealing.public_key.clone());
+            let mut ready = None;
+            if !instance.ready_sent {
+                let echoed = instance
+                    .echoes
+                    .iter()
+                    .find(|(_, voters)| voters.len() >= echo_quorum);
+                // Enough readies mean at least one honest node saw an echo quorum
+                let amplified = instance
+                    .readies
+                    .iter()
+                    .find(|(_, voters)| voters.len() > faulty);
+                if let Some((digest, _)) = echoed.or(amplified) {
+                    ready = Some(digest.clone());
+                    instance.ready_sent = true;
+                }
+            }
+            if instance.delivered.is_none() {
+                instance.delivered = instance
+                    .readies
+                    .iter()
+                    .find(|(_, voters)| voters.len() >= ready_quorum)
+                    .map(|(digest, _)| digest.clone());
+            }
+            (ready, instance.take_delivery(&round))
+        };
+        if let Some(digest) = ready {
+            self.vote(DealingKind::RbcReady, &round, &sender, digest);
+        }
+        delivered
+    }
+
+    fn vote(&self, kind: DealingKind, round: &RoundId, sender: &PublicKey, digest: Digest) {
+        let vote = RbcVote {
+            sender: sender.to_vec(),
+            digest,
+        };
+        self.outbox.broadcast(Dealing {
+            dealing: vote.encode_to_vec().into(),
+            round_id: round.clone(),
+            kind: kind as i32,
+            ..Default::default()
+        });
+    }
+
+    // Forget broadcasts that started longer ago than max_age
+    pub fn prune(&self, max_age: Duration) {
+        self.instances
+            .lock()
+            .retain(|_, instance| instance.started.elapsed() <= max_age);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestCommittee;
+
+    // With four members one may be faulty: an echo quorum is 3, f + 1 readies are 2 and a ready
+    // quorum is 3
+    const SENDER: usize = 3;
+
+    fn round() -> RoundId {
+        RoundId::from(vec![5; 32])
+    }
+
+    fn digest(committee: &TestCommittee, public_part: &[u8]) -> Digest {
+        ReliableBroadcast::digest(&round(), &committee.public_key(SENDER), public_part)
+    }
+
+    fn digest_of_another_dealing(committee: &TestCommittee) -> Digest {
+        digest(committee, b"another public part")
+    }
+
+    fn vote(
+        committee: &TestCommittee,
+        voter: usize,
+        kind: DealingKind,
+        digest: &Digest,
+    ) -> Dealing {
+        let vote = RbcVote {
+            sender: committee.public_key(SENDER).to_vec(),
+            digest: digest.clone(),
+        };
+        Dealing {
+            dealing: vote.encode_to_vec().into(),
+            round_id: round(),
+            public_key: committee.public_key(voter),
+            kind: kind as i32,
+            ..Default::default()
+        }
+    }
+
+    fn broadcast(committee: &TestCommittee) -> ReliableBroadcast {
+        let node = &committee.nodes[0];
+        ReliableBroadcast::new(node.outbox.clone(), node.peers.clone())
+    }
+
+    // The digests node 0 voted for with the given kind since the last call, once per vote
+    fn votes_sent(committee: &mut TestCommittee, kind: DealingKind) -> Vec<Digest> {
+        committee
+            .sent()
+            .into_iter()
+            .filter(|(recipient, dealing)| *recipient == 0 && dealing.kind() == kind)
+            .map(|(_, dealing)| RbcVote::decode(&dealing.dealing[..]).unwrap().digest)
+            .collect()
+    }
+
+    #[test]
+    fn ready_is_sent_after_more_than_n_plus_f_over_two_echoes() {
+        let mut committee = TestCommittee::new(4, "rbc-echo-quorum");
+        let rbc = broadcast(&committee);
+        let digest = digest(&committee, b"public part");
+        let other = digest_of_another_dealing(&committee);
+
+        rbc.handle_vote(&vote(&committee, 0, DealingKind::RbcEcho, &digest));
+        rbc.handle_vote(&vote(&committee, 1, DealingKind::RbcEcho, &digest));
+        // An echo for another digest doesn't add to this one's
+        rbc.handle_vote(&vote(&committee, 2, DealingKind::RbcEcho, &other));
+        assert!(votes_sent(&mut committee, DealingKind::RbcReady).is_empty());
+
+        rbc.handle_vote(&vote(&committee, 3, DealingKind::RbcEcho, &digest));
+        assert_eq!(
+            votes_sent(&mut committee, DealingKind::RbcReady),
+            vec![digest.clone()]
+        );
+        // Ready is sent only once
+        rbc.handle_vote(&vote(&committee, 2, DealingKind::RbcEcho, &digest));
+        assert!(votes_sent(&mut committee, DealingKind::RbcReady).is_empty());
+    }
+
+    #[test]
+    fn f_plus_one_readies_are_amplified() {
+        let mut committee = TestCommittee::new(4, "rbc-amplification");
+        let rbc = broadcast(&committee);
+        let digest = digest(&committee, b"public part");
+
+        rbc.handle_vote(&vote(&committee, 1, DealingKind::RbcReady, &digest));
+        assert!(votes_sent(&mut committee, DealingKind::RbcReady).is_empty());
+        rbc.handle_vote(&vote(&committee, 2, DealingKind::RbcReady, &digest));
+        assert_eq!(
+            votes_sent(&mut committee, DealingKind::RbcReady),
+            vec![digest]
+        );
+    }
+
+    #[test]
+    fn dealing_is_delivered_at_two_f_plus_one_readies() {
+        let mut committee = TestCommittee::new(4, "rbc-delivery");
+        let rbc = broadcast(&committee);
+        let digest = digest(&committee, b"public part");
+        let dealing = Dealing {
+            round_id: round(),
+            public_key: committee.public_key(SENDER),
+            dealing: b"sealed to node 0".to_vec().into(),
+            ..Default::default()
+        };
+
+        assert!(rbc.receive(dealing.clone(), digest.clone()).is_none());
+        assert_eq!(
+            votes_sent(&mut committee, DealingKind::RbcEcho),
+            vec![digest.clone()]
+        );
+        assert!(rbc
+            .handle_vote(&vote(&committee, 1, DealingKind::RbcReady, &digest))
+            .is_none());
+        assert!(rbc
+            .handle_vote(&vote(&committee, 2, DealingKind::RbcReady, &digest))
+            .is_none());
+        assert_eq!(
+            rbc.handle_vote(&vote(&committee, 3, DealingKind::RbcReady, &digest)),
+            Some(dealing)
+        );
+        // It is delivered once
+        assert!(rbc
+            .handle_vote(&vote(&committee, 0, DealingKind::RbcReady, &digest))
+            .is_none());
+    }
+
+    #[test]
+    fn dealing_that_differs_from_the_delivered_one_is_not_used() {
+        let committee = TestCommittee::new(4, "rbc-mismatch");
+        let rbc = broadcast(&committee);
+        let delivered = digest(&committee, b"public part");
+        let dealing = Dealing {
+            round_id: round(),
+            public_key: committee.public_key(SENDER),
+            ..Default::default()
+        };
+
+        rbc.receive(dealing, digest_of_another_dealing(&committee));
+        for voter in 0..3 {
+            assert!(rbc
+                .handle_vote(&vote(&committee, voter, DealingKind::RbcReady, &delivered))
+                .is_none());
+        }
+    }
+
+    #[test]
+    fn repeated_votes_from_one_member_count_once() {
+        let mut committee = TestCommittee::new(4, "rbc-duplicate-votes");
+        let rbc = broadcast(&committee);
+        let digest = digest(&committee, b"public part");
+        let dealing = Dealing {
+            round_id: round(),
+            public_key: committee.public_key(SENDER),
+            ..Default::default()
+        };
+        rbc.receive(dealing, digest.clone());
+        committee.sent();
+
+        for _ in 0..3 {
+            rbc.handle_vote(&vote(&committee, 1, DealingKind::RbcEcho, &digest));
+            rbc.handle_vote(&vote(&committee, 2, DealingKind::RbcEcho, &digest));
+        }
+        assert!(votes_sent(&mut committee, DealingKind::RbcReady).is_empty());
+        for _ in 0..3 {
+            rbc.handle_vote(&vote(&committee, 1, DealingKind::RbcReady, &digest));
+        }
+        assert!(votes_sent(&mut committee, DealingKind::RbcReady).is_empty());
+
+        // Votes from outside the committee don't count at all
+        let mut outsider = vote(&committee, 2, DealingKind::RbcReady, &digest);
+        outsider.public_key = PublicKey::from(vec![9; 32]);
+        for _ in 0..3 {
+            assert!(rbc.handle_vote(&outsider).is_none());
+        }
+        assert!(votes_sent(&mut committee, DealingKind::RbcReady).is_empty());
+    }
+}
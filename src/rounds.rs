@@ -0,0 +1,623 @@
+// Turns inbound random dealings into completed rounds
+// A dealing is validated, agreed on through reliable broadcast if that is enabled, and added to its
+// round. Once a round has threshold dealings it is combined right away, unless there is a complaint
+// window. Then the round first goes through a complaint phase: one window after completion this
+// node complains about every committee member whose dealing still has not arrived, and a dealer
+// answers such a complaint by revealing the dealing it sent the complainer. Another window later
+// every dealer that a complaint proved faulty or that left a complaint unanswered is disqualified,
+// and the dealings of all the others are combined. Complaints and answers are broadcast, so as long
+// as they arrive within the window every honest node combines the same dealers. With the beacon
+// enabled every combined round's joint secret is then reconstructed into a public random value.
+// System
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// Third Party
+use parking_lot::Mutex;
+use tokio::sync::broadcast;
+
+// Local
+use super::aggregator::Aggregator;
+use super::beacon::Beacon;
+use super::complaint;
+use super::config::NodeConfig;
+use super::evidence::Equivocation;
+use super::frost::{self, FrostError, Identifier};
+use super::outbox::Outbox;
+use super::peer::PeerMap;
+use super::rbc::ReliableBroadcast;
+use super::scheme::DealingScheme;
+use super::types::{PublicKey, RoundId};
+use super::utils;
+use super::vss::JointShare;
+use crate::node_setup::NodeSetup;
+use crate::sample::{ComplaintReason, Dealing, RoundEvent, RoundOutcome};
+
+// How long a reliable broadcast instance or a pending beacon is remembered after it started
+const INSTANCE_LIFETIME: Duration = Duration::from_secs(600);
+
+#[derive(Clone)]
+pub struct RandomRounds {
+    aggregator: Arc<Mutex<Aggregator>>,
+    round_events: broadcast::Sender<RoundEvent>,
+    scheme: Arc<dyn DealingScheme>,
+    rbc: Option<ReliableBroadcast>,
+    beacon: Option<Beacon>,
+    outbox: Outbox,
+    peers: PeerMap,
+    node_setup: NodeSetup,
+    threshold: u32,
+    complaint_window: Option<Duration>,
+}
+
+impl RandomRounds {
+    pub fn new(
+        config: &NodeConfig,
+        aggregator: Arc<Mutex<Aggregator>>,
+        round_events: broadcast::Sender<RoundEvent>,
+        scheme: Arc<dyn DealingScheme>,
+        outbox: Outbox,
+        peers: PeerMap,
+        node_setup: NodeSetup,
+    ) -> Self {
+        let rbc = config
+            .reliable_broadcast
+            .then(|| ReliableBroadcast::new(outbox.clone(), peers.clone()));
+        let beacon = config
+            .beacon
+            .then(|| Beacon::new(outbox.clone(), peers.clone(), config.threshold));
+        Self {
+            aggregator,
+            round_events,
+            scheme,
+            rbc,
+            beacon,
+            outbox,
+            peers,
+            node_setup,
+            threshold: config.threshold,
+            complaint_window: config.complaint_window,
+        }
+    }
+
+    // Handle a random dealing whose signature has already been verified. This does scalar
+    // multiplications and may send dealings, so call it from spawn_blocking.
+    pub fn handle_dealing(&self, dealing: Dealing) {
+        if let Some(dealing) = self.accept(dealing) {
+            self.insert(dealing);
+        }
+    }
+
+    // Handle a reliable broadcast echo or ready, the same way
+    pub fn handle_vote(&self, vote: Dealing) {
+        let delivered = match &self.rbc {
+            Some(rbc) => rbc.handle_vote(&vote),
+            None => None,
+        };
+        if let Some(dealing) = delivered {
+            self.insert(dealing);
+        }
+    }
+
+    // Handle a complaint whose signature has already been verified, the same way
+    pub fn handle_complaint(&self, complaint: Dealing) {
+        let committee = self.peers.public_keys();
+        match complaint::verify(&complaint, &committee, self.scheme.as_ref(), self.threshold) {
+            Ok((dealer, reason)) => {
+                let recorded = self.aggregator.lock().record_complaint(
+                    &complaint.round_id,
+                    complaint.protocol_round,
+                    dealer.clone(),
+                    complaint.public_key.clone(),
+                    reason != ComplaintReason::Missing,
+                );
+                if !recorded {
+                    return;
+                }
+                utils::debug_line_to_file("Complaint.", "complaint_recorded.debug.txt");
+                if reason == ComplaintReason::Missing && dealer == self.node_setup.public_key {
+                    self.answer(&complaint.round_id, &complaint.public_key);
+                }
+            }
+            Err(error) => println!(
+                "Dropping a complaint for round {}: {}",
+                utils::hex(&complaint.round_id),
+                error
+            ),
+        }
+    }
+
+    // Handle an answer to a missing complaint whose signature has already been verified, the same
+    // way
+    pub fn handle_complaint_answer(&self, answer: Dealing) {
+        let committee = self.peers.public_keys();
+        let dealing = match complaint::verify_answer(
+            &answer,
+            &committee,
+            self.scheme.as_ref(),
+            self.threshold,
+        ) {
+            Ok(dealing) => dealing,
+            Err(error) => {
+                return println!(
+                    "Dropping a complaint answer for round {}: {}",
+                    utils::hex(&answer.round_id),
+                    error
+                )
+            }
+        };
+        self.aggregator.lock().record_answer(
+            &dealing.round_id,
+            dealing.protocol_round,
+            dealing.public_key.clone(),
+            dealing.recipient.clone(),
+        );
+        // The revealed dealing is what the dealer is bound to for me, so check it like any other
+        // dealing I receive
+        if dealing.recipient == self.node_setup.public_key && self.check(&dealing).is_some() {
+            self.insert(dealing);
+        }
+    }
+
+    // Answer a complaint that my dealing for a round is missing by revealing the dealing I sent the
+    // complainer
+    fn answer(&self, round_id: &RoundId, complainer: &PublicKey) {
+        let peer = match self.peers.snapshot().get(complainer) {
+            Some(peer) => peer.clone(),
+            None => return,
+        };
+        let sent = peer
+            .random_dealings
+            .lock()
+            .get(round_id)
+            .and_then(|exchanged| exchanged.sent.clone());
+        let dealing = match sent {
+            Some(dealing) => dealing,
+            None => {
+                return println!(
+                    "Can't answer a complaint for round {}: the dealing is not in the peer history",
+                    utils::hex(round_id)
+                )
+            }
+        };
+        if let Ok(key) = self.node_setup.sealer.sealing_key(&dealing) {
+            self.outbox.broadcast(complaint::answer(&dealing, key));
+        }
+    }
+
+    // Handle a beacon share whose signature has already been verified, the same way
+    pub fn handle_beacon_share(&self, share: Dealing) {
+        if let Some(beacon) = &self.beacon {
+            beacon.handle(&share);
+        }
+    }
+
+    pub fn beacon(&self) -> Option<&Beacon> {
+        self.beacon.as_ref()
+    }
+
+    // Forget reliable broadcasts and pending beacons once their rounds are long over
+    pub fn prune(&self) {
+        if let Some(rbc) = &self.rbc {
+            rbc.prune(INSTANCE_LIFETIME);
+        }
+        if let Some(beacon) = &self.beacon {
+            beacon.prune(INSTANCE_LIFETIME);
+        }
+    }
+
+    fn identifier(&self) -> Identifier {
+        (self
+            .peers
+            .index_of_public_key(self.node_setup.public_key.clone())
+            + 1) as Identifier
+    }
+
+    // Validate my part of a random dealing. Returns the dealing once it can be added to its round,
+    // which with reliable broadcast is when the committee has delivered it.
+    fn accept(&self, dealing: Dealing) -> Option<Dealing> {
+        let payload = self.check(&dealing)?;
+        // Reliable broadcast only ever looks at a sender's first dealing, so a second one has to
+        // be caught before it gets there
+        if let Some(previous) = self.previous_dealing(&dealing) {
+            if previous.dealing != dealing.dealing {
+                self.check_equivocation(previous, dealing);
+                return None;
+            }
+        }
+        let rbc = match &self.rbc {
+            Some(rbc) => rbc,
+            None => return Some(dealing),
+        };
+        let public_part = self.scheme.public_part(&payload).ok()?;
+        let digest =
+            ReliableBroadcast::digest(&dealing.round_id, &dealing.public_key, &public_part);
+        rbc.receive(dealing, digest)
+    }
+
+    // The first valid dealing a sender gave me for a round, if I still have it
+    fn previous_dealing(&self, dealing: &Dealing) -> Option<Dealing> {
+        let received = self
+            .peers
+            .snapshot()
+            .get(&dealing.public_key)
+            .and_then(|peer| {
+                peer.random_dealings
+                    .lock()
+                    .get(&dealing.round_id)
+                    .and_then(|exchanged| exchanged.received.clone())
+            });
+        received.or_else(|| {
+            self.aggregator
+                .lock()
+                .dealing(&dealing.round_id, &dealing.public_key)
+                .cloned()
+        })
+    }
+
+    // Open a random dealing sealed to me and validate my share. Returns the payload if the share is
+    // valid and otherwise complains about the dealer when there is a complaint phase. Only valid
+    // dealings are kept in the peer's history, so that an invalid one never shadows a valid one
+    // the peer sends again.
+    fn check(&self, dealing: &Dealing) -> Option<Vec<u8>> {
+        let sealer = &self.node_setup.sealer;
+        let payload = match sealer.open(dealing) {
+            Ok(payload) => payload,
+            Err(error) => {
+                self.reject(dealing, &error.to_string());
+                if self.complaint_window.is_some() {
+                    if let Ok((shared_point, proof)) = sealer.prove_shared_secret(dealing) {
+                        self.outbox
+                            .broadcast(complaint::unreadable(dealing, shared_point, proof));
+                    }
+                }
+                return None;
+            }
+        };
+        if let Err(error) = self
+            .scheme
+            .validate(&payload, self.identifier(), self.threshold)
+        {
+            self.reject(dealing, &error.to_string());
+            if self.complaint_window.is_some() {
+                if let Ok(key) = sealer.reveal_key(dealing) {
+                    self.outbox
+                        .broadcast(complaint::invalid_share(dealing, key));
+                }
+            }
+            return None;
+        }
+        if dealing.public_key != self.node_setup.public_key {
+            if let Some(peer) = self.peers.snapshot().get(&dealing.public_key) {
+                peer.random_dealings.lock().record_received(dealing);
+            }
+        }
+        Some(payload)
+    }
+
+    fn reject(&self, dealing: &Dealing, error: &str) {
+        println!(
+            "Dropping a dealing for round {}: {}",
+            utils::hex(&dealing.round_id),
+            error
+        );
+        utils::debug_line_to_file("Rejected.", "dealing_rejected.debug.txt");
+    }
+
+    // Add an accepted random dealing to its round and finish the round if that completes it
+    fn insert(&self, dealing: Dealing) {
+        let round_id = dealing.round_id.clone();
+        let completed = {
+            let mut aggregator = self.aggregator.lock();
+            let previous = aggregator.dealing(&round_id, &dealing.public_key).cloned();
+            match previous {
+                Some(previous) => {
+                    drop(aggregator);
+                    return self.check_equivocation(previous, dealing);
+                }
+                None => aggregator.insert(dealing),
+            }
+        };
+        let dealings = match completed {
+            Some(dealings) => dealings,
+            None => return,
+        };
+        let complaint_window = match self.complaint_window {
+            Some(complaint_window) => complaint_window,
+            None => return self.finish(round_id, dealings),
+        };
+        // Wait out the complaint phase without holding on to a blocking thread
+        let rounds = self.clone();
+        tokio::runtime::Handle::current().spawn(async move {
+            tokio::time::sleep(complaint_window).await;
+            let rounds_to_move = rounds.clone();
+            let round_to_move = round_id.clone();
+            tokio::task::spawn_blocking(move || rounds_to_move.complain_missing(&round_to_move))
+                .await
+                .unwrap();
+            tokio::time::sleep(complaint_window).await;
+            tokio::task::spawn_blocking(move || rounds.finish(round_id, dealings));
+        });
+    }
+
+    // A second dealing from the same sender for a round, both sealed to me, is evidence of
+    // equivocation if its commitments differ from the first one's. Otherwise it is a retransmission
+    // and is ignored.
+    fn check_equivocation(&self, previous: Dealing, dealing: Dealing) {
+        if previous.dealing == dealing.dealing {
+            return;
+        }
+        let sealer = &self.node_setup.sealer;
+        let evidence = match (sealer.reveal_key(&previous), sealer.reveal_key(&dealing)) {
+            (Ok(first_key), Ok(second_key)) => Equivocation {
+                first: previous,
+                first_key,
+                second: dealing,
+                second_key,
+            },
+            _ => return,
+        };
+        if !evidence.verify(self.scheme.as_ref()) {
+            return;
+        }
+        let round_id = evidence.round();
+        let sender = evidence.sender().clone();
+        let mut aggregator = self.aggregator.lock();
+        aggregator.record_equivocation(evidence);
+        if aggregator
+            .round(&round_id)
+            .is_some_and(|round_state| round_state.tainted)
+        {
+            println!(
+                "Round {} is tainted: {} equivocated after its dealing counted",
+                utils::hex(&round_id),
+                utils::hex(&sender)
+            );
+        }
+    }
+
+    // Complain about every committee member whose dealing for a round has not arrived by now,
+    // unless a complaint already proved it faulty
+    fn complain_missing(&self, round_id: &RoundId) {
+        let (protocol_round, settled) = match self.aggregator.lock().round(round_id) {
+            Some(round_state) => (
+                round_state.protocol_round,
+                round_state
+                    .dealings
+                    .keys()
+                    .chain(&round_state.stragglers)
+                    .chain(&round_state.convicted)
+                    .cloned()
+                    .collect::<BTreeSet<PublicKey>>(),
+            ),
+            None => return,
+        };
+        for member in self.peers.public_keys() {
+            if member != self.node_setup.public_key && !settled.contains(&member) {
+                self.outbox
+                    .broadcast(complaint::missing(round_id, protocol_round, &member));
+            }
+        }
+    }
+
+    // Combine the dealings of a round and tell subscribers how it went. With a complaint phase these
+    // are the dealings of every committee member that was not disqualified, including the ones that
+    // arrived after the round completed, so that every honest node combines the same dealers.
+    fn finish(&self, round_id: RoundId, dealings: BTreeMap<PublicKey, Dealing>) {
+        let (dealings, disqualified) = if self.complaint_window.is_some() {
+            self.qualified_dealings(&round_id)
+        } else {
+            (Ok(dealings), BTreeSet::new())
+        };
+        let tainted = self
+            .aggregator
+            .lock()
+            .round(&round_id)
+            .is_some_and(|round_state| round_state.tainted);
+        let combined = match &dealings {
+            Err(error) => Err(error.clone()),
+            Ok(_) if tainted => Err("an equivocating dealer's dealing counted".to_string()),
+            Ok(dealings) if dealings.len() < self.threshold as usize => Err(format!(
+                "only {} dealings are left after disqualifying {} dealers",
+                dealings.len(),
+                disqualified.len()
+            )),
+            Ok(dealings) => self
+                .handle_received_dealings(dealings)
+                .map_err(|error| error.to_string()),
+        };
+        let (protocol_round, latency) = self
+            .aggregator
+            .lock()
+            .round(&round_id)
+            .map(|round_state| {
+                let completed_at = round_state.completed_at.unwrap_or_else(Instant::now);
+                (
+                    round_state.protocol_round,
+                    completed_at.duration_since(round_state.first_arrival),
+                )
+            })
+            .unwrap_or_default();
+        let dealers: Vec<PublicKey> = dealings.unwrap_or_default().into_keys().collect();
+        let mut event = RoundEvent {
+            round_id: round_id.to_vec(),
+            protocol_round,
+            contributors: utils::to_vecs(&dealers),
+            latency_us: latency.as_micros() as u64,
+            disqualified: utils::to_vecs(&disqualified),
+            ..Default::default()
+        };
+        match combined {
+            Ok(output) => {
+                event.output = frost::serialize_point(&output.public_key);
+                if let Some(beacon) = &self.beacon {
+                    beacon.contribute(&round_id, protocol_round, dealers, &output);
+                }
+                self.aggregator.lock().set_output(&round_id, output);
+            }
+            Err(error) => {
+                println!(
+                    "Could not combine round {}: {}",
+                    utils::hex(&round_id),
+                    error
+                );
+                event.set_outcome(RoundOutcome::Failed);
+                event.error = error;
+            }
+        }
+        // Sending only fails when nobody is subscribed
+        let _ = self.round_events.send(event);
+    }
+
+    // The dealings of every committee member a round did not disqualify, and the disqualified
+    // dealers. Fails if I don't hold the dealing of a qualified dealer, because then I can't combine
+    // what the rest of the committee does.
+    fn qualified_dealings(
+        &self,
+        round_id: &RoundId,
+    ) -> (
+        Result<BTreeMap<PublicKey, Dealing>, String>,
+        BTreeSet<PublicKey>,
+    ) {
+        let mut aggregator = self.aggregator.lock();
+        let disqualified = aggregator.disqualify(round_id);
+        let held = aggregator
+            .round(round_id)
+            .map(|round_state| round_state.dealings.clone())
+            .unwrap_or_default();
+        let mut dealings = BTreeMap::new();
+        for dealer in self.peers.public_keys() {
+            if disqualified.contains(&dealer) {
+                continue;
+            }
+            match held.get(&dealer) {
+                Some(dealing) => dealings.insert(dealer, dealing.clone()),
+                None => {
+                    let error = format!(
+                        "the dealing of qualified dealer {} is missing",
+                        utils::hex(&dealer)
+                    );
+                    return (Err(error), disqualified);
+                }
+            };
+        }
+        (Ok(dealings), disqualified)
+    }
+
+    // This should be called only inside a tokio::task::spawn_blocking because it does some computationally
+    // expensive work
+    fn handle_received_dealings(
+        &self,
+        dealings: &BTreeMap<PublicKey, Dealing>,
+    ) -> Result<JointShare, FrostError> {
+        assert!(dealings.len() >= self.threshold as usize);
+        // The aggregator keeps the dealings as they were signed, so they are still sealed
+        let payloads: Vec<Vec<u8>> = dealings
+            .values()
+            .map(|dealing| self.node_setup.sealer.open(dealing))
+            .collect::<Result<_, _>>()
+            .map_err(|_| FrostError::InvalidPayload)?;
+        let joint_share = self.scheme.combine(&payloads, self.identifier())?;
+        utils::debug_line_to_file("Done.", "opening_complete.debug.txt");
+        Ok(joint_share)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RetentionPolicy;
+    use crate::scheme::FeldmanVss;
+    use crate::testing::TestCommittee;
+
+    // The rounds of the first node of the committee
+    fn rounds(committee: &TestCommittee, threshold: u32, reliable_broadcast: bool) -> RandomRounds {
+        let node = &committee.nodes[0];
+        let mut config = node.config.clone();
+        config.threshold = threshold;
+        config.reliable_broadcast = reliable_broadcast;
+        let aggregator = Aggregator::new(threshold, false, RetentionPolicy::default());
+        RandomRounds::new(
+            &config,
+            Arc::new(Mutex::new(aggregator)),
+            broadcast::channel(16).0,
+            Arc::new(FeldmanVss),
+            node.outbox.clone(),
+            node.peers.clone(),
+            node.node_setup.clone(),
+        )
+    }
+
+    // Two dealings from the second node to the first for the same round, with different
+    // polynomials
+    fn conflicting_dealings(committee: &TestCommittee, threshold: u32) -> (Dealing, Dealing) {
+        let round_id = RoundId::from_static(&[7; 32]);
+        let members = committee.nodes[0].peers.public_keys();
+        let first = FeldmanVss.deal(&members, threshold, 0);
+        let second = FeldmanVss.deal(&members, threshold, 0);
+        (
+            committee.random_dealing(1, 0, &round_id, &first[&members[0]]),
+            committee.random_dealing(1, 0, &round_id, &second[&members[0]]),
+        )
+    }
+
+    #[test]
+    fn second_dealing_with_other_commitments_excludes_its_sender() {
+        for reliable_broadcast in [false, true] {
+            let committee = TestCommittee::new(3, "rounds-equivocation");
+            let rounds = rounds(&committee, 3, reliable_broadcast);
+            let (first, second) = conflicting_dealings(&committee, 3);
+            let round_id = first.round_id.clone();
+            let dealer = first.public_key.clone();
+
+            rounds.handle_dealing(first.clone());
+            // Sent again, it is only a retransmission
+            rounds.handle_dealing(first.clone());
+            assert!(rounds.aggregator.lock().equivocations().is_empty());
+
+            rounds.handle_dealing(second.clone());
+            let aggregator = rounds.aggregator.lock();
+            let equivocations = aggregator.equivocations();
+            assert_eq!(equivocations.len(), 1);
+            assert_eq!(equivocations[0].first.dealing, first.dealing);
+            assert_eq!(equivocations[0].second.dealing, second.dealing);
+            assert!(equivocations[0].verify(&FeldmanVss));
+            let round_state = aggregator.round(&round_id).unwrap();
+            assert!(round_state.equivocators.contains(&dealer));
+            assert!(!round_state.dealings.contains_key(&dealer));
+            drop(aggregator);
+
+            // Neither dealing counts any more
+            rounds.handle_dealing(first);
+            assert!(rounds
+                .aggregator
+                .lock()
+                .dealing(&round_id, &dealer)
+                .is_none());
+        }
+    }
+
+    #[test]
+    fn equivocation_after_a_round_completed_taints_it() {
+        let committee = TestCommittee::new(3, "rounds-tainted");
+        let rounds = rounds(&committee, 1, false);
+        let (first, second) = conflicting_dealings(&committee, 1);
+        let round_id = first.round_id.clone();
+
+        rounds.handle_dealing(first);
+        assert!(rounds
+            .aggregator
+            .lock()
+            .round(&round_id)
+            .unwrap()
+            .output
+            .is_some());
+
+        rounds.handle_dealing(second);
+        let aggregator = rounds.aggregator.lock();
+        let round_state = aggregator.round(&round_id).unwrap();
+        assert!(round_state.tainted);
+        assert!(round_state.output.is_none());
+    }
+}
@@ -1,27 +1,63 @@
 // System
-use std::collections::{BTreeMap, HashMap};
+use std::collections::BTreeMap;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 // Third Party
 use backoff::{future::retry, ExponentialBackoff};
-use ring::{rand, signature};
+use parking_lot::Mutex;
+use prost::Message;
 use tokio::sync::{broadcast, mpsc};
 use tokio_stream::wrappers::ReceiverStream;
+use tokio_stream::{Stream, StreamExt};
+use tonic::codec::CompressionEncoding;
 use tonic::metadata::BinaryMetadataValue;
+use tonic::transport::Endpoint;
 use tonic::Code;
 use tonic::{Request, Response, Status};
 
 // Local
-use super::peer::{Peer, PeerMap};
-use super::types::{DealingValue, NodeIndex, ProtocolRoundIndex, PublicKey};
+use super::aggregator::{Aggregator, FailedRound};
+use super::batch;
+use super::beacon::Beacon;
+use super::config::{BatchPolicy, NodeConfig, TransportTuning};
+use super::dkg::{self, Dkg};
+use super::evidence::{self, Equivocation};
+use super::frost;
+use super::outbox::Outbox;
+use super::peer::{DealingHistory, Peer, PeerMap};
+use super::rounds::RandomRounds;
+use super::scheme::{DealingScheme, Schemes};
+use super::session;
+use super::signing::FrostSigner;
+use super::traffic::{MeteredChannel, TrafficStats};
+use super::types::{PublicKey, RoundId};
 use super::utils;
 use crate::node_setup::NodeSetup;
 use crate::sample::sample_client::SampleClient;
 use crate::sample::sample_server::Sample;
 use crate::sample::{
-    AddPeerRequest, Dealing, HealthRequest, HealthResponse, IteratePeersRequest, PeerResponse,
-    SharingRequest, SharingResponse,
+    AddPeerRequest, BeaconRequest, BeaconValue, Dealing, DealingBatch, DealingKind, DkgRequest,
+    DkgResponse, EquivocationsRequest, EquivocationsResponse, FailedRoundsRequest,
+    FailedRoundsResponse, GroupKeyRequest, GroupKeyResponse, HealthRequest, HealthResponse,
+    IteratePeersRequest, PeerDealingsRequest, PeerDealingsResponse, PeerResponse,
+    ReportEquivocationResponse, ReshareRequest, ReshareResponse, RetransmitRequest, RoundEvent,
+    RoundOutcome, RoundRequest, RoundResponse, RoundStatus, SharingRequest, SharingResponse,
+    SignatureRequest, SignatureResponse, StatsRequest, StatsResponse, SubscribeBeaconRequest,
+    SubscribeRoundsRequest, VerifyRequest, VerifyResponse,
 };
 
+// How long a FROST signing coordinator waits for commitments and signature shares
+const FROST_SIGN_TIMEOUT: Duration = Duration::from_secs(30);
+// How long a presignature stays in the pool. Participants forget their nonces FROST_SIGN_TIMEOUT
+// after committing, so a presignature must be used well before that.
+const PRESIGNATURE_LIFETIME: Duration = Duration::from_secs(15);
+// Limits on a single InitialDealing request
+const DEFAULT_DEALING_COUNT: u32 = 3;
+const MAX_DEALING_COUNT: u32 = 10_000;
+const MAX_PAYLOAD_SIZE: usize = 1 << 20;
+
 // Our gRPC server
 pub struct MySample {
     peers: PeerMap,
@@ -30,21 +66,37 @@ pub struct MySample {
     _hostname: String,
     // This aggregates all new dealings from all sources
     inbound_dealing_sender: broadcast::Sender<Dealing>,
+    aggregator: Arc<Mutex<Aggregator>>,
+    outbox: Outbox,
+    signer: FrostSigner,
+    dkg: Dkg,
+    round_events: broadcast::Sender<RoundEvent>,
+    scheme: Arc<dyn DealingScheme>,
+    peer_history_rounds: usize,
+    threshold: u32,
+    beacon: Option<Beacon>,
+    batching: BatchPolicy,
+    compression: Option<CompressionEncoding>,
+    traffic: Arc<TrafficStats>,
+    transport: TransportTuning,
 }
 
 impl MySample {
-    pub fn new(node_count: u32, hostname: String) -> Self {
-        let node_setup = NodeSetup::new(node_count).unwrap();
+    pub fn new(config: NodeConfig, schemes: &Schemes) -> Self {
+        let scheme = schemes
+            .get(&config.dealing_scheme)
+            .unwrap_or_else(|| panic!("Unknown dealing scheme {}", config.dealing_scheme));
+        let node_count = config.node_count;
+        let node_setup = NodeSetup::new(&config.identity_path).unwrap();
         let peers = PeerMap::new();
 
         // Add myself to the peers map so that all dealings can be conveniently iterated.
         let self_peer: Peer = Peer {
             address: "http://localhost:2323".to_string(),
             public_key: node_setup.public_key.clone(),
-            connection: None,
             server_dealing_sender: None,
             client_dealing_sender: None,
-            random_dealings: BTreeMap::new(),
+            random_dealings: DealingHistory::shared(0),
         };
         peers.add_peer(self_peer, node_count);
 
@@ -57,78 +109,335 @@ impl MySample {
             broadcast::Sender<Dealing>,
             broadcast::Receiver<Dealing>,
         ) = broadcast::channel(1000);
+        let threshold = config.threshold;
+        let aggregator = Arc::new(Mutex::new(Aggregator::new(
+            threshold,
+            // The complaint phase combines the dealings that arrived after the round completed
+            config.record_stragglers || config.complaint_window.is_some(),
+            config.retention.clone(),
+        )));
+        let outbox = Outbox::new(
+            peers.clone(),
+            node_setup.clone(),
+            inbound_dealing_sender.clone(),
+        );
+        let signer = FrostSigner::new(outbox.clone());
+        let dkg = Dkg::new(
+            &config,
+            peers.clone(),
+            outbox.clone(),
+            signer.clone(),
+            node_setup.sealer.clone(),
+        );
+        // Round completions, failures and timeouts for SubscribeRounds. Events are dropped when
+        // nobody is subscribed.
+        let (round_events, _) = broadcast::channel(1000);
+        let rounds = RandomRounds::new(
+            &config,
+            aggregator.clone(),
+            round_events.clone(),
+            scheme.clone(),
+            outbox.clone(),
+            peers.clone(),
+            node_setup.clone(),
+        );
+        let rounds_to_move = rounds.clone();
         let node_setup_to_move = node_setup.clone();
+        let signer_to_move = signer.clone();
+        let dkg_to_move = dkg.clone();
+        let peers_to_move = peers.clone();
         tokio::spawn(async move {
-            let mut dealings_aggregator: HashMap<ProtocolRoundIndex, BTreeMap<PublicKey, Dealing>> =
-                HashMap::new();
             loop {
                 let dealing: Dealing = inbound_dealing_receiver.recv().await.unwrap();
-                let round_dealings = dealings_aggregator
-                    .entry(dealing.clone().protocol_round as usize)
-                    .or_insert_with(BTreeMap::new);
-                round_dealings.insert(dealing.clone().public_key, dealing.clone());
-                if round_dealings.len() == node_count as usize {
-                    let node_setup = node_setup_to_move.clone();
-                    let dealings = dealings_aggregator
-                        .get(&(dealing.clone().protocol_round as usize))
-                        .unwrap()
-                        .clone();
+                if !evidence::verify_dealing(&dealing) {
+                    println!(
+                        "Dropping a dealing for round {} with an invalid signature",
+                        utils::hex(&dealing.round_id)
+                    );
+                    utils::debug_line_to_file("Rejected.", "dealing_rejected.debug.txt");
+                    continue;
+                }
+                // Sealed payloads are opened by whoever consumes them, but only I can open mine
+                if !dealing.recipient.is_empty()
+                    && dealing.recipient != node_setup_to_move.public_key
+                {
+                    println!(
+                        "Dropping a dealing for round {} sealed to another node",
+                        utils::hex(&dealing.round_id)
+                    );
+                    utils::debug_line_to_file("Rejected.", "dealing_rejected.debug.txt");
+                    continue;
+                }
+                if dealing.kind() == DealingKind::RetransmitRequest {
+                    let peers = peers_to_move.clone();
+                    tokio::task::spawn_blocking(move || Self::retransmit(&dealing, &peers));
+                    continue;
+                }
+                if matches!(dealing.kind(), DealingKind::RbcEcho | DealingKind::RbcReady) {
+                    let rounds = rounds_to_move.clone();
+                    tokio::task::spawn_blocking(move || rounds.handle_vote(dealing));
+                    continue;
+                }
+                if dealing.kind() == DealingKind::Complaint {
+                    let rounds = rounds_to_move.clone();
+                    tokio::task::spawn_blocking(move || rounds.handle_complaint(dealing));
+                    continue;
+                }
+                if dealing.kind() == DealingKind::ComplaintAnswer {
+                    let rounds = rounds_to_move.clone();
+                    tokio::task::spawn_blocking(move || rounds.handle_complaint_answer(dealing));
+                    continue;
+                }
+                if dealing.kind() == DealingKind::BeaconShare {
+                    let rounds = rounds_to_move.clone();
+                    tokio::task::spawn_blocking(move || rounds.handle_beacon_share(dealing));
+                    continue;
+                }
+                if dealing.kind() == DealingKind::DkgDealing {
+                    let dkg = dkg_to_move.clone();
+                    tokio::task::spawn_blocking(move || dkg.handle(dealing));
+                    continue;
+                }
+                if dealing.kind() == DealingKind::ReshareDealing {
+                    let dkg = dkg_to_move.clone();
+                    tokio::task::spawn_blocking(move || dkg.handle_reshare(dealing));
+                    continue;
+                }
+                if dealing.kind() == DealingKind::ReshareSelection {
+                    let dkg = dkg_to_move.clone();
+                    tokio::task::spawn_blocking(move || dkg.handle_reshare_selection(dealing));
+                    continue;
+                }
+                if dealing.kind() != DealingKind::Random {
+                    let signer = signer_to_move.clone();
+                    tokio::task::spawn_blocking(move || signer.handle(dealing));
+                    continue;
+                }
+                let rounds = rounds_to_move.clone();
+                tokio::task::spawn_blocking(move || rounds.handle_dealing(dealing));
+            }
+        });
+
+        // Periodically ask for the dealings missing from stalled rounds, fail rounds that missed
+        // their deadline and drop rounds that have outlived the retention policy
+        let round_deadline = config.round_deadline;
+        let retransmit_after = config.retransmit_after;
+        if round_deadline.is_some()
+            || retransmit_after.is_some()
+            || config.retention.max_age.is_some()
+        {
+            let aggregator_to_move = aggregator.clone();
+            let round_events_to_move = round_events.clone();
+            let outbox_to_move = outbox.clone();
+            let peers_to_move = peers.clone();
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(Duration::from_secs(1));
+                loop {
+                    interval.tick().await;
+                    let mut aggregator = aggregator_to_move.lock();
+                    if let Some(retransmit_after) = retransmit_after {
+                        let committee = peers_to_move.public_keys();
+                        let stalled = aggregator.stalled_rounds(retransmit_after, &committee);
+                        if !stalled.is_empty() {
+                            let outbox = outbox_to_move.clone();
+                            tokio::task::spawn_blocking(move || {
+                                Self::request_retransmits(&outbox, stalled)
+                            });
+                        }
+                    }
+                    if let Some(round_deadline) = round_deadline {
+                        let committee = peers_to_move.public_keys();
+                        for failed_round in aggregator.expire_deadlines(round_deadline, &committee)
+                        {
+                            Self::report_failed_round(
+                                &failed_round,
+                                &committee,
+                                &round_events_to_move,
+                            );
+                        }
+                    }
+                    aggregator.prune();
+                }
+            });
+        }
+
+        // Forget the nonces of signing sessions whose signing package never came
+        let signer_to_move = signer.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(10));
+            loop {
+                interval.tick().await;
+                signer_to_move.expire_nonces(FROST_SIGN_TIMEOUT);
+            }
+        });
+
+        // Forget reliable broadcasts and pending beacons once their rounds are long over
+        let beacon = rounds.beacon().cloned();
+        if config.reliable_broadcast || config.beacon {
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(Duration::from_secs(10));
+                loop {
+                    interval.tick().await;
+                    rounds.prune();
+                }
+            });
+        }
+
+        // Keep the presignature pool topped up
+        if config.presignature_target > 0 {
+            let signer_to_move = signer.clone();
+            let target = config.presignature_target;
+            let low_watermark = config.presignature_low_watermark;
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(Duration::from_secs(1));
+                loop {
+                    interval.tick().await;
+                    let signer = signer_to_move.clone();
                     tokio::task::spawn_blocking(move || {
-                        Self::handle_received_dealings(&dealings, node_count, &node_setup);
-                    });
+                        signer.refill_presignatures(target, low_watermark, PRESIGNATURE_LIFETIME)
+                    })
+                    .await
+                    .unwrap();
                 }
+            });
+        }
+
+        // Fail key generations and reshares that missed their deadline and forget ended ones
+        let dkg_to_move = dkg.clone();
+        let dkg_deadline = config.dkg_deadline;
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(1));
+            loop {
+                interval.tick().await;
+                dkg_to_move.expire(dkg_deadline);
             }
         });
 
+        // Refresh the shares of the group key at the start of every refresh period. Every node
+        // with the same key derives the same refresh round from the period, so the nodes whose
+        // clocks run late simply join it.
+        if let Some(key_refresh) = config.key_refresh {
+            let dkg_to_move = dkg.clone();
+            tokio::spawn(async move {
+                loop {
+                    let (epoch, wait) = dkg::next_refresh(key_refresh);
+                    tokio::time::sleep(wait).await;
+                    let dkg = dkg_to_move.clone();
+                    tokio::task::spawn_blocking(move || dkg.refresh(epoch));
+                }
+            });
+        }
+
         Self {
             peers,
             node_setup,
             node_count,
-            _hostname: hostname,
+            _hostname: config.hostname,
             inbound_dealing_sender,
+            aggregator,
+            outbox,
+            signer,
+            dkg,
+            round_events,
+            scheme,
+            peer_history_rounds: config.peer_history_rounds,
+            threshold,
+            beacon,
+            batching: config.batching,
+            compression: config.compression,
+            traffic: Arc::new(TrafficStats::default()),
+            transport: config.transport,
         }
     }
 
-    // Produce one random dealing from this node to all of my peers
-    fn dealing(
-        node_setup: &NodeSetup,
-        _public_keys: &[PublicKey],
-        _my_node_index: NodeIndex,
-        _node_count: u32,
-    ) -> (DealingValue, ProtocolRoundIndex) {
-        const MESSAGE: &[u8] = b"hello, world";
-        let rng = rand::SystemRandom::new();
-        let pkcs8_bytes = signature::Ed25519KeyPair::generate_pkcs8(&rng).unwrap();
-        let key_pair = signature::Ed25519KeyPair::from_pkcs8(pkcs8_bytes.as_ref()).unwrap();
-        let sig = key_pair.sign(MESSAGE).as_ref().to_vec();
-        let protocol_round = node_setup.get_next_round();
-        (sig, protocol_round)
+    pub fn traffic(&self) -> Arc<TrafficStats> {
+        self.traffic.clone()
     }
 
-    fn dealing_random(
-        node_setup: &NodeSetup,
-        public_keys: &[PublicKey],
-        my_node_index: NodeIndex,
-        node_count: u32,
-    ) -> (DealingValue, ProtocolRoundIndex) {
-        Self::dealing(node_setup, public_keys, my_node_index, node_count)
+    fn report_failed_round(
+        failed_round: &FailedRound,
+        committee: &[PublicKey],
+        round_events: &broadcast::Sender<RoundEvent>,
+    ) {
+        println!(
+            "Round {} failed after {:?} with {} dealings, missing {} senders",
+            utils::hex(&failed_round.round),
+            failed_round.age,
+            failed_round.received,
+            failed_round.missing.len()
+        );
+        utils::debug_line_to_file("Failed.", "round_failed.debug.txt");
+        let mut event = RoundEvent {
+            round_id: failed_round.round.to_vec(),
+            protocol_round: failed_round.protocol_round,
+            contributors: utils::to_vecs(
+                committee
+                    .iter()
+                    .filter(|public_key| !failed_round.missing.contains(public_key)),
+            ),
+            missing: utils::to_vecs(&failed_round.missing),
+            latency_us: failed_round.age.as_micros() as u64,
+            ..Default::default()
+        };
+        event.set_outcome(RoundOutcome::TimedOut);
+        let _ = round_events.send(event);
     }
 
-    // This should be called only inside a tokio::task::spawn_blocking because it does some computationally
-    // expensive work
-    fn handle_received_dealings(
-        dealings: &BTreeMap<PublicKey, Dealing>,
-        node_count: u32,
-        _node_setup: &NodeSetup,
-    ) {
-        let dealings: BTreeMap<NodeIndex, DealingValue> = dealings
-            .iter()
-            .zip(0..node_count)
-            .map(|((_, dealing), node_index)| (node_index, dealing.dealing.clone()))
-            .collect();
-        let node_count = node_count as usize;
-        assert!(dealings.len() == node_count);
-        utils::debug_line_to_file("Done.", "opening_complete.debug.txt");
+    // Ask every sender missing from a stalled round for its dealing again, with one request per
+    // sender. Blocks while a peer's channel is full, so call it from spawn_blocking.
+    fn request_retransmits(outbox: &Outbox, stalled: Vec<(RoundId, Vec<PublicKey>)>) {
+        let mut requests: BTreeMap<PublicKey, Vec<RoundId>> = BTreeMap::new();
+        for (round, missing) in stalled {
+            for sender in missing {
+                requests.entry(sender).or_default().push(round.clone());
+            }
+        }
+        // My own dealings never leave this node, so there is nobody to ask
+        requests.remove(outbox.public_key());
+        for (sender, round_ids) in requests {
+            let request = RetransmitRequest {
+                round_ids: utils::to_vecs(&round_ids),
+            };
+            let dealing = Dealing {
+                dealing: request.encode_to_vec().into(),
+                kind: DealingKind::RetransmitRequest as i32,
+                ..Default::default()
+            };
+            if outbox.send_to(&sender, dealing) {
+                utils::debug_line_to_file("Requested.", "retransmit_requested.debug.txt");
+            }
+        }
+    }
+
+    // Send a peer the random dealings it asked for again, exactly as they were signed. Blocks
+    // while the peer's channel is full, so call it from spawn_blocking.
+    fn retransmit(request: &Dealing, peers: &PeerMap) {
+        let round_ids = match RetransmitRequest::decode(&request.dealing[..]) {
+            Ok(request) => request.round_ids,
+            Err(_) => {
+                println!("Dropping a malformed retransmit request");
+                return;
+            }
+        };
+        let peer = match peers.snapshot().get(&request.public_key) {
+            Some(peer) => peer.clone(),
+            None => return,
+        };
+        let dealings: Vec<Dealing> = {
+            let history = peer.random_dealings.lock();
+            round_ids
+                .iter()
+                .filter_map(|round| {
+                    history
+                        .get(&RoundId::from(round.clone()))
+                        .and_then(|exchanged| exchanged.sent.clone())
+                })
+                .collect()
+        };
+        for dealing in dealings {
+            peer.blocking_send(dealing);
+            utils::debug_line_to_file("Retransmitted.", "dealing_retransmitted.debug.txt");
+        }
     }
 }
 
@@ -137,97 +446,211 @@ impl MySample {
 impl Sample for MySample {
     async fn initial_dealing(
         &self,
-        _request: Request<SharingRequest>,
+        request: Request<SharingRequest>,
     ) -> Result<Response<SharingResponse>, Status> {
         let public_keys: Vec<PublicKey> = self.peers.public_keys();
-        for _ in 0..=2 {
-            // Create key, kappa, and lambda
-            // create new dealings and queue them for broadcast
-            // The presignature is kappa
-            let node_setup = self.node_setup.clone();
-            let node_count = self.node_count;
-            let my_node_index = self
-                .peers
-                .index_of_public_key(node_setup.public_key.clone());
-            let public_keys = public_keys.clone();
-            let peers = self.peers.clone();
-            let inbound_dealing_sender = self.inbound_dealing_sender.clone();
-            tokio::task::spawn_blocking(move || {
-                let (dealing, protocol_round) = Self::dealing_random(
-                    &node_setup.clone(),
-                    &public_keys,
-                    my_node_index as u32,
-                    node_count,
-                );
-                utils::debug_line_to_file("Created.", "dealing_created.debug.txt");
-                // Add the new key to myself
-                let dealing_message = Dealing {
-                    dealing: dealing,
-                    protocol_round: protocol_round as u32,
-                    public_key: node_setup.public_key.clone(),
-                };
-                inbound_dealing_sender
-                    .send(dealing_message.clone())
-                    .unwrap();
-                peers.with_map(|peers| {
-                    for (_, peer) in peers.iter() {
-                        // I already have my dealing. Send the new dealing across all peer streams
-                        if peer.public_key != node_setup.public_key.clone() {
-                            #[allow(clippy::option_if_let_else)]
-                            if let Some(client_dealing_sender) = peer.client_dealing_sender.clone()
-                            {
-                                client_dealing_sender
-                                    .blocking_send(dealing_message.clone())
-                                    .unwrap();
-                                utils::debug_line_to_file("Sent.", "client_sent.debug.txt");
-                                utils::debug_line_to_file("Sent.", "dealing_sent.debug.txt");
-                            } else if let Some(server_dealing_sender) =
-                                peer.server_dealing_sender.clone()
+        let request = request.into_inner();
+        let count = match request.count {
+            0 => DEFAULT_DEALING_COUNT,
+            count if count > MAX_DEALING_COUNT => {
+                return Err(Status::new(
+                    Code::InvalidArgument,
+                    format!("At most {} dealings per request", MAX_DEALING_COUNT),
+                ))
+            }
+            count => count,
+        };
+        let payload_size = request.payload_size as usize;
+        if payload_size > MAX_PAYLOAD_SIZE {
+            return Err(Status::new(
+                Code::InvalidArgument,
+                format!("Payloads are limited to {} bytes", MAX_PAYLOAD_SIZE),
+            ));
+        }
+        if let Some(unknown) = request
+            .targets
+            .iter()
+            .find(|target| !public_keys.iter().any(|public_key| public_key == *target))
+        {
+            return Err(Status::new(
+                Code::InvalidArgument,
+                format!("{} is not a peer", utils::hex(unknown)),
+            ));
+        }
+        let delay = Duration::from_millis(request.delay_ms as u64);
+        // Without a shared nonce the nodes' rounds never line up, and a reused one replays the
+        // round IDs of an earlier run
+        let session_nonce = request.session_nonce;
+        if session_nonce.is_empty() {
+            return Err(Status::new(
+                Code::InvalidArgument,
+                "A session_nonce shared by every node is required.",
+            ));
+        }
+        let round_ids: Vec<RoundId> = (0..count)
+            .map(|protocol_round| session::round_id(&public_keys, &session_nonce, protocol_round))
+            .collect();
+
+        let node_setup = self.node_setup.clone();
+        let threshold = self.threshold;
+        let scheme = self.scheme.clone();
+        let outbox = self.outbox.clone();
+        let targets = Arc::new(request.targets);
+        let round_ids_to_move = round_ids.clone();
+        tokio::spawn(async move {
+            for (protocol_round, round_id) in (0..).zip(round_ids_to_move) {
+                // create new dealings and queue them for broadcast
+                let node_setup = node_setup.clone();
+                let public_keys = public_keys.clone();
+                let scheme = scheme.clone();
+                let outbox = outbox.clone();
+                let targets = targets.clone();
+                tokio::task::spawn_blocking(move || {
+                    let payloads = scheme.deal(&public_keys, threshold, payload_size);
+                    utils::debug_line_to_file("Created.", "dealing_created.debug.txt");
+                    // Add the new key to myself and send every targeted peer its own share
+                    outbox.broadcast_sealed(
+                        Dealing {
+                            protocol_round,
+                            round_id,
+                            ..Default::default()
+                        },
+                        |recipient| {
+                            if !targets.is_empty()
+                                && !targets.iter().any(|target| target == recipient)
+                                && recipient != &node_setup.public_key
                             {
-                                server_dealing_sender
-                                    .blocking_send(Ok(dealing_message.clone()))
-                                    .unwrap();
-                                utils::debug_line_to_file("Sent.", "server_sent.debug.txt");
-                                utils::debug_line_to_file("Sent.", "dealing_sent.debug.txt");
-                            } else {
-                                panic!("Nowhere to send a dealing to this peer");
+                                return None;
                             }
-                        }
-                    }
+                            payloads.get(recipient).cloned()
+                        },
+                    );
                 });
-            });
-        }
+                if !delay.is_zero() {
+                    tokio::time::sleep(delay).await;
+                }
+            }
+            utils::debug_line_to_file("Spawned.", "spawned_all_dealing_requests.debug.txt");
+        });
 
-        utils::debug_line_to_file("Spawned.", "spawned_all_dealing_requests.debug.txt");
         Ok(Response::new(SharingResponse {
             success: true,
-            public_key: self.node_setup.public_key.clone(),
+            public_key: self.node_setup.public_key.to_vec(),
+            round_ids: utils::to_vecs(&round_ids),
         }))
     }
-    type ReceiveDealingsStream = ReceiverStream<Result<Dealing, tonic::Status>>;
+    type SubscribeRoundsStream = ReceiverStream<Result<RoundEvent, tonic::Status>>;
+    // Stream an event for every round that completes, fails or times out from now on
+    async fn subscribe_rounds(
+        &self,
+        _request: Request<SubscribeRoundsRequest>,
+    ) -> Result<Response<Self::SubscribeRoundsStream>, Status> {
+        let mut round_events = self.round_events.subscribe();
+        let (event_sender, event_receiver) = mpsc::channel(100);
+        tokio::spawn(async move {
+            loop {
+                match round_events.recv().await {
+                    Ok(event) => {
+                        // The subscriber went away
+                        if event_sender.send(Ok(event)).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        println!(
+                            "A round subscriber fell behind and missed {} events",
+                            skipped
+                        );
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+        Ok(Response::new(ReceiverStream::new(event_receiver)))
+    }
+    async fn get_beacon(
+        &self,
+        request: Request<BeaconRequest>,
+    ) -> Result<Response<BeaconValue>, Status> {
+        let beacon = self.beacon.as_ref().ok_or_else(|| {
+            Status::new(
+                Code::FailedPrecondition,
+                "The beacon is not enabled on this node.",
+            )
+        })?;
+        let round_id = RoundId::from(request.into_inner().round_id);
+        let value = beacon.get(&round_id).ok_or_else(|| {
+            Status::new(
+                Code::NotFound,
+                format!(
+                    "There is no beacon value for round {}",
+                    utils::hex(&round_id)
+                ),
+            )
+        })?;
+        Ok(Response::new(value))
+    }
+    type SubscribeBeaconStream = ReceiverStream<Result<BeaconValue, tonic::Status>>;
+    // Stream every beacon value this node produces from now on
+    async fn subscribe_beacon(
+        &self,
+        _request: Request<SubscribeBeaconRequest>,
+    ) -> Result<Response<Self::SubscribeBeaconStream>, Status> {
+        let mut values = self
+            .beacon
+            .as_ref()
+            .ok_or_else(|| {
+                Status::new(
+                    Code::FailedPrecondition,
+                    "The beacon is not enabled on this node.",
+                )
+            })?
+            .subscribe();
+        let (value_sender, value_receiver) = mpsc::channel(100);
+        tokio::spawn(async move {
+            loop {
+                match values.recv().await {
+                    Ok(value) => {
+                        // The subscriber went away
+                        if value_sender.send(Ok(value)).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        println!(
+                            "A beacon subscriber fell behind and missed {} values",
+                            skipped
+                        );
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+        Ok(Response::new(ReceiverStream::new(value_receiver)))
+    }
+    type ReceiveDealingsStream =
+        Pin<Box<dyn Stream<Item = Result<DealingBatch, tonic::Status>> + Send>>;
     // Call this once to open a bidirectional stream for dealings
     async fn receive_dealings(
         &self,
-        request: Request<tonic::Streaming<Dealing>>,
+        request: Request<tonic::Streaming<DealingBatch>>,
     ) -> Result<Response<Self::ReceiveDealingsStream>, Status> {
         // FIXME: In production I want to check the certificate of the sender to verify it is who
         // I think it is rather than relying on this metadata hack
-        let peer_public_key: Vec<u8> = request
+        let peer_public_key: PublicKey = request
             .metadata()
             .get_bin("trace-proto-bin")
             .unwrap()
             .to_bytes()
-            .unwrap()
-            .to_vec();
+            .unwrap();
         //If I don't have this peer in my Peers, add it
         if !self.peers.contains_public_key(peer_public_key.clone()) {
             let new_peer: Peer = Peer {
                 address: request.remote_addr().unwrap().to_string(),
                 public_key: peer_public_key.clone(),
-                connection: None,
                 server_dealing_sender: None,
                 client_dealing_sender: None,
-                random_dealings: BTreeMap::new(),
+                random_dealings: DealingHistory::shared(self.peer_history_rounds),
             };
             self.peers.add_peer(new_peer, self.node_count);
         }
@@ -239,22 +662,23 @@ impl Sample for MySample {
         // server_dealing_channel
         // This channel handles server-side dealings sent from other peers
         tokio::spawn(async move {
-            while let Some(request_inner) = streamer.message().await.unwrap() {
-                let public_key = request_inner.public_key;
-                let protocol_round: ProtocolRoundIndex = request_inner.protocol_round as usize;
-                let dealing = Dealing {
-                    dealing: request_inner.dealing.clone(),
-                    protocol_round: protocol_round as u32,
-                    public_key,
-                };
-                utils::debug_line_to_file("Received.", "server_received.debug.txt");
-                utils::debug_line_to_file("Received.", "inbound_dealing_received.debug.txt");
-                inbound_dealing_sender.send(dealing).unwrap();
+            while let Some(batch) = streamer.message().await.unwrap() {
+                for dealing in batch.dealings {
+                    utils::debug_line_to_file("Received.", "server_received.debug.txt");
+                    utils::debug_line_to_file("Received.", "inbound_dealing_received.debug.txt");
+                    inbound_dealing_sender
+                        .send(dealing.into_dealing().unwrap())
+                        .unwrap();
+                }
             }
         });
-        Ok(Response::new(ReceiverStream::new(
+        let outbound = batch::batches(
             dealing_received_receiver,
-        )))
+            self.batching.clone(),
+            self.traffic.clone(),
+        )
+        .map(Ok);
+        Ok(Response::new(Box::pin(outbound)))
     }
     async fn add_peer(
         &self,
@@ -265,11 +689,21 @@ impl Sample for MySample {
         let address = request_inner.address;
         // Make a health check to confirm we can connect before adding a peer
         // TODO: Make sure the address resolves to remote_addr
-        let client = retry(ExponentialBackoff::default(), || async {
-            Ok(SampleClient::connect(address.clone()).await?)
+        let channel = retry(ExponentialBackoff::default(), || async {
+            let endpoint = self
+                .transport
+                .endpoint(Endpoint::from_shared(address.clone())?);
+            Ok(endpoint.connect().await?)
         })
         .await
         .unwrap();
+        // Compressed responses are always accepted, see NodeConfig::compression
+        let mut client = SampleClient::new(MeteredChannel::new(channel, self.traffic.clone()))
+            .accept_compressed(CompressionEncoding::Gzip)
+            .accept_compressed(CompressionEncoding::Zstd);
+        if let Some(compression) = self.compression {
+            client = client.send_compressed(compression);
+        }
 
         let request = tonic::Request::new(HealthRequest {});
         let response = client.clone().check_health(request).await?;
@@ -286,18 +720,15 @@ impl Sample for MySample {
         // Call receive_dealings() on the connection I just created to open
         // the streams that listen for dealings
         let node_setup = self.node_setup.clone();
-        let client_to_move = client.clone();
-        let (client_dealing_sender, mut client_dealing_receiver) = mpsc::channel(1000);
+        let client_to_move = client;
+        let (client_dealing_sender, client_dealing_receiver) = mpsc::channel(1000);
+        let batching = self.batching.clone();
+        let traffic = self.traffic.clone();
         let inbound_dealing_sender = self.inbound_dealing_sender.clone();
         // client_dealing_channel
         // This channel handles client-side dealings sent from other peers
         tokio::spawn(async move {
-            let outbound = async_stream::stream! {
-                loop {
-                    let dealing: Dealing = client_dealing_receiver.recv().await.unwrap();
-                    yield dealing;
-                }
-            };
+            let outbound = batch::batches(client_dealing_receiver, batching, traffic);
             let mut request = Request::new(outbound);
             let metadata_value = BinaryMetadataValue::from_bytes(&node_setup.public_key);
             request
@@ -309,20 +740,24 @@ impl Sample for MySample {
                 .await
                 .unwrap();
             let mut inbound = response.into_inner();
-            while let Some(dealing) = inbound.message().await.unwrap() {
-                utils::debug_line_to_file("Received.", "inbound_dealing_received.debug.txt");
-                utils::debug_line_to_file("Received.", "client_received.debug.txt");
-                inbound_dealing_sender.send(dealing).unwrap();
+            while let Some(batch) = inbound.message().await.unwrap() {
+                for dealing in batch.dealings {
+                    utils::debug_line_to_file("Received.", "inbound_dealing_received.debug.txt");
+                    utils::debug_line_to_file("Received.", "client_received.debug.txt");
+                    inbound_dealing_sender
+                        .send(dealing.into_dealing().unwrap())
+                        .unwrap();
+                }
             }
         });
 
+        let public_key = PublicKey::from(public_key);
         let new_peer = Peer {
             address: address.clone(),
             public_key: public_key.clone(),
-            connection: Some(client.clone()),
             server_dealing_sender: None,
             client_dealing_sender: Some(client_dealing_sender),
-            random_dealings: BTreeMap::new(),
+            random_dealings: DealingHistory::shared(self.peer_history_rounds),
         };
         // Don't add the peer if it's already there
         // Don't add the peer if it resolves to this node
@@ -332,7 +767,7 @@ impl Sample for MySample {
 
         Ok(Response::new(PeerResponse {
             success: true,
-            public_key: self.node_setup.public_key.clone(),
+            public_key: self.node_setup.public_key.to_vec(),
         }))
     }
     // Add as a peer every node whose ID is less than mine.
@@ -347,13 +782,13 @@ impl Sample for MySample {
         for n in 1..=n {
             let add_peer_request = Request::new(AddPeerRequest {
                 address: format!("http://tokio-sample-node-{}:2323", n),
-                public_key: my_public_key.clone(),
+                public_key: my_public_key.to_vec(),
             });
             self.add_peer(add_peer_request).await.unwrap();
         }
         Ok(Response::new(PeerResponse {
             success: true,
-            public_key: self.node_setup.public_key.clone(),
+            public_key: self.node_setup.public_key.to_vec(),
         }))
     }
     async fn check_health(
@@ -362,7 +797,478 @@ impl Sample for MySample {
     ) -> Result<Response<HealthResponse>, Status> {
         Ok(Response::new(HealthResponse {
             healthy: true,
-            public_key: self.node_setup.public_key.clone(),
+            public_key: self.node_setup.public_key.to_vec(),
+        }))
+    }
+    async fn get_stats(
+        &self,
+        _request: Request<StatsRequest>,
+    ) -> Result<Response<StatsResponse>, Status> {
+        let stats = self.aggregator.lock().stats();
+        Ok(Response::new(StatsResponse {
+            rounds_in_flight: stats.rounds_in_flight as u64,
+            rounds_retained: stats.rounds_retained as u64,
+            rounds_completed: stats.rounds_completed,
+            rounds_pruned: stats.rounds_pruned,
+            results_stored: stats.results_stored as u64,
+            aggregator_bytes: stats.approx_bytes as u64,
+            rounds_failed: stats.rounds_failed,
+            stragglers: stats.stragglers,
+            equivocations: stats.equivocations as u64,
+            presignatures_ready: self.signer.presignatures_ready() as u64,
+            dealing_raw_bytes_sent: self.traffic.raw_bytes_sent(),
+            dealing_wire_bytes_sent: self.traffic.wire_bytes_sent(),
+        }))
+    }
+    async fn get_failed_rounds(
+        &self,
+        _request: Request<FailedRoundsRequest>,
+    ) -> Result<Response<FailedRoundsResponse>, Status> {
+        let rounds = self
+            .aggregator
+            .lock()
+            .failed_rounds()
+            .into_iter()
+            .map(|failed_round| crate::sample::FailedRound {
+                protocol_round: failed_round.protocol_round,
+                received: failed_round.received as u32,
+                missing: utils::to_vecs(&failed_round.missing),
+                age_ms: failed_round.age.as_millis() as u64,
+                round_id: failed_round.round.to_vec(),
+            })
+            .collect();
+        Ok(Response::new(FailedRoundsResponse { rounds }))
+    }
+    async fn get_round(
+        &self,
+        request: Request<RoundRequest>,
+    ) -> Result<Response<RoundResponse>, Status> {
+        let round_id = RoundId::from(request.into_inner().round_id);
+        let committee = self.peers.public_keys();
+        let aggregator = self.aggregator.lock();
+        let mut response = if let Some(round_state) = aggregator.round(&round_id) {
+            let now = Instant::now();
+            let contributors: Vec<PublicKey> = round_state
+                .dealings
+                .keys()
+                .filter(|public_key| !round_state.stragglers.contains(public_key))
+                .cloned()
+                .collect();
+            let (status, missing) = match &round_state.missing {
+                Some(missing) => (RoundStatus::Failed, missing.clone()),
+                None => (
+                    if round_state.completed_at.is_some() {
+                        RoundStatus::Complete
+                    } else {
+                        RoundStatus::Collecting
+                    },
+                    committee
+                        .iter()
+                        .filter(|public_key| !round_state.dealings.contains_key(*public_key))
+                        .cloned()
+                        .collect(),
+                ),
+            };
+            let mut response = RoundResponse {
+                protocol_round: round_state.protocol_round,
+                contributors: utils::to_vecs(&contributors),
+                missing: utils::to_vecs(&missing),
+                stragglers: utils::to_vecs(&round_state.stragglers),
+                disqualified: utils::to_vecs(&round_state.disqualified),
+                first_arrival_age_ms: now.duration_since(round_state.first_arrival).as_millis()
+                    as u64,
+                last_arrival_age_ms: now.duration_since(round_state.last_arrival).as_millis()
+                    as u64,
+                output: round_state
+                    .output
+                    .as_ref()
+                    .map(|output| frost::serialize_point(&output.public_key))
+                    .unwrap_or_default(),
+                ..Default::default()
+            };
+            response.set_status(if round_state.tainted {
+                RoundStatus::Tainted
+            } else {
+                status
+            });
+            response
+        } else if let Some(result) = aggregator.result(&round_id) {
+            let mut response = RoundResponse {
+                contributors: utils::to_vecs(&result.contributors),
+                missing: utils::to_vecs(
+                    committee
+                        .iter()
+                        .filter(|public_key| !result.contributors.contains(public_key)),
+                ),
+                output: result
+                    .output
+                    .as_ref()
+                    .map(|output| frost::serialize_point(&output.public_key))
+                    .unwrap_or_default(),
+                pruned: true,
+                ..Default::default()
+            };
+            response.set_status(if result.tainted {
+                RoundStatus::Tainted
+            } else {
+                RoundStatus::Complete
+            });
+            response
+        } else {
+            return Err(Status::new(
+                Code::NotFound,
+                format!("Round {} is not known", utils::hex(&round_id)),
+            ));
+        };
+        response.round_id = round_id.to_vec();
+        Ok(Response::new(response))
+    }
+    async fn get_peer_dealings(
+        &self,
+        request: Request<PeerDealingsRequest>,
+    ) -> Result<Response<PeerDealingsResponse>, Status> {
+        let request = request.into_inner();
+        let peer = self
+            .peers
+            .snapshot()
+            .get(&PublicKey::from(request.public_key.clone()))
+            .cloned()
+            .ok_or_else(|| {
+                Status::new(
+                    Code::NotFound,
+                    format!("{} is not a peer", utils::hex(&request.public_key)),
+                )
+            })?;
+        let exchanged = peer
+            .random_dealings
+            .lock()
+            .get(&RoundId::from(request.round_id))
+            .cloned()
+            .unwrap_or_default();
+        Ok(Response::new(PeerDealingsResponse {
+            received: exchanged.received,
+            sent: exchanged.sent,
         }))
     }
+    async fn get_equivocations(
+        &self,
+        _request: Request<EquivocationsRequest>,
+    ) -> Result<Response<EquivocationsResponse>, Status> {
+        let evidence = self
+            .aggregator
+            .lock()
+            .equivocations()
+            .into_iter()
+            .map(|equivocation| crate::sample::Equivocation {
+                first: Some(equivocation.first),
+                second: Some(equivocation.second),
+                first_key: equivocation.first_key,
+                second_key: equivocation.second_key,
+            })
+            .collect();
+        Ok(Response::new(EquivocationsResponse { evidence }))
+    }
+    // Accept equivocation evidence forwarded by another node
+    async fn report_equivocation(
+        &self,
+        request: Request<crate::sample::Equivocation>,
+    ) -> Result<Response<ReportEquivocationResponse>, Status> {
+        let request_inner = request.into_inner();
+        let (first, second) = match (request_inner.first, request_inner.second) {
+            (Some(first), Some(second)) => (first, second),
+            _ => {
+                return Err(Status::new(
+                    Code::InvalidArgument,
+                    "Equivocation evidence needs two dealings.",
+                ))
+            }
+        };
+        let equivocation = Equivocation {
+            first,
+            first_key: request_inner.first_key,
+            second,
+            second_key: request_inner.second_key,
+        };
+        if !equivocation.verify(self.scheme.as_ref()) {
+            return Ok(Response::new(ReportEquivocationResponse {
+                accepted: false,
+                new: false,
+            }));
+        }
+        let new = self.aggregator.lock().record_equivocation(equivocation);
+        Ok(Response::new(ReportEquivocationResponse {
+            accepted: true,
+            new,
+        }))
+    }
+    // Start a distributed key generation across the current committee. Every other member joins
+    // when it receives this node's dealing.
+    async fn start_dkg(
+        &self,
+        request: Request<DkgRequest>,
+    ) -> Result<Response<DkgResponse>, Status> {
+        let request_inner = request.into_inner();
+        let committee_size = self.peers.public_keys().len();
+        // Every member checks dealings against its own DKG_THRESHOLD, so a request can't change it
+        let threshold = self.dkg.threshold();
+        if request_inner.threshold != 0 && request_inner.threshold as usize != threshold {
+            return Err(Status::new(
+                Code::InvalidArgument,
+                format!(
+                    "Threshold {} differs from the configured DKG threshold {}.",
+                    request_inner.threshold, threshold
+                ),
+            ));
+        }
+        if threshold > committee_size {
+            return Err(Status::new(
+                Code::FailedPrecondition,
+                format!(
+                    "Threshold {} is larger than the committee of {}.",
+                    threshold, committee_size
+                ),
+            ));
+        }
+        if self.signer.key().is_some() && !request_inner.replace_key {
+            return Err(Status::new(
+                Code::FailedPrecondition,
+                "This node already holds a group key. Set replace_key to replace it.",
+            ));
+        }
+        // Without a shared nonce the nodes' rounds never line up, and a reused one replays the
+        // round IDs of an earlier run
+        let session_nonce = request_inner.session_nonce;
+        if session_nonce.is_empty() {
+            return Err(Status::new(
+                Code::InvalidArgument,
+                "A session_nonce shared by every node is required.",
+            ));
+        }
+        let dkg = self.dkg.clone();
+        let round_id = tokio::task::spawn_blocking(move || {
+            dkg.start(&session_nonce, request_inner.replace_key)
+        })
+        .await
+        .unwrap();
+        Ok(Response::new(DkgResponse {
+            round_id: round_id.to_vec(),
+        }))
+    }
+    // Move the installed key to the current committee, optionally with a new threshold. Every
+    // other holder of the key joins when it receives this node's dealing.
+    async fn reshare(
+        &self,
+        request: Request<ReshareRequest>,
+    ) -> Result<Response<ReshareResponse>, Status> {
+        let request_inner = request.into_inner();
+        let committee_size = self.peers.public_keys().len();
+        let threshold = match request_inner.threshold {
+            0 => None,
+            threshold => Some(threshold as usize),
+        };
+        if threshold.unwrap_or(0) > committee_size {
+            return Err(Status::new(
+                Code::FailedPrecondition,
+                format!(
+                    "Threshold {} is larger than the committee of {}.",
+                    request_inner.threshold, committee_size
+                ),
+            ));
+        }
+        // Without a shared nonce the nodes' rounds never line up, and a reused one replays the
+        // round IDs of an earlier run
+        let session_nonce = request_inner.session_nonce;
+        if session_nonce.is_empty() {
+            return Err(Status::new(
+                Code::InvalidArgument,
+                "A session_nonce shared by every node is required.",
+            ));
+        }
+        let dkg = self.dkg.clone();
+        let round_id =
+            tokio::task::spawn_blocking(move || dkg.start_reshare(&session_nonce, threshold))
+                .await
+                .unwrap();
+        match round_id {
+            Some(round_id) => Ok(Response::new(ReshareResponse {
+                round_id: round_id.to_vec(),
+            })),
+            None => Err(Status::new(
+                Code::FailedPrecondition,
+                "This node holds no share of a group key.",
+            )),
+        }
+    }
+    async fn get_group_key(
+        &self,
+        _request: Request<GroupKeyRequest>,
+    ) -> Result<Response<GroupKeyResponse>, Status> {
+        let key_package = match self.signer.key() {
+            Some((key_package, _)) => key_package,
+            None => {
+                return Err(Status::new(
+                    Code::NotFound,
+                    "No key generation has completed on this node.",
+                ))
+            }
+        };
+        Ok(Response::new(GroupKeyResponse {
+            group_public_key: frost::serialize_point(&key_package.group_public_key),
+            threshold: u32::from(key_package.threshold),
+            identifier: u32::from(key_package.identifier),
+            verifying_share: frost::serialize_point(
+                &key_package.verifying_shares[&key_package.identifier],
+            ),
+            round_id: self.dkg.key_round().unwrap_or_default().to_vec(),
+        }))
+    }
+    // Sign a message with the group key, coordinating the signing round across the committee
+    async fn sign(
+        &self,
+        request: Request<SignatureRequest>,
+    ) -> Result<Response<SignatureResponse>, Status> {
+        let group_public_key = match self.signer.group_public_key() {
+            Some(group_public_key) => group_public_key,
+            None => {
+                return Err(Status::new(
+                    Code::FailedPrecondition,
+                    "This node holds no share of a group key.",
+                ))
+            }
+        };
+        let message = request.into_inner().message.into_bytes();
+        let signer = self.signer.clone();
+        let session = tokio::task::spawn_blocking(move || signer.start_signing(message))
+            .await
+            .unwrap();
+        let round_id = session.round_id;
+        let group_signature =
+            match tokio::time::timeout(FROST_SIGN_TIMEOUT, session.result_receiver).await {
+                Ok(Ok(Ok(group_signature))) => group_signature,
+                Ok(Ok(Err(error))) => {
+                    return Err(Status::new(
+                        Code::Aborted,
+                        format!("Signing failed: {}", error),
+                    ))
+                }
+                Ok(Err(_)) | Err(_) => {
+                    self.signer.abandon(&round_id);
+                    return Err(Status::new(
+                        Code::DeadlineExceeded,
+                        "Not enough signers answered in time.",
+                    ));
+                }
+            };
+        Ok(Response::new(SignatureResponse {
+            signature: group_signature.signature,
+            group_public_key: frost::serialize_point(&group_public_key),
+            contributors: utils::to_vecs(&group_signature.contributors),
+            round_id: round_id.to_vec(),
+            presigned: session.presigned,
+        }))
+    }
+    // Check a signature from Sign against the node's group key or a given one
+    async fn verify(
+        &self,
+        request: Request<VerifyRequest>,
+    ) -> Result<Response<VerifyResponse>, Status> {
+        let request_inner = request.into_inner();
+        let group_public_key = if request_inner.group_public_key.is_empty() {
+            match self.signer.group_public_key() {
+                Some(group_public_key) => group_public_key,
+                None => {
+                    return Err(Status::new(
+                        Code::FailedPrecondition,
+                        "This node holds no share of a group key.",
+                    ))
+                }
+            }
+        } else {
+            match frost::deserialize_point(&request_inner.group_public_key) {
+                Ok(group_public_key) => group_public_key,
+                Err(_) => {
+                    return Err(Status::new(
+                        Code::InvalidArgument,
+                        "The group public key is not a valid point.",
+                    ))
+                }
+            }
+        };
+        let valid = frost::verify_signature(
+            &group_public_key,
+            request_inner.message.as_bytes(),
+            &request_inner.signature,
+        )
+        .is_ok();
+        Ok(Response::new(VerifyResponse { valid }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sample::RetransmitRequest;
+    use crate::wire::EncodedDealing;
+
+    // A single peer that remembers history_rounds rounds, with the receiving end of its stream
+    fn peer(history_rounds: usize) -> (PeerMap, Arc<Peer>, mpsc::Receiver<EncodedDealing>) {
+        let (sender, receiver) = mpsc::channel(10);
+        let peers = PeerMap::new();
+        peers.add_peer(
+            Peer {
+                address: "peer".to_string(),
+                public_key: PublicKey::from(vec![1; 32]),
+                server_dealing_sender: None,
+                client_dealing_sender: Some(sender),
+                random_dealings: DealingHistory::shared(history_rounds),
+            },
+            1,
+        );
+        let peer = peers.snapshot().values().next().unwrap().clone();
+        (peers, peer, receiver)
+    }
+
+    fn request(peer: &Peer, rounds: &[RoundId]) -> Dealing {
+        Dealing {
+            dealing: RetransmitRequest {
+                round_ids: utils::to_vecs(rounds),
+            }
+            .encode_to_vec()
+            .into(),
+            public_key: peer.public_key.clone(),
+            kind: DealingKind::RetransmitRequest as i32,
+            ..Default::default()
+        }
+    }
+
+    fn sent_to(peer: &Peer, round: u8) -> Dealing {
+        Dealing {
+            round_id: vec![round].into(),
+            recipient: peer.public_key.clone(),
+            dealing: b"sealed".to_vec().into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn peer_gets_the_dealings_it_was_sent_again() {
+        let (peers, peer, mut receiver) = peer(10);
+        peer.random_dealings.lock().record_sent(&sent_to(&peer, 1));
+        MySample::retransmit(
+            &request(&peer, &[RoundId::from(vec![1]), RoundId::from(vec![2])]),
+            &peers,
+        );
+        let retransmitted = receiver.try_recv().unwrap().into_dealing().unwrap();
+        assert_eq!(retransmitted, sent_to(&peer, 1));
+        // Nothing was sent in the second round
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn nothing_is_retransmitted_without_a_history() {
+        let (peers, peer, mut receiver) = peer(0);
+        peer.random_dealings.lock().record_sent(&sent_to(&peer, 1));
+        MySample::retransmit(&request(&peer, &[RoundId::from(vec![1])]), &peers);
+        assert!(receiver.try_recv().is_err());
+    }
 }
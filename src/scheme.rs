@@ -0,0 +1,127 @@
+// How a node deals its contribution to a round, checks the contributions it receives and combines
+// a complete round. The scheme is picked by name with DEALING_SCHEME, so every node in a network
+// must use the same one.
+
+// System
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+// Third Party
+use prost::Message;
+
+// Local
+use super::frost::{FrostError, Identifier};
+use super::types::PublicKey;
+use super::vss::{self, JointShare};
+use crate::sample::VssDealing;
+
+pub const DEFAULT_SCHEME: &str = "feldman-vss";
+
+// Committee members are identified by their position in the sorted committee, starting from 1.
+// Dealing, validating and combining do scalar multiplications, so call them from spawn_blocking.
+pub trait DealingScheme: Send + Sync {
+    // Deal for a new round. Returns every committee member's payload, padded to at least
+    // payload_size bytes. The payloads are sealed to their recipients by the caller.
+    fn deal(
+        &self,
+        committee: &[PublicKey],
+        threshold: u32,
+        payload_size: usize,
+    ) -> BTreeMap<PublicKey, Vec<u8>>;
+
+    // Check the payload a dealer sent me before it counts towards its round
+    fn validate(
+        &self,
+        payload: &[u8],
+        identifier: Identifier,
+        threshold: u32,
+    ) -> Result<(), FrostError>;
+
+    // The part of a validated payload that the dealer sends every recipient alike. Reliable
+    // broadcast agrees on its digest.
+    fn public_part(&self, payload: &[u8]) -> Result<Vec<u8>, FrostError>;
+
+    // Combine the validated payloads of a complete round into my share of its output
+    fn combine(
+        &self,
+        payloads: &[Vec<u8>],
+        identifier: Identifier,
+    ) -> Result<JointShare, FrostError>;
+}
+
+// Every dealing is a Feldman verifiable secret sharing of a random secret and a round's output is
+// the sum of the dealt secrets
+pub struct FeldmanVss;
+
+impl DealingScheme for FeldmanVss {
+    fn deal(
+        &self,
+        committee: &[PublicKey],
+        threshold: u32,
+        payload_size: usize,
+    ) -> BTreeMap<PublicKey, Vec<u8>> {
+        let dealing = vss::deal(committee.len() as u16, threshold as u16);
+        committee
+            .iter()
+            .filter_map(|recipient| {
+                vss::payload_for(&dealing, committee, recipient, payload_size)
+                    .map(|payload| (recipient.clone(), payload))
+            })
+            .collect()
+    }
+
+    fn validate(
+        &self,
+        payload: &[u8],
+        identifier: Identifier,
+        threshold: u32,
+    ) -> Result<(), FrostError> {
+        vss::verify(payload, identifier, threshold as usize)
+    }
+
+    // The coefficient commitments
+    fn public_part(&self, payload: &[u8]) -> Result<Vec<u8>, FrostError> {
+        let dealing = VssDealing::decode(payload).map_err(|_| FrostError::InvalidPayload)?;
+        let commitments = VssDealing {
+            commitments: dealing.commitments,
+            ..Default::default()
+        };
+        Ok(commitments.encode_to_vec())
+    }
+
+    fn combine(
+        &self,
+        payloads: &[Vec<u8>],
+        identifier: Identifier,
+    ) -> Result<JointShare, FrostError> {
+        vss::combine(identifier, payloads.iter().map(|payload| &payload[..]))
+    }
+}
+
+// The schemes a node can be configured with. Register additional schemes before starting the
+// server.
+#[derive(Clone)]
+pub struct Schemes {
+    schemes: HashMap<String, Arc<dyn DealingScheme>>,
+}
+
+impl Default for Schemes {
+    fn default() -> Self {
+        let mut schemes = Self {
+            schemes: HashMap::new(),
+        };
+        schemes.register(DEFAULT_SCHEME, Arc::new(FeldmanVss));
+        schemes
+    }
+}
+
+impl Schemes {
+    // Replaces any scheme already registered under the same name
+    pub fn register(&mut self, name: &str, scheme: Arc<dyn DealingScheme>) {
+        self.schemes.insert(name.to_string(), scheme);
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn DealingScheme>> {
+        self.schemes.get(name).cloned()
+    }
+}
@@ -0,0 +1,26 @@
+// Third Party
+use ring::digest;
+
+// Local
+use super::types::{PublicKey, RoundId};
+
+const ROUND_ID_CONTEXT: &[u8] = b"tokio-sample round id v1";
+// Every round ID is a SHA-256 digest
+pub const ROUND_ID_LEN: usize = 32;
+
+// Every node that is given the same committee and session nonce derives the same round IDs, no
+// matter how many sessions it has run before or in which order its dealings were created.
+// The committee must be sorted, which it is when it comes from the PeerMap.
+pub fn round_id(committee: &[PublicKey], session_nonce: &[u8], index: u32) -> RoundId {
+    let mut context = digest::Context::new(&digest::SHA256);
+    context.update(ROUND_ID_CONTEXT);
+    context.update(&(committee.len() as u32).to_be_bytes());
+    for public_key in committee {
+        context.update(&(public_key.len() as u32).to_be_bytes());
+        context.update(public_key);
+    }
+    context.update(&(session_nonce.len() as u32).to_be_bytes());
+    context.update(session_nonce);
+    context.update(&index.to_be_bytes());
+    context.finish().as_ref().to_vec().into()
+}
@@ -0,0 +1,853 @@
+// Drives FROST signing over the dealing streams with the key produced by the DKG
+// A node that is asked to sign acts as the coordinator for that signing session:
+// 1. It broadcasts a FROST_SIGN_REQUEST.
+// 2. Every participant holding a key share replies to it with a FROST_COMMITMENT.
+// 3. Once it has threshold commitments it sends those signers a FROST_SIGNING_PACKAGE.
+// 4. Each of them replies with a FROST_SIGNATURE_SHARE, which it verifies and aggregates.
+// Steps 1 to 3 don't depend on the message, so a coordinator can run them ahead of time and keep
+// the sessions that have their signers as a pool of presignatures. Signing with a presignature
+// starts at step 3 and takes a single round trip. There is a pool per group key and committee,
+// and a presignature is only used while the node signs with the key and committee it was made for.
+// A signer whose share fails verification is left out and the message is signed again, with a
+// presignature or fresh commitments from other signers, until fewer than threshold are left.
+
+// System
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// Third Party
+use curve25519_dalek::edwards::EdwardsPoint;
+use curve25519_dalek::scalar::Scalar;
+use parking_lot::Mutex;
+use prost::Message;
+use ring::rand::{SecureRandom, SystemRandom};
+use tokio::sync::oneshot;
+
+// Local
+use super::frost::{self, FrostError, Identifier, KeyPackage, SigningCommitment, SigningNonces};
+use super::outbox::Outbox;
+use super::types::{PublicKey, RoundId};
+use super::utils;
+use crate::sample::{
+    Dealing, DealingKind, FrostCommitment, FrostSignRequest, FrostSignatureShare,
+    FrostSigningPackage,
+};
+
+// A group signature with the node identities of the participants whose shares it combines
+pub struct GroupSignature {
+    pub signature: Vec<u8>,
+    pub contributors: Vec<PublicKey>,
+}
+
+pub type SigningResult = Result<GroupSignature, FrostError>;
+
+// A signing session this node coordinates
+pub struct SigningSession {
+    pub round_id: RoundId,
+    // Whether the session started from a pooled presignature
+    pub presigned: bool,
+    pub result_receiver: oneshot::Receiver<SigningResult>,
+}
+
+// The group key and committee presignatures are made for, each of which has a pool of its own
+#[derive(Clone, Default, PartialEq, Eq, Hash)]
+struct PoolKey {
+    group_public_key: Vec<u8>,
+    threshold: u16,
+    committee: Vec<PublicKey>,
+}
+
+impl PoolKey {
+    fn new(key_package: &KeyPackage, participants: &BTreeMap<Identifier, PublicKey>) -> Self {
+        Self {
+            group_public_key: frost::serialize_point(&key_package.group_public_key),
+            threshold: key_package.threshold,
+            committee: participants.values().cloned().collect(),
+        }
+    }
+}
+
+// The coordinator's view of one signing session
+struct CoordinatorSession {
+    pool_key: PoolKey,
+    // The session start_signing returned, which a retry carries over
+    origin: RoundId,
+    // Not known yet while the session is a presignature
+    message: Option<Vec<u8>>,
+    // Signers whose shares failed verification in an earlier attempt
+    excluded: BTreeSet<Identifier>,
+    commitments: Vec<SigningCommitment>,
+    // Fixed once threshold commitments have arrived
+    signers: Option<Vec<SigningCommitment>>,
+    signature_shares: BTreeMap<Identifier, Scalar>,
+    result_sender: Option<oneshot::Sender<SigningResult>>,
+    started: Instant,
+}
+
+// Participant side: my nonces for a session I committed to
+struct CommittedNonces {
+    coordinator: PublicKey,
+    nonces: SigningNonces,
+    committed: Instant,
+}
+
+#[derive(Default)]
+struct FrostState {
+    key_package: Option<KeyPackage>,
+    // The node identity of every participant of the current key
+    participants: BTreeMap<Identifier, PublicKey>,
+    pool_key: Option<PoolKey>,
+    nonces: HashMap<RoundId, CommittedNonces>,
+    // Coordinator side
+    sessions: HashMap<RoundId, CoordinatorSession>,
+    // Presignature sessions that have their signers, oldest first
+    presignatures: HashMap<PoolKey, VecDeque<RoundId>>,
+}
+
+impl FrostState {
+    // Take the oldest presignature for the installed key and committee none of whose signers are
+    // excluded
+    fn take_presignature(&mut self, excluded: &BTreeSet<Identifier>) -> Option<RoundId> {
+        let pool = self.presignatures.get_mut(self.pool_key.as_ref()?)?;
+        let sessions = &self.sessions;
+        let position = pool.iter().position(|round_id| {
+            sessions.get(round_id).is_some_and(|session| {
+                session
+                    .signers
+                    .iter()
+                    .flatten()
+                    .all(|commitment| !excluded.contains(&commitment.identifier))
+            })
+        })?;
+        pool.remove(position)
+    }
+}
+
+#[derive(Clone)]
+pub struct FrostSigner {
+    state: Arc<Mutex<FrostState>>,
+    outbox: Outbox,
+}
+
+impl FrostSigner {
+    pub fn new(outbox: Outbox) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(FrostState::default())),
+            outbox,
+        }
+    }
+
+    pub fn group_public_key(&self) -> Option<EdwardsPoint> {
+        self.state
+            .lock()
+            .key_package
+            .as_ref()
+            .map(|key_package| key_package.group_public_key)
+    }
+
+    // The installed key with the node identity of every participant
+    pub fn key(&self) -> Option<(KeyPackage, BTreeMap<Identifier, PublicKey>)> {
+        let state = self.state.lock();
+        state
+            .key_package
+            .clone()
+            .map(|key_package| (key_package, state.participants.clone()))
+    }
+
+    // Replace the key this node signs with, along with the node identity of every participant
+    pub fn install_key(
+        &self,
+        key_package: KeyPackage,
+        participants: BTreeMap<Identifier, PublicKey>,
+    ) {
+        let pool_key = PoolKey::new(&key_package, &participants);
+        let mut state_guard = self.state.lock();
+        let state = &mut *state_guard;
+        // Commitments don't depend on the key share, so nonces and presignatures outlive a refresh
+        // that keeps the group key and committee. Those for another key or committee are useless
+        // now, and so are the sessions that already asked for shares made with the old share.
+        if state.pool_key.as_ref() != Some(&pool_key) {
+            state.nonces.clear();
+        }
+        state.presignatures.retain(|key, _| *key == pool_key);
+        let ended: Vec<RoundId> = state
+            .sessions
+            .iter()
+            .filter(|(_, session)| {
+                session.pool_key != pool_key
+                    || (session.message.is_some() && session.signers.is_some())
+            })
+            .map(|(round_id, _)| round_id.clone())
+            .collect();
+        for round_id in ended {
+            let session = state.sessions.remove(&round_id).unwrap();
+            if let Some(result_sender) = session.result_sender {
+                let _ = result_sender.send(Err(FrostError::KeyRotated));
+            }
+        }
+        state.key_package = Some(key_package);
+        state.participants = participants;
+        state.pool_key = Some(pool_key);
+    }
+
+    // Start a signing session as its coordinator, from a presignature if one is ready. The
+    // receiver yields the group signature.
+    pub fn start_signing(&self, message: Vec<u8>) -> SigningSession {
+        let (result_sender, result_receiver) = oneshot::channel();
+        let (round_id, presigned) = self.sign_with(message, result_sender, None, BTreeSet::new());
+        SigningSession {
+            round_id,
+            presigned,
+            result_receiver,
+        }
+    }
+
+    // Sign with a presignature none of whose signers are excluded if one is ready, and otherwise
+    // ask every participant for fresh commitments. Returns the session and whether it was
+    // presigned.
+    fn sign_with(
+        &self,
+        message: Vec<u8>,
+        result_sender: oneshot::Sender<SigningResult>,
+        origin: Option<RoundId>,
+        excluded: BTreeSet<Identifier>,
+    ) -> (RoundId, bool) {
+        let mut state_guard = self.state.lock();
+        let state = &mut *state_guard;
+        if let Some(round_id) = state.take_presignature(&excluded) {
+            let session = state.sessions.get_mut(&round_id).unwrap();
+            session.message = Some(message.clone());
+            session.result_sender = Some(result_sender);
+            session.origin = origin.unwrap_or_else(|| round_id.clone());
+            session.excluded = excluded;
+            let signers = signer_identities(&state.participants, session);
+            drop(state_guard);
+            self.send_signing_package(&round_id, message, &signers);
+            return (round_id, true);
+        }
+        drop(state_guard);
+        let round_id =
+            self.open_session(Some(message.clone()), Some(result_sender), origin, excluded);
+        let request = FrostSignRequest { message };
+        self.outbox.broadcast(frost_dealing(
+            &round_id,
+            DealingKind::FrostSignRequest,
+            &request,
+        ));
+        (round_id, false)
+    }
+
+    // Top the presignature pool back up to target once fewer than low_watermark presignatures are
+    // ready. Presignatures that are not used within lifetime are given up, whether they found their
+    // signers or not, so lifetime must be shorter than the time participants keep their nonces.
+    // This sends dealings, so it must be called from spawn_blocking.
+    pub fn refill_presignatures(&self, target: usize, low_watermark: usize, lifetime: Duration) {
+        let missing = {
+            let mut state_guard = self.state.lock();
+            let state = &mut *state_guard;
+            let pool_key = match &state.pool_key {
+                Some(pool_key) => pool_key.clone(),
+                None => return,
+            };
+            state.sessions.retain(|_, session| {
+                session.message.is_some() || session.started.elapsed() < lifetime
+            });
+            let sessions = &state.sessions;
+            state.presignatures.retain(|_, pool| {
+                pool.retain(|round_id| sessions.contains_key(round_id));
+                !pool.is_empty()
+            });
+            let ready = state.presignatures.get(&pool_key).map_or(0, VecDeque::len);
+            if ready >= low_watermark {
+                return;
+            }
+            // Sessions that are still collecting commitments will fill the pool too
+            let pooled = state
+                .sessions
+                .values()
+                .filter(|session| session.message.is_none() && session.pool_key == pool_key)
+                .count();
+            target.saturating_sub(pooled)
+        };
+        for _ in 0..missing {
+            let round_id = self.open_session(None, None, None, BTreeSet::new());
+            self.outbox.broadcast(frost_dealing(
+                &round_id,
+                DealingKind::FrostSignRequest,
+                &FrostSignRequest::default(),
+            ));
+        }
+    }
+
+    // Forget the nonces of sessions whose signing package has not arrived within timeout, for
+    // example because the coordinator picked other signers
+    pub fn expire_nonces(&self, timeout: Duration) {
+        self.state
+            .lock()
+            .nonces
+            .retain(|_, committed| committed.committed.elapsed() < timeout);
+    }
+
+    // How many presignatures are ready for the installed key and committee
+    pub fn presignatures_ready(&self) -> usize {
+        let state = self.state.lock();
+        state
+            .pool_key
+            .as_ref()
+            .and_then(|pool_key| state.presignatures.get(pool_key))
+            .map_or(0, VecDeque::len)
+    }
+
+    fn open_session(
+        &self,
+        message: Option<Vec<u8>>,
+        result_sender: Option<oneshot::Sender<SigningResult>>,
+        origin: Option<RoundId>,
+        excluded: BTreeSet<Identifier>,
+    ) -> RoundId {
+        let round_id = random_round_id();
+        let mut state = self.state.lock();
+        let pool_key = state.pool_key.clone().unwrap_or_default();
+        state.sessions.insert(
+            round_id.clone(),
+            CoordinatorSession {
+                pool_key,
+                origin: origin.unwrap_or_else(|| round_id.clone()),
+                message,
+                excluded,
+                commitments: Vec::new(),
+                signers: None,
+                signature_shares: BTreeMap::new(),
+                result_sender,
+                started: Instant::now(),
+            },
+        );
+        round_id
+    }
+
+    // Forget a session whose caller stopped waiting for it, along with any retry of it
+    pub fn abandon(&self, round_id: &RoundId) {
+        self.state
+            .lock()
+            .sessions
+            .retain(|_, session| session.origin != *round_id);
+    }
+
+    // Handle one inbound FROST dealing whose signature has already been verified. This may send
+    // dealings, so it must be called from spawn_blocking.
+    pub fn handle(&self, dealing: Dealing) {
+        let result = match dealing.kind() {
+            DealingKind::FrostSignRequest => self.handle_sign_request(&dealing),
+            DealingKind::FrostCommitment => self.handle_commitment(&dealing),
+            DealingKind::FrostSigningPackage => self.handle_signing_package(&dealing),
+            DealingKind::FrostSignatureShare => self.handle_signature_share(&dealing),
+            DealingKind::Random
+            | DealingKind::DkgDealing
+            | DealingKind::ReshareDealing
+            | DealingKind::ReshareSelection
+            | DealingKind::RetransmitRequest
+            | DealingKind::RbcEcho
+            | DealingKind::RbcReady
+            | DealingKind::Complaint
+            | DealingKind::ComplaintAnswer
+            | DealingKind::BeaconShare => Ok(()),
+        };
+        if let Err(error) = result {
+            println!(
+                "Dropping a {:?} dealing for session {}: {}",
+                dealing.kind(),
+                utils::hex(&dealing.round_id),
+                error
+            );
+        }
+    }
+
+    fn handle_sign_request(&self, dealing: &Dealing) -> Result<(), FrostError> {
+        // Check the request is well formed before committing to it
+        decode::<FrostSignRequest>(dealing)?;
+        let commitment = {
+            let mut state = self.state.lock();
+            let key_package = match &state.key_package {
+                Some(key_package) => key_package,
+                // I can't take part without a key share
+                None => return Ok(()),
+            };
+            let (nonces, commitment) = frost::commit(key_package);
+            state.nonces.insert(
+                dealing.round_id.clone(),
+                CommittedNonces {
+                    coordinator: dealing.public_key.clone(),
+                    nonces,
+                    committed: Instant::now(),
+                },
+            );
+            commitment
+        };
+        let commitment = FrostCommitment {
+            identifier: u32::from(commitment.identifier),
+            hiding: frost::serialize_point(&commitment.hiding),
+            binding: frost::serialize_point(&commitment.binding),
+        };
+        self.outbox.send_to(
+            &dealing.public_key,
+            frost_dealing(&dealing.round_id, DealingKind::FrostCommitment, &commitment),
+        );
+        Ok(())
+    }
+
+    fn handle_commitment(&self, dealing: &Dealing) -> Result<(), FrostError> {
+        let commitment = decode_commitment(&decode::<FrostCommitment>(dealing)?)?;
+        let (message, signers) = {
+            let mut state = self.state.lock();
+            let threshold = match &state.key_package {
+                Some(key_package) => key_package.threshold as usize,
+                None => return Ok(()),
+            };
+            check_sender(&state.participants, commitment.identifier, dealing)?;
+            let state = &mut *state;
+            let session = match state.sessions.get_mut(&dealing.round_id) {
+                Some(session) => session,
+                None => return Ok(()),
+            };
+            if session.signers.is_some()
+                || session.excluded.contains(&commitment.identifier)
+                || session
+                    .commitments
+                    .iter()
+                    .any(|c| c.identifier == commitment.identifier)
+            {
+                return Ok(());
+            }
+            session.commitments.push(commitment);
+            if session.commitments.len() < threshold {
+                return Ok(());
+            }
+            // The first threshold participants to commit are the signers
+            session.signers = Some(session.commitments.clone());
+            match &session.message {
+                Some(message) => (
+                    message.clone(),
+                    signer_identities(&state.participants, session),
+                ),
+                // A presignature waits in the pool for its message
+                None => {
+                    state
+                        .presignatures
+                        .entry(session.pool_key.clone())
+                        .or_default()
+                        .push_back(dealing.round_id.clone());
+                    return Ok(());
+                }
+            }
+        };
+        self.send_signing_package(&dealing.round_id, message, &signers);
+        Ok(())
+    }
+
+    fn send_signing_package(
+        &self,
+        round_id: &RoundId,
+        message: Vec<u8>,
+        signers: &[(PublicKey, SigningCommitment)],
+    ) {
+        let signing_package = FrostSigningPackage {
+            message,
+            commitments: signers
+                .iter()
+                .map(|(_, c)| FrostCommitment {
+                    identifier: u32::from(c.identifier),
+                    hiding: frost::serialize_point(&c.hiding),
+                    binding: frost::serialize_point(&c.binding),
+                })
+                .collect(),
+        };
+        for (public_key, _) in signers {
+            self.outbox.send_to(
+                public_key,
+                frost_dealing(round_id, DealingKind::FrostSigningPackage, &signing_package),
+            );
+        }
+    }
+
+    fn handle_signing_package(&self, dealing: &Dealing) -> Result<(), FrostError> {
+        let signing_package = decode::<FrostSigningPackage>(dealing)?;
+        let commitments: Vec<SigningCommitment> = signing_package
+            .commitments
+            .iter()
+            .map(decode_commitment)
+            .collect::<Result<_, _>>()?;
+        let signature_share = {
+            let mut state = self.state.lock();
+            // Only the coordinator I committed to may ask me to sign, and only once
+            match state.nonces.get(&dealing.round_id) {
+                Some(committed) if committed.coordinator == dealing.public_key => {}
+                _ => return Ok(()),
+            }
+            let nonces = state.nonces.remove(&dealing.round_id).unwrap().nonces;
+            let key_package = match &state.key_package {
+                Some(key_package) => key_package,
+                None => return Ok(()),
+            };
+            let signature_share =
+                frost::sign(key_package, nonces, &signing_package.message, &commitments)?;
+            FrostSignatureShare {
+                identifier: u32::from(key_package.identifier),
+                share: frost::serialize_scalar(&signature_share),
+            }
+        };
+        self.outbox.send_to(
+            &dealing.public_key,
+            frost_dealing(
+                &dealing.round_id,
+                DealingKind::FrostSignatureShare,
+                &signature_share,
+            ),
+        );
+        Ok(())
+    }
+
+    fn handle_signature_share(&self, dealing: &Dealing) -> Result<(), FrostError> {
+        let signature_share = decode::<FrostSignatureShare>(dealing)?;
+        let identifier = identifier(signature_share.identifier)?;
+        let share = frost::deserialize_scalar(&signature_share.share)?;
+        let mut state_guard = self.state.lock();
+        let state = &mut *state_guard;
+        check_sender(&state.participants, identifier, dealing)?;
+        let key_package = match &state.key_package {
+            Some(key_package) => key_package.clone(),
+            None => return Ok(()),
+        };
+        let session = match state.sessions.get_mut(&dealing.round_id) {
+            Some(session) => session,
+            None => return Ok(()),
+        };
+        let (signers, message) = match (&session.signers, &session.message) {
+            (Some(signers), Some(message)) => (signers.clone(), message.clone()),
+            _ => return Ok(()),
+        };
+        let verification = frost::verify_signature_share(
+            identifier,
+            &key_package.verifying_shares[&identifier],
+            &share,
+            &key_package.group_public_key,
+            &message,
+            &signers,
+        );
+        if let Err(error) = verification {
+            // Sign again without that signer, as long as threshold others are left
+            let mut session = state.sessions.remove(&dealing.round_id).unwrap();
+            let result_sender = match session.result_sender.take() {
+                Some(result_sender) => result_sender,
+                None => return Ok(()),
+            };
+            session.excluded.insert(identifier);
+            if state.participants.len() - session.excluded.len() < key_package.threshold as usize {
+                let _ = result_sender.send(Err(error));
+                return Ok(());
+            }
+            drop(state_guard);
+            println!(
+                "Signing session {} again without participant {}: {}",
+                utils::hex(&session.origin),
+                identifier,
+                error
+            );
+            self.sign_with(
+                message,
+                result_sender,
+                Some(session.origin),
+                session.excluded,
+            );
+            return Ok(());
+        }
+        session.signature_shares.insert(identifier, share);
+        if session.signature_shares.len() < signers.len() {
+            return Ok(());
+        }
+        let session = state.sessions.remove(&dealing.round_id).unwrap();
+        let contributors = signers
+            .iter()
+            .map(|commitment| state.participants[&commitment.identifier].clone())
+            .collect();
+        let signature = frost::aggregate(
+            &key_package.group_public_key,
+            &message,
+            &signers,
+            &session.signature_shares,
+        )
+        .and_then(|signature| {
+            frost::verify_signature(&key_package.group_public_key, &message, &signature).map(|_| {
+                GroupSignature {
+                    signature,
+                    contributors,
+                }
+            })
+        });
+        utils::debug_line_to_file("Done.", "frost_signature_complete.debug.txt");
+        if let Some(result_sender) = session.result_sender {
+            let _ = result_sender.send(signature);
+        }
+        Ok(())
+    }
+}
+
+// The node identity and commitment of every signer of a session that has its signers
+fn signer_identities(
+    participants: &BTreeMap<Identifier, PublicKey>,
+    session: &CoordinatorSession,
+) -> Vec<(PublicKey, SigningCommitment)> {
+    session
+        .signers
+        .iter()
+        .flatten()
+        .map(|c| (participants[&c.identifier].clone(), *c))
+        .collect()
+}
+
+fn frost_dealing<M: Message>(round_id: &RoundId, kind: DealingKind, payload: &M) -> Dealing {
+    Dealing {
+        dealing: payload.encode_to_vec().into(),
+        round_id: round_id.clone(),
+        kind: kind as i32,
+        ..Default::default()
+    }
+}
+
+fn decode<M: Message + Default>(dealing: &Dealing) -> Result<M, FrostError> {
+    M::decode(&dealing.dealing[..]).map_err(|_| FrostError::InvalidPayload)
+}
+
+fn decode_commitment(commitment: &FrostCommitment) -> Result<SigningCommitment, FrostError> {
+    Ok(SigningCommitment {
+        identifier: identifier(commitment.identifier)?,
+        hiding: frost::deserialize_point(&commitment.hiding)?,
+        binding: frost::deserialize_point(&commitment.binding)?,
+    })
+}
+
+pub fn identifier(value: u32) -> Result<Identifier, FrostError> {
+    match Identifier::try_from(value) {
+        Ok(identifier) if identifier != 0 => Ok(identifier),
+        _ => Err(FrostError::InvalidPayload),
+    }
+}
+
+// A participant may only speak for its own identifier
+fn check_sender(
+    participants: &BTreeMap<Identifier, PublicKey>,
+    identifier: Identifier,
+    dealing: &Dealing,
+) -> Result<(), FrostError> {
+    if participants.get(&identifier) == Some(&dealing.public_key) {
+        Ok(())
+    } else {
+        Err(FrostError::InvalidShare(identifier))
+    }
+}
+
+fn random_round_id() -> RoundId {
+    let mut round_id = vec![0u8; 32];
+    SystemRandom::new().fill(&mut round_id).unwrap();
+    round_id.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestCommittee;
+
+    // A signer per node of the committee, each holding its share of a key dealt from
+    // group_secret, or of a wrong share for the positions in corrupt
+    fn signers(
+        committee: &TestCommittee,
+        group_secret: Scalar,
+        threshold: usize,
+        corrupt: &[usize],
+    ) -> Vec<FrostSigner> {
+        let signers: Vec<FrostSigner> = committee
+            .nodes
+            .iter()
+            .map(|node| FrostSigner::new(node.outbox.clone()))
+            .collect();
+        install(committee, &signers, group_secret, threshold, corrupt);
+        signers
+    }
+
+    fn install(
+        committee: &TestCommittee,
+        signers: &[FrostSigner],
+        group_secret: Scalar,
+        threshold: usize,
+        corrupt: &[usize],
+    ) {
+        let mut coefficients = vec![group_secret];
+        coefficients.extend((1..threshold).map(|_| frost::random_scalar()));
+        let identifiers: Vec<Identifier> = (1..=signers.len()).map(|id| id as Identifier).collect();
+        let verifying_shares: BTreeMap<Identifier, EdwardsPoint> = identifiers
+            .iter()
+            .map(|identifier| {
+                let share = frost::evaluate_polynomial(&coefficients, *identifier);
+                (*identifier, EdwardsPoint::mul_base(&share))
+            })
+            .collect();
+        let participants: BTreeMap<Identifier, PublicKey> = identifiers
+            .iter()
+            .map(|identifier| (*identifier, committee.public_key(*identifier as usize - 1)))
+            .collect();
+        for (position, signer) in signers.iter().enumerate() {
+            let identifier = identifiers[position];
+            let mut secret_share = frost::evaluate_polynomial(&coefficients, identifier);
+            if corrupt.contains(&position) {
+                secret_share += Scalar::ONE;
+            }
+            signer.install_key(
+                KeyPackage {
+                    identifier,
+                    secret_share,
+                    group_public_key: EdwardsPoint::mul_base(&group_secret),
+                    verifying_shares: verifying_shares.clone(),
+                    threshold: threshold as u16,
+                },
+                participants.clone(),
+            );
+        }
+    }
+
+    // Deliver every dealing until the committee goes quiet
+    fn run(committee: &mut TestCommittee, signers: &[FrostSigner]) {
+        loop {
+            let sent = committee.sent();
+            if sent.is_empty() {
+                return;
+            }
+            for (recipient, dealing) in sent {
+                signers[recipient].handle(dealing);
+            }
+        }
+    }
+
+    fn signature(session: &mut SigningSession) -> SigningResult {
+        session.result_receiver.try_recv().unwrap()
+    }
+
+    #[test]
+    fn signer_whose_share_fails_is_left_out_of_a_retry() {
+        let mut committee = TestCommittee::new(4, "signing-retry");
+        let group_secret = frost::random_scalar();
+        // The first three to commit include node 1, whose share is wrong
+        let signers = signers(&committee, group_secret, 3, &[1]);
+        let mut session = signers[0].start_signing(b"retry".to_vec());
+        run(&mut committee, &signers);
+
+        let group_signature = signature(&mut session).unwrap();
+        frost::verify_signature(
+            &EdwardsPoint::mul_base(&group_secret),
+            b"retry",
+            &group_signature.signature,
+        )
+        .unwrap();
+        assert_eq!(
+            group_signature.contributors,
+            vec![
+                committee.public_key(0),
+                committee.public_key(2),
+                committee.public_key(3)
+            ]
+        );
+    }
+
+    #[test]
+    fn signing_fails_once_fewer_than_threshold_signers_are_left() {
+        let mut committee = TestCommittee::new(3, "signing-too-few");
+        let signers = signers(&committee, frost::random_scalar(), 3, &[2]);
+        let mut session = signers[0].start_signing(b"too few".to_vec());
+        run(&mut committee, &signers);
+        assert!(matches!(
+            signature(&mut session),
+            Err(FrostError::InvalidShare(3))
+        ));
+    }
+
+    #[test]
+    fn pool_is_refilled_once_it_drops_below_the_low_watermark() {
+        let mut committee = TestCommittee::new(3, "signing-refill");
+        let signers = signers(&committee, frost::random_scalar(), 2, &[]);
+        let lifetime = Duration::from_secs(60);
+        signers[0].refill_presignatures(3, 2, lifetime);
+        run(&mut committee, &signers);
+        assert_eq!(signers[0].presignatures_ready(), 3);
+
+        let mut session = signers[0].start_signing(b"first".to_vec());
+        assert!(session.presigned);
+        run(&mut committee, &signers);
+        assert!(signature(&mut session).is_ok());
+        assert_eq!(signers[0].presignatures_ready(), 2);
+        // Two ready is not below the low watermark
+        signers[0].refill_presignatures(3, 2, lifetime);
+        assert!(committee.sent().is_empty());
+
+        let mut session = signers[0].start_signing(b"second".to_vec());
+        run(&mut committee, &signers);
+        assert!(signature(&mut session).is_ok());
+        signers[0].refill_presignatures(3, 2, lifetime);
+        run(&mut committee, &signers);
+        assert_eq!(signers[0].presignatures_ready(), 3);
+    }
+
+    #[test]
+    fn unused_presignatures_expire() {
+        let mut committee = TestCommittee::new(3, "signing-expiry");
+        let signers = signers(&committee, frost::random_scalar(), 2, &[]);
+        signers[0].refill_presignatures(2, 1, Duration::from_secs(60));
+        run(&mut committee, &signers);
+        assert_eq!(signers[0].presignatures_ready(), 2);
+
+        // The expired presignatures are dropped and replaced
+        signers[0].refill_presignatures(2, 1, Duration::ZERO);
+        assert_eq!(signers[0].presignatures_ready(), 0);
+        run(&mut committee, &signers);
+        assert_eq!(signers[0].presignatures_ready(), 2);
+
+        // Once the signers forgot their nonces a presignature can't be signed with anymore
+        for signer in &signers {
+            signer.expire_nonces(Duration::ZERO);
+        }
+        let mut session = signers[0].start_signing(b"expired".to_vec());
+        assert!(session.presigned);
+        run(&mut committee, &signers);
+        assert!(session.result_receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn installing_another_key_aborts_sessions_and_drops_the_pool() {
+        let mut committee = TestCommittee::new(3, "signing-key-rotated");
+        let group_secret = frost::random_scalar();
+        let signers = signers(&committee, group_secret, 2, &[]);
+        signers[0].refill_presignatures(2, 1, Duration::from_secs(60));
+        run(&mut committee, &signers);
+        assert_eq!(signers[0].presignatures_ready(), 2);
+
+        // A refresh keeps the group key and committee, and so the pool
+        install(&committee, &signers, group_secret, 2, &[]);
+        assert_eq!(signers[0].presignatures_ready(), 2);
+        let mut session = signers[0].start_signing(b"refreshed".to_vec());
+        assert!(session.presigned);
+        run(&mut committee, &signers);
+        assert!(signature(&mut session).is_ok());
+
+        // A session waiting for its signature shares can't complete under another key, and the
+        // presignatures left for the old one are dropped
+        signers[0].refill_presignatures(2, 2, Duration::from_secs(60));
+        run(&mut committee, &signers);
+        let mut session = signers[0].start_signing(b"rotated".to_vec());
+        committee.sent();
+        assert_eq!(signers[0].presignatures_ready(), 1);
+        install(&committee, &signers, frost::random_scalar(), 2, &[]);
+        assert!(matches!(
+            signature(&mut session),
+            Err(FrostError::KeyRotated)
+        ));
+        assert_eq!(signers[0].presignatures_ready(), 0);
+    }
+}
@@ -0,0 +1,144 @@
+// A committee of nodes wired together in memory, for tests
+// Every dealing a node sends lands in a channel that the test drains, so the test decides which
+// dealings are delivered, in which order and whether at all. Nothing verifies signatures on the
+// way, the way sample_grpc does before handing a dealing on.
+
+// System
+use std::fs;
+use std::path::PathBuf;
+
+// Third Party
+use tokio::sync::{broadcast, mpsc};
+
+// Local
+use super::config::NodeConfig;
+use super::node_setup::NodeSetup;
+use super::outbox::Outbox;
+use super::peer::{DealingHistory, Peer, PeerMap};
+use super::types::{PublicKey, RoundId};
+use super::wire::EncodedDealing;
+use crate::sample::Dealing;
+
+pub struct TestNode {
+    pub config: NodeConfig,
+    pub node_setup: NodeSetup,
+    pub peers: PeerMap,
+    pub outbox: Outbox,
+    // What the node sent itself
+    inbound: broadcast::Receiver<Dealing>,
+    // What the node sent every other node, with the recipient's position in the committee
+    outgoing: Vec<(usize, mpsc::Receiver<EncodedDealing>)>,
+}
+
+pub struct TestCommittee {
+    // In committee order, so that the node at position i has FROST identifier i + 1
+    pub nodes: Vec<TestNode>,
+    directory: PathBuf,
+}
+
+impl TestCommittee {
+    // Identities and key shares are kept in a directory named after the test, so tests running in
+    // parallel don't share files
+    pub fn new(size: usize, name: &str) -> Self {
+        let directory =
+            std::env::temp_dir().join(format!("tokio-sample-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        let mut node_setups: Vec<NodeSetup> = (0..size)
+            .map(|index| {
+                NodeSetup::new(&directory.join(format!("{}.identity.bin", index))).unwrap()
+            })
+            .collect();
+        node_setups.sort_by(|a, b| a.public_key.cmp(&b.public_key));
+
+        let mut nodes = Vec::new();
+        for (position, node_setup) in node_setups.iter().enumerate() {
+            let mut config = NodeConfig::new(size as u32, format!("node-{}", position));
+            config.identity_path = directory.join(format!("{}.identity.bin", position));
+            config.key_path = directory.join(format!("{}.group_key.bin", position));
+            let peers = PeerMap::new();
+            let mut outgoing = Vec::new();
+            for (recipient, recipient_setup) in node_setups.iter().enumerate() {
+                // Dealings to myself go through the inbound channel
+                let client_dealing_sender = (recipient != position).then(|| {
+                    let (sender, receiver) = mpsc::channel(10_000);
+                    outgoing.push((recipient, receiver));
+                    sender
+                });
+                peers.add_peer(
+                    Peer {
+                        address: format!("node-{}", recipient),
+                        public_key: recipient_setup.public_key.clone(),
+                        server_dealing_sender: None,
+                        client_dealing_sender,
+                        random_dealings: DealingHistory::shared(config.peer_history_rounds),
+                    },
+                    size as u32,
+                );
+            }
+            let (inbound_dealing_sender, inbound) = broadcast::channel(10_000);
+            let outbox = Outbox::new(peers.clone(), node_setup.clone(), inbound_dealing_sender);
+            nodes.push(TestNode {
+                config,
+                node_setup: node_setup.clone(),
+                peers,
+                outbox,
+                inbound,
+                outgoing,
+            });
+        }
+        Self { nodes, directory }
+    }
+
+    pub fn public_key(&self, position: usize) -> PublicKey {
+        self.nodes[position].node_setup.public_key.clone()
+    }
+
+    // A random dealing with the given payload, sealed by the dealer to the recipient and signed,
+    // without sending it anywhere
+    pub fn random_dealing(
+        &self,
+        dealer: usize,
+        recipient: usize,
+        round_id: &RoundId,
+        payload: &[u8],
+    ) -> Dealing {
+        let node_setup = &self.nodes[dealer].node_setup;
+        let recipient = self.public_key(recipient);
+        let mut dealing = Dealing {
+            round_id: round_id.clone(),
+            public_key: node_setup.public_key.clone(),
+            recipient: recipient.clone(),
+            ..Default::default()
+        };
+        dealing.dealing = node_setup
+            .sealer
+            .seal(&recipient, &dealing, payload)
+            .unwrap()
+            .into();
+        node_setup.sign_dealing(&mut dealing);
+        dealing
+    }
+
+    // Every dealing sent since the last call, with the position of the node it was sent to
+    pub fn sent(&mut self) -> Vec<(usize, Dealing)> {
+        let mut sent = Vec::new();
+        for (position, node) in self.nodes.iter_mut().enumerate() {
+            while let Ok(dealing) = node.inbound.try_recv() {
+                sent.push((position, dealing));
+            }
+            for (recipient, receiver) in node.outgoing.iter_mut() {
+                while let Ok(encoded) = receiver.try_recv() {
+                    sent.push((*recipient, encoded.into_dealing().unwrap()));
+                }
+            }
+        }
+        sent
+    }
+}
+
+impl Drop for TestCommittee {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.directory);
+    }
+}
@@ -0,0 +1,342 @@
+// Counts the bytes this node writes to ReceiveDealings streams, before and after compression
+// Raw bytes are counted as dealing batches are handed to a stream and wire bytes as the frames
+// leave for the peer. Both include the five byte header gRPC puts in front of every message, so
+// without compression the two are the same.
+// tonic answers with the first encoding a peer lists in grpc-accept-encoding, whichever encoding the
+// server was told to send, and every node lists gzip first. So the server puts its own encoding
+// first when the peer accepts it, or COMPRESSION=zstd would send gzip.
+
+// System
+use std::convert::Infallible;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+// Third Party
+use bytes::{Buf, Bytes};
+use tonic::body::BoxBody;
+use tonic::codec::CompressionEncoding;
+use tonic::codegen::{http, Body, BoxFuture, Service};
+use tonic::server::NamedService;
+use tonic::transport::channel::ResponseFuture;
+use tonic::transport::Channel;
+use tonic::Status;
+
+const RECEIVE_DEALINGS_PATH: &str = "/sample.Sample/ReceiveDealings";
+const GRPC_HEADER_BYTES: usize = 5;
+const ACCEPT_ENCODING_HEADER: &str = "grpc-accept-encoding";
+
+#[derive(Default)]
+pub struct TrafficStats {
+    raw_bytes_sent: AtomicU64,
+    wire_bytes_sent: AtomicU64,
+}
+
+impl TrafficStats {
+    // Record a message of encoded_len bytes handed to a dealing stream
+    pub fn record_raw(&self, encoded_len: usize) {
+        self.raw_bytes_sent
+            .fetch_add((encoded_len + GRPC_HEADER_BYTES) as u64, Ordering::Relaxed);
+    }
+
+    pub fn raw_bytes_sent(&self) -> u64 {
+        self.raw_bytes_sent.load(Ordering::Relaxed)
+    }
+
+    pub fn wire_bytes_sent(&self) -> u64 {
+        self.wire_bytes_sent.load(Ordering::Relaxed)
+    }
+}
+
+// A request or response body that counts the bytes read from it
+struct CountingBody {
+    inner: BoxBody,
+    stats: Arc<TrafficStats>,
+}
+
+impl CountingBody {
+    fn boxed(inner: BoxBody, stats: Arc<TrafficStats>) -> BoxBody {
+        CountingBody { inner, stats }.boxed_unsync()
+    }
+}
+
+impl Body for CountingBody {
+    type Data = Bytes;
+    type Error = Status;
+
+    fn poll_data(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        let poll = Pin::new(&mut self.inner).poll_data(cx);
+        if let Poll::Ready(Some(Ok(data))) = &poll {
+            self.stats
+                .wire_bytes_sent
+                .fetch_add(data.remaining() as u64, Ordering::Relaxed);
+        }
+        poll
+    }
+
+    fn poll_trailers(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<http::HeaderMap>, Self::Error>> {
+        Pin::new(&mut self.inner).poll_trailers(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+}
+
+// The channel to a peer, counting what this node writes to its dealing stream as the client
+#[derive(Clone)]
+pub struct MeteredChannel {
+    channel: Channel,
+    stats: Arc<TrafficStats>,
+}
+
+impl MeteredChannel {
+    pub fn new(channel: Channel, stats: Arc<TrafficStats>) -> Self {
+        Self { channel, stats }
+    }
+}
+
+impl Service<http::Request<BoxBody>> for MeteredChannel {
+    type Response = http::Response<tonic::transport::Body>;
+    type Error = tonic::transport::Error;
+    type Future = ResponseFuture;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.channel.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: http::Request<BoxBody>) -> Self::Future {
+        if request.uri().path() != RECEIVE_DEALINGS_PATH {
+            return self.channel.call(request);
+        }
+        let stats = self.stats.clone();
+        self.channel
+            .call(request.map(|body| CountingBody::boxed(body, stats)))
+    }
+}
+
+// The Sample service, counting what this node writes to dealing streams as the server and
+// answering in its own compression where the peer accepts it
+#[derive(Clone)]
+pub struct MeteredServer<S> {
+    inner: S,
+    stats: Arc<TrafficStats>,
+    compression: Option<CompressionEncoding>,
+}
+
+impl<S> MeteredServer<S> {
+    pub fn new(
+        inner: S,
+        stats: Arc<TrafficStats>,
+        compression: Option<CompressionEncoding>,
+    ) -> Self {
+        Self {
+            inner,
+            stats,
+            compression,
+        }
+    }
+}
+
+// List the given encoding first in a request's grpc-accept-encoding if it is there at all
+fn prefer_encoding(headers: &mut http::HeaderMap, compression: CompressionEncoding) {
+    let preferred = match compression {
+        CompressionEncoding::Gzip => "gzip",
+        CompressionEncoding::Zstd => "zstd",
+        _ => return,
+    };
+    let accepted: Vec<String> = match headers
+        .get(ACCEPT_ENCODING_HEADER)
+        .and_then(|value| value.to_str().ok())
+    {
+        Some(value) => value
+            .split(',')
+            .map(|encoding| encoding.trim().to_string())
+            .collect(),
+        None => return,
+    };
+    if !accepted.iter().any(|encoding| encoding == preferred) {
+        return;
+    }
+    let reordered: Vec<&str> = std::iter::once(preferred)
+        .chain(
+            accepted
+                .iter()
+                .map(String::as_str)
+                .filter(|encoding| *encoding != preferred),
+        )
+        .collect();
+    if let Ok(value) = http::HeaderValue::from_str(&reordered.join(",")) {
+        headers.insert(ACCEPT_ENCODING_HEADER, value);
+    }
+}
+
+impl<S> Service<http::Request<tonic::transport::Body>> for MeteredServer<S>
+where
+    S: Service<
+        http::Request<tonic::transport::Body>,
+        Response = http::Response<BoxBody>,
+        Error = Infallible,
+    >,
+    S::Future: Send + 'static,
+{
+    type Response = http::Response<BoxBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: http::Request<tonic::transport::Body>) -> Self::Future {
+        if let Some(compression) = self.compression {
+            prefer_encoding(request.headers_mut(), compression);
+        }
+        let stats = (request.uri().path() == RECEIVE_DEALINGS_PATH).then(|| self.stats.clone());
+        let response = self.inner.call(request);
+        Box::pin(async move {
+            let response = response.await?;
+            Ok(match stats {
+                Some(stats) => response.map(|body| CountingBody::boxed(body, stats)),
+                None => response,
+            })
+        })
+    }
+}
+
+impl<S: NamedService> NamedService for MeteredServer<S> {
+    const NAME: &'static str = S::NAME;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sample::{Dealing, DealingBatch};
+    use crate::wire::EncodedDealing;
+    use prost::Message;
+    use tonic::codec::{ProstCodec, Streaming};
+    use tonic::codegen::BoxStream;
+    use tonic::server::{Grpc, StreamingService};
+
+    // Answers a dealing stream with the same batches every time
+    #[derive(Clone)]
+    struct Batches(Vec<DealingBatch>);
+
+    impl StreamingService<DealingBatch> for Batches {
+        type Response = DealingBatch;
+        type ResponseStream = BoxStream<DealingBatch>;
+        type Future = BoxFuture<tonic::Response<Self::ResponseStream>, Status>;
+
+        fn call(&mut self, _request: tonic::Request<Streaming<DealingBatch>>) -> Self::Future {
+            let batches = self.0.clone();
+            Box::pin(async move {
+                let stream: BoxStream<DealingBatch> =
+                    Box::pin(tokio_stream::iter(batches.into_iter().map(Ok)));
+                Ok(tonic::Response::new(stream))
+            })
+        }
+    }
+
+    // The server half of ReceiveDealings the way the generated Sample server encodes it
+    #[derive(Clone)]
+    struct ReceiveDealings {
+        batches: Batches,
+        compression: Option<CompressionEncoding>,
+    }
+
+    impl Service<http::Request<tonic::transport::Body>> for ReceiveDealings {
+        type Response = http::Response<BoxBody>;
+        type Error = Infallible;
+        type Future = BoxFuture<Self::Response, Self::Error>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, request: http::Request<tonic::transport::Body>) -> Self::Future {
+            let mut grpc = Grpc::new(ProstCodec::<DealingBatch, DealingBatch>::default());
+            if let Some(compression) = self.compression {
+                grpc = grpc.send_compressed(compression);
+            }
+            let batches = self.batches.clone();
+            Box::pin(async move { Ok(grpc.streaming(batches, request).await) })
+        }
+    }
+
+    // Answer a peer that accepts accept_encoding with compressible batches. Returns the response
+    // headers and the bytes written before and after compression.
+    async fn receive_dealings(
+        compression: Option<CompressionEncoding>,
+        accept_encoding: &'static str,
+    ) -> (http::HeaderMap, u64, u64) {
+        let batches: Vec<DealingBatch> = (0..10)
+            .map(|_| DealingBatch {
+                dealings: (0..20)
+                    .map(|_| {
+                        EncodedDealing::new(&Dealing {
+                            dealing: vec![7; 300].into(),
+                            ..Default::default()
+                        })
+                    })
+                    .collect(),
+            })
+            .collect();
+        let stats = Arc::new(TrafficStats::default());
+        for batch in &batches {
+            stats.record_raw(batch.encoded_len());
+        }
+        let mut server = MeteredServer::new(
+            ReceiveDealings {
+                batches: Batches(batches),
+                compression,
+            },
+            stats.clone(),
+            compression,
+        );
+        let request = http::Request::builder()
+            .uri(format!("http://node{}", RECEIVE_DEALINGS_PATH))
+            .header("content-type", "application/grpc")
+            .header(ACCEPT_ENCODING_HEADER, accept_encoding)
+            .body(tonic::transport::Body::empty())
+            .unwrap();
+        let (parts, mut body) = server.call(request).await.unwrap().into_parts();
+        while let Some(data) = body.data().await {
+            data.unwrap();
+        }
+        (
+            parts.headers,
+            stats.raw_bytes_sent(),
+            stats.wire_bytes_sent(),
+        )
+    }
+
+    #[tokio::test]
+    async fn wire_bytes_match_raw_bytes_without_compression() {
+        let (_, raw, wire) = receive_dealings(None, "gzip,zstd").await;
+        assert!(raw > 0);
+        assert_eq!(wire, raw);
+    }
+
+    #[tokio::test]
+    async fn wire_bytes_count_what_compression_left() {
+        let (_, raw, wire) = receive_dealings(Some(CompressionEncoding::Gzip), "gzip,zstd").await;
+        assert!(wire > 0);
+        assert!(wire * 10 < raw, "{} wire bytes for {} raw bytes", wire, raw);
+    }
+
+    #[tokio::test]
+    async fn server_answers_in_its_own_compression_when_the_peer_accepts_it() {
+        let (headers, _, _) = receive_dealings(Some(CompressionEncoding::Zstd), "gzip,zstd").await;
+        assert_eq!(headers["grpc-encoding"], "zstd");
+        // A peer that doesn't accept zstd is answered the way it asked
+        let (headers, _, _) = receive_dealings(Some(CompressionEncoding::Zstd), "gzip").await;
+        assert_eq!(headers["grpc-encoding"], "gzip");
+    }
+}
@@ -1,4 +1,8 @@
-pub type ProtocolRoundIndex = usize;
-pub type PublicKey = Vec<u8>;
-pub type DealingValue = Vec<u8>;
+// Third Party
+use bytes::Bytes;
+
+// Shared rather than copied, like the fields of a Dealing
+pub type PublicKey = Bytes;
 pub type NodeIndex = u32;
+// SHA-256 of the committee, session nonce and dealing index. See session::round_id.
+pub type RoundId = Bytes;
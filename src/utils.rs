@@ -1,7 +1,11 @@
 use std::collections::HashSet;
-use std::fs::OpenOptions;
+use std::fs::{self, OpenOptions};
 use std::hash::Hash;
 use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+
+use bytes::Bytes;
 
 pub fn has_unique_elements<T>(iter: T) -> bool
 where
@@ -23,3 +27,38 @@ pub fn debug_line_to_file(line: &str, filename: &str) {
         Err(error) => println!("Failed to write to {} with error {:?}", filename, error),
     };
 }
+
+// Lowercase hex, for printing round IDs and keys
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// The inverse of hex, for keys given in the environment
+pub fn unhex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) || !hex.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|start| u8::from_str_radix(&hex[start..start + 2], 16).ok())
+        .collect()
+}
+
+// Public keys or round IDs as the byte strings of a gRPC message
+pub fn to_vecs<'a>(items: impl IntoIterator<Item = &'a Bytes>) -> Vec<Vec<u8>> {
+    items.into_iter().map(|item| item.to_vec()).collect()
+}
+
+// Write a secret to a file only this user can read. The file is replaced atomically.
+pub fn write_private(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let temporary_path = path.with_extension("tmp");
+    let _ = fs::remove_file(&temporary_path);
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(&temporary_path)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    fs::rename(&temporary_path, path)
+}
@@ -0,0 +1,251 @@
+// Feldman verifiable secret sharing
+// A dealer shares a random secret with a degree threshold - 1 polynomial, publishes a commitment
+// to every coefficient and gives participant i the share f(i). Anyone holding a share can check it
+// against the commitments without learning anything about the other shares.
+
+// Third Party
+use curve25519_dalek::edwards::EdwardsPoint;
+use curve25519_dalek::scalar::Scalar;
+use curve25519_dalek::traits::{Identity, VartimeMultiscalarMul};
+use prost::Message;
+
+// Local
+use super::frost::{self, FrostError, Identifier};
+use super::types::PublicKey;
+use crate::sample::{VssDealing, VssShare};
+
+// A node's share of the joint secret produced by one round, which is the sum of every included
+// dealer's secret
+#[derive(Clone)]
+pub struct JointShare {
+    pub secret_share: Scalar,
+    // The joint secret times the base point
+    pub public_key: EdwardsPoint,
+    // The sum of every included dealer's coefficient commitments, which commit to the joint
+    // secret's sharing polynomial
+    pub commitments: Vec<EdwardsPoint>,
+    pub dealers: usize,
+}
+
+// Share a fresh random secret among participants 1..=participants
+pub fn deal(participants: u16, threshold: u16) -> VssDealing {
+    deal_secret(frost::random_scalar(), participants, threshold)
+}
+
+// Share a given secret among participants 1..=participants
+pub fn deal_secret(secret: Scalar, participants: u16, threshold: u16) -> VssDealing {
+    let coefficients: Vec<Scalar> = std::iter::once(secret)
+        .chain((1..threshold).map(|_| frost::random_scalar()))
+        .collect();
+    VssDealing {
+        commitments: coefficients
+            .iter()
+            .map(|coefficient| frost::serialize_point(&EdwardsPoint::mul_base(coefficient)))
+            .collect(),
+        shares: (1..=participants)
+            .map(|identifier| VssShare {
+                identifier: u32::from(identifier),
+                share: frost::serialize_scalar(&frost::evaluate_polynomial(
+                    &coefficients,
+                    identifier,
+                )),
+            })
+            .collect(),
+        padding: Vec::new(),
+    }
+}
+
+// The part of a dealing meant for one participant: the commitments and that participant's share
+pub fn for_recipient(dealing: &VssDealing, identifier: Identifier) -> Option<VssDealing> {
+    let share = dealing
+        .shares
+        .iter()
+        .find(|share| share.identifier == u32::from(identifier))?;
+    Some(VssDealing {
+        commitments: dealing.commitments.clone(),
+        shares: vec![share.clone()],
+        padding: Vec::new(),
+    })
+}
+
+// The encoded part of a dealing meant for a committee member, padded to at least size bytes
+pub fn payload_for(
+    dealing: &VssDealing,
+    committee: &[PublicKey],
+    recipient: &PublicKey,
+    size: usize,
+) -> Option<Vec<u8>> {
+    let position = committee.iter().position(|member| member == recipient)?;
+    let mut part = for_recipient(dealing, (position + 1) as Identifier)?;
+    let length = part.encoded_len();
+    if length < size {
+        part.padding = vec![0; size - length];
+    }
+    Some(part.encode_to_vec())
+}
+
+// Decode the part of a dealing sent to me and check that it has the expected threshold and that my
+// share matches the dealer's commitments
+pub fn verify(payload: &[u8], identifier: Identifier, threshold: usize) -> Result<(), FrostError> {
+    let dealing = VssDealing::decode(payload).map_err(|_| FrostError::InvalidPayload)?;
+    verify_dealing(&dealing, identifier, threshold)?;
+    Ok(())
+}
+
+// Check a decoded dealing the same way and return its coefficient commitments and my share
+pub fn verify_dealing(
+    dealing: &VssDealing,
+    identifier: Identifier,
+    threshold: usize,
+) -> Result<(Vec<EdwardsPoint>, Scalar), FrostError> {
+    if dealing.commitments.len() != threshold || dealing.shares.len() != 1 {
+        return Err(FrostError::InvalidPayload);
+    }
+    let commitments: Vec<EdwardsPoint> = dealing
+        .commitments
+        .iter()
+        .map(|commitment| frost::deserialize_point(commitment))
+        .collect::<Result<_, _>>()?;
+    let share = my_share(dealing, identifier)?;
+    if EdwardsPoint::mul_base(&share) != evaluate_commitments(&commitments, identifier) {
+        return Err(FrostError::InvalidShare(identifier));
+    }
+    Ok((commitments, share))
+}
+
+// Add up my shares of every dealer's secret. The dealings must already have been verified.
+pub fn combine<'a>(
+    identifier: Identifier,
+    payloads: impl Iterator<Item = &'a [u8]>,
+) -> Result<JointShare, FrostError> {
+    let mut joint_share = JointShare {
+        secret_share: Scalar::ZERO,
+        public_key: EdwardsPoint::identity(),
+        commitments: Vec::new(),
+        dealers: 0,
+    };
+    for payload in payloads {
+        let dealing = VssDealing::decode(payload).map_err(|_| FrostError::InvalidPayload)?;
+        let commitments: Vec<EdwardsPoint> = dealing
+            .commitments
+            .iter()
+            .map(|commitment| frost::deserialize_point(commitment))
+            .collect::<Result<_, _>>()?;
+        if commitments.is_empty()
+            || (joint_share.dealers > 0 && commitments.len() != joint_share.commitments.len())
+        {
+            return Err(FrostError::InvalidPayload);
+        }
+        joint_share.secret_share += my_share(&dealing, identifier)?;
+        joint_share.public_key += commitments[0];
+        if joint_share.commitments.is_empty() {
+            joint_share.commitments = commitments;
+        } else {
+            for (sum, commitment) in joint_share.commitments.iter_mut().zip(commitments) {
+                *sum += commitment;
+            }
+        }
+        joint_share.dealers += 1;
+    }
+    Ok(joint_share)
+}
+
+fn my_share(dealing: &VssDealing, identifier: Identifier) -> Result<Scalar, FrostError> {
+    let share = dealing
+        .shares
+        .iter()
+        .find(|share| share.identifier == u32::from(identifier))
+        .ok_or(FrostError::InvalidShare(identifier))?;
+    frost::deserialize_scalar(&share.share)
+}
+
+// The commitment to f(identifier), computed from the coefficient commitments
+pub fn evaluate_commitments(commitments: &[EdwardsPoint], identifier: Identifier) -> EdwardsPoint {
+    let x = frost::identifier_scalar(identifier);
+    let powers: Vec<Scalar> = commitments
+        .iter()
+        .scan(Scalar::ONE, |power, _| {
+            let current = *power;
+            *power *= x;
+            Some(current)
+        })
+        .collect();
+    EdwardsPoint::vartime_multiscalar_mul(powers, commitments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn committee(size: u8) -> Vec<PublicKey> {
+        (0..size)
+            .map(|member| PublicKey::from(vec![member]))
+            .collect()
+    }
+
+    #[test]
+    fn every_member_verifies_its_part() {
+        let committee = committee(5);
+        let dealing = deal(5, 3);
+        for (identifier, member) in (1..).zip(&committee) {
+            let payload = payload_for(&dealing, &committee, member, 1000).unwrap();
+            assert!(payload.len() >= 1000);
+            verify(&payload, identifier, 3).unwrap();
+        }
+        assert!(payload_for(&dealing, &committee, &PublicKey::from(vec![9]), 0).is_none());
+    }
+
+    #[test]
+    fn rejects_a_bad_share_or_threshold() {
+        let mut dealing = for_recipient(&deal(5, 3), 2).unwrap();
+        assert!(matches!(
+            verify_dealing(&dealing, 2, 4),
+            Err(FrostError::InvalidPayload)
+        ));
+        dealing.shares[0].share = frost::serialize_scalar(&frost::random_scalar());
+        assert!(matches!(
+            verify(&dealing.encode_to_vec(), 2, 3),
+            Err(FrostError::InvalidShare(2))
+        ));
+    }
+
+    #[test]
+    fn combined_shares_interpolate_to_the_joint_secret() {
+        let secrets = [frost::random_scalar(), frost::random_scalar()];
+        let dealings: Vec<VssDealing> = secrets
+            .iter()
+            .map(|secret| deal_secret(*secret, 4, 2))
+            .collect();
+        let participants = [2, 4];
+        let joint_shares: Vec<JointShare> = participants
+            .iter()
+            .map(|identifier| {
+                let payloads: Vec<Vec<u8>> = dealings
+                    .iter()
+                    .map(|dealing| for_recipient(dealing, *identifier).unwrap().encode_to_vec())
+                    .collect();
+                combine(*identifier, payloads.iter().map(Vec::as_slice)).unwrap()
+            })
+            .collect();
+        let joint_secret: Scalar = secrets.iter().sum();
+        let interpolated: Scalar = participants
+            .iter()
+            .zip(&joint_shares)
+            .map(|(identifier, joint_share)| {
+                frost::lagrange_coefficient(*identifier, &participants) * joint_share.secret_share
+            })
+            .sum();
+        assert_eq!(interpolated, joint_secret);
+        for (identifier, joint_share) in participants.iter().zip(&joint_shares) {
+            assert_eq!(joint_share.dealers, 2);
+            assert_eq!(
+                joint_share.public_key,
+                EdwardsPoint::mul_base(&joint_secret)
+            );
+            assert_eq!(
+                evaluate_commitments(&joint_share.commitments, *identifier),
+                EdwardsPoint::mul_base(&joint_share.secret_share)
+            );
+        }
+    }
+}
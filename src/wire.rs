@@ -0,0 +1,140 @@
+// Dealings as they travel over the ReceiveDealings streams
+// prost encodes a message every time it is written to a stream, so a dealing broadcast to n peers
+// used to be encoded n - 1 times. A broadcast now encodes the dealing once into shared bytes and
+// every peer stream writes those bytes as they are. Inbound dealings are decoded as usual.
+
+// Third Party
+use bytes::{Buf, BufMut, Bytes};
+use prost::encoding::{DecodeContext, WireType};
+use prost::{DecodeError, Message};
+
+// Local
+use crate::sample::Dealing;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum EncodedDealing {
+    // A dealing encoded once, to be written to any number of streams
+    Encoded(Bytes),
+    // A dealing that still has to be encoded, or one that was just decoded
+    Decoded(Dealing),
+}
+
+impl EncodedDealing {
+    pub fn new(dealing: &Dealing) -> Self {
+        EncodedDealing::Encoded(dealing.encode_to_vec().into())
+    }
+
+    pub fn into_dealing(self) -> Result<Dealing, DecodeError> {
+        match self {
+            EncodedDealing::Encoded(bytes) => Dealing::decode(bytes),
+            EncodedDealing::Decoded(dealing) => Ok(dealing),
+        }
+    }
+}
+
+impl Default for EncodedDealing {
+    fn default() -> Self {
+        EncodedDealing::Decoded(Dealing::default())
+    }
+}
+
+impl From<Dealing> for EncodedDealing {
+    fn from(dealing: Dealing) -> Self {
+        EncodedDealing::Decoded(dealing)
+    }
+}
+
+// Encodes exactly like a Dealing, so the other side of a stream can't tell the two apart
+impl Message for EncodedDealing {
+    fn encode_raw<B>(&self, buf: &mut B)
+    where
+        B: BufMut,
+    {
+        match self {
+            EncodedDealing::Encoded(bytes) => buf.put_slice(bytes),
+            EncodedDealing::Decoded(dealing) => dealing.encode_raw(buf),
+        }
+    }
+
+    fn merge_field<B>(
+        &mut self,
+        tag: u32,
+        wire_type: WireType,
+        buf: &mut B,
+        ctx: DecodeContext,
+    ) -> Result<(), DecodeError>
+    where
+        B: Buf,
+    {
+        if let EncodedDealing::Encoded(bytes) = self {
+            *self = EncodedDealing::Decoded(Dealing::decode(bytes.clone())?);
+        }
+        match self {
+            EncodedDealing::Decoded(dealing) => dealing.merge_field(tag, wire_type, buf, ctx),
+            EncodedDealing::Encoded(_) => unreachable!(),
+        }
+    }
+
+    fn encoded_len(&self) -> usize {
+        match self {
+            EncodedDealing::Encoded(bytes) => bytes.len(),
+            EncodedDealing::Decoded(dealing) => dealing.encoded_len(),
+        }
+    }
+
+    fn clear(&mut self) {
+        *self = EncodedDealing::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sample::DealingKind;
+
+    fn dealing(index: u32) -> Dealing {
+        Dealing {
+            dealing: vec![7; 300].into(),
+            protocol_round: index,
+            public_key: vec![1; 32].into(),
+            signature: vec![2; 64].into(),
+            round_id: vec![3; 8].into(),
+            kind: DealingKind::FrostCommitment as i32,
+            recipient: vec![4; 32].into(),
+        }
+    }
+
+    #[test]
+    fn encodes_like_a_dealing() {
+        let dealing = dealing(1);
+        let encoded = EncodedDealing::new(&dealing);
+        assert_eq!(encoded.encode_to_vec(), dealing.encode_to_vec());
+        assert_eq!(
+            EncodedDealing::from(dealing.clone()).encode_to_vec(),
+            dealing.encode_to_vec()
+        );
+        assert_eq!(encoded.into_dealing().unwrap(), dealing);
+    }
+
+    #[test]
+    fn batches_encode_alike_and_decode() {
+        use crate::sample::DealingBatch;
+
+        let dealings: Vec<Dealing> = (0..3).map(dealing).collect();
+        let encoded = DealingBatch {
+            dealings: dealings.iter().map(EncodedDealing::new).collect(),
+        };
+        let decoded = DealingBatch {
+            dealings: dealings.iter().cloned().map(EncodedDealing::from).collect(),
+        };
+        let bytes = encoded.encode_to_vec();
+        assert_eq!(bytes, decoded.encode_to_vec());
+        let batch = DealingBatch::decode(&bytes[..]).unwrap();
+        let received: Vec<Dealing> = batch
+            .dealings
+            .into_iter()
+            .map(|dealing| dealing.into_dealing().unwrap())
+            .collect();
+        assert_eq!(received, dealings);
+    }
+}